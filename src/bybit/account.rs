@@ -10,17 +10,100 @@ use futures::Future;
 use reqwest::Method;
 use serde_json::Value;
 
-use crate::endpoints::v5account;
+use crate::{endpoints::v5account, errors::app_error::AppError, helpers::de};
 
 use super::{
     http_manager::{HttpManager, Manager},
+    response,
+    types::{AccountType, Number},
     Result,
 };
 
+/// One coin of a wallet as returned by get_wallet_balance_typed.
+/// Bybit leaves fields that don't apply to the account type as "", which become None.
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WalletCoin {
+    pub coin: String,
+    #[serde(default, deserialize_with = "de::string_as_option_number")]
+    pub equity: Option<Number>,
+    #[serde(default, deserialize_with = "de::string_as_number")]
+    pub wallet_balance: Number,
+    /// Empty for unified accounts, use get_coin_balance of the asset module there.
+    #[serde(default, deserialize_with = "de::string_as_option_number")]
+    pub available_to_withdraw: Option<Number>,
+    #[serde(default, deserialize_with = "de::string_as_option_number")]
+    pub unrealised_pnl: Option<Number>,
+    #[serde(default, deserialize_with = "de::string_as_option_number")]
+    pub cum_realised_pnl: Option<Number>,
+    #[serde(default, deserialize_with = "de::string_as_option_number")]
+    pub locked: Option<Number>,
+    #[serde(default, deserialize_with = "de::string_as_option_number")]
+    pub usd_value: Option<Number>,
+}
+
+/// Balance of one account as returned by get_wallet_balance_typed.
+/// The account wide totals are in USD and only set for unified accounts.
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WalletBalance {
+    pub account_type: String,
+    #[serde(default, deserialize_with = "de::string_as_option_number")]
+    pub total_equity: Option<Number>,
+    #[serde(default, deserialize_with = "de::string_as_option_number")]
+    pub total_wallet_balance: Option<Number>,
+    #[serde(default, deserialize_with = "de::string_as_option_number")]
+    pub total_margin_balance: Option<Number>,
+    #[serde(default, deserialize_with = "de::string_as_option_number")]
+    pub total_available_balance: Option<Number>,
+    #[serde(default, deserialize_with = "de::string_as_option_number")]
+    pub total_initial_margin: Option<Number>,
+    #[serde(default, deserialize_with = "de::string_as_option_number")]
+    pub total_maintenance_margin: Option<Number>,
+    #[serde(default)]
+    pub coin: Vec<WalletCoin>,
+}
+
+impl WalletBalance {
+    pub fn get(&self, coin: &str) -> Option<&WalletCoin> {
+        self.coin.iter().find(|balance| balance.coin == coin)
+    }
+}
+
+/// Margin mode configuration of the account as returned by get_account_info.
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountInfo {
+    /// 1: classic account, 3: UTA1.0, 4: UTA1.0 Pro, 5: UTA2.0, 6: UTA2.0 Pro
+    pub unified_margin_status: u8,
+    /// ISOLATED_MARGIN, REGULAR_MARGIN, PORTFOLIO_MARGIN
+    pub margin_mode: String,
+    /// ON, OFF. Unified accounts only
+    #[serde(default)]
+    pub spot_hedging_status: String,
+    #[serde(default)]
+    pub updated_time: String,
+}
+
+impl AccountInfo {
+    pub fn is_unified(&self) -> bool {
+        self.unified_margin_status >= 3
+    }
+
+    pub fn spot_hedging_enabled(&self) -> bool {
+        self.spot_hedging_status == "ON"
+    }
+}
+
 #[async_trait]
 pub trait Account {
     fn new(http_manager: Arc<HttpManager>) -> Self;
     async fn get_wallet_balance(&self, query: HashMap<String, String>) -> Result<Value>;
+    async fn get_wallet_balance_typed(
+        &self,
+        account_type: AccountType,
+        coins: &[&str],
+    ) -> Result<WalletBalance>;
 
     async fn upgrade_to_unified_trading_account(
         &self,
@@ -36,9 +119,14 @@ pub trait Account {
     async fn get_fee_rates(&self, query: HashMap<String, String>) -> Result<Value>;
 
     async fn get_account_info(&self, query: HashMap<String, String>) -> Result<Value>;
+    async fn get_account_info_typed(&self) -> Result<AccountInfo>;
     async fn get_transaction_log(&self, query: HashMap<String, String>) -> Result<Value>;
     async fn set_margin_mode(&self, query: HashMap<String, String>) -> Result<Value>;
 
+    async fn get_spot_hedging_status(&self) -> Result<bool>;
+
+    async fn set_spot_hedging(&self, on: bool) -> Result<bool>;
+
     async fn set_mmp(&self, query: HashMap<String, String>) -> Result<Value>;
 
     async fn reset_mmp(&self, query: HashMap<String, String>) -> Result<Value>;
@@ -83,6 +171,33 @@ impl Account for AccountHTTP {
         Ok(result)
     }
 
+    /// Same as get_wallet_balance, typed. `coins` narrows the breakdown, empty for every coin.
+
+    ///     Required args:
+    ///         account_type (AccountType): Unified, Contract or Spot
+
+    ///     Returns:
+    ///         WalletBalance
+
+    ///     Additional information:
+    ///         https://bybit-exchange.github.io/docs/v5/account/wallet-balance
+    async fn get_wallet_balance_typed(
+        &self,
+        account_type: AccountType,
+        coins: &[&str],
+    ) -> Result<WalletBalance> {
+        let mut query = HashMap::new();
+        query.insert("accountType".to_string(), account_type.to_string());
+        if !coins.is_empty() {
+            query.insert("coin".to_string(), coins.join(","));
+        }
+        let balances: Vec<WalletBalance> =
+            response::into_list(self.get_wallet_balance(query).await?)?;
+        balances.into_iter().next().ok_or_else(|| {
+            AppError::InvalidParameter(format!("no {} wallet on this account", account_type))
+        })
+    }
+
     /// Upgrade Unified Account
 
     ///     Returns:
@@ -182,6 +297,17 @@ impl Account for AccountHTTP {
         Ok(result)
     }
 
+    /// Same as get_account_info, with the response typed.
+
+    ///     Returns:
+    ///         AccountInfo
+
+    ///     Additional information:
+    ///         https://bybit-exchange.github.io/docs/v5/account/account-info
+    async fn get_account_info_typed(&self) -> Result<AccountInfo> {
+        response::into_result(self.get_account_info(HashMap::new()).await?)
+    }
+
     /// Query transaction logs in Unified account.
 
     ///     Returns:
@@ -217,6 +343,46 @@ impl Account for AccountHTTP {
         Ok(result)
     }
 
+    /// Whether spot hedging is turned on. Unified accounts only.
+
+    ///     Returns:
+    ///         true when spotHedgingStatus is ON
+
+    ///     Additional information:
+    ///         https://bybit-exchange.github.io/docs/v5/account/account-info
+    async fn get_spot_hedging_status(&self) -> Result<bool> {
+        let info = self.get_account_info_typed().await?;
+        if !info.is_unified() {
+            return Err(AppError::InvalidParameter(
+                "spot hedging is only available for unified accounts".to_string(),
+            ));
+        }
+        Ok(info.spot_hedging_enabled())
+    }
+
+    /// Turn spot hedging on or off. Unified accounts only, checked before sending.
+
+    ///     Returns:
+    ///         The spot hedging status read back after the change.
+
+    ///     Additional information:
+    ///         https://bybit-exchange.github.io/docs/v5/account/set-spot-hedge
+    async fn set_spot_hedging(&self, on: bool) -> Result<bool> {
+        self.get_spot_hedging_status().await?;
+
+        let mut query = HashMap::new();
+        let mode = if on { "ON" } else { "OFF" };
+        query.insert("setHedgingMode".to_string(), mode.to_string());
+        let endpoint = v5account::Account::SetHedgingMode.to_string();
+        let result = self
+            .http_manager
+            .submit_post_request(Method::POST, &endpoint, true, query)
+            .await?;
+        response::check_ret_code(&result)?;
+
+        self.get_spot_hedging_status().await
+    }
+
     /// Market Maker Protection (MMP) is an automated mechanism designed to protect market makers (MM) against liquidity risks
     ///     and over-exposure in the market. It prevents simultaneous trade executions on quotes provided by the MM within a short time span.
     ///     The MM can automatically pull their quotes if the number of contracts traded for an underlying asset exceeds the configured
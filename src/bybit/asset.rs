@@ -10,13 +10,364 @@ use futures::Future;
 use reqwest::Method;
 use serde_json::Value;
 
-use crate::endpoints::v5asset;
+use crate::{
+    endpoints::v5asset,
+    errors::app_error::AppError,
+    helpers::{de, utils},
+};
 
 use super::{
     http_manager::{HttpManager, Manager},
+    response,
+    types::{format_number, AccountType, Number},
     Result,
 };
 
+/// Balance of one coin in one account type, as returned by get_coin_balance.
+/// `transfer_balance` is what can be moved out right now, the rest of
+/// `wallet_balance` is locked (orders, positions, borrowing).
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CoinBalance {
+    pub coin: String,
+    #[serde(deserialize_with = "de::string_as_number")]
+    pub wallet_balance: Number,
+    #[serde(deserialize_with = "de::string_as_number")]
+    pub transfer_balance: Number,
+    #[serde(default, deserialize_with = "de::string_as_number")]
+    pub bonus: Number,
+}
+
+impl CoinBalance {
+    pub fn locked(&self) -> Number {
+        (self.wallet_balance - self.transfer_balance).max(Number::default())
+    }
+}
+
+#[derive(serde_derive::Deserialize)]
+struct CoinBalanceResult {
+    balance: CoinBalance,
+}
+
+///
+/// Parameters of create_internal_transfer_typed. `new` generates the transferId, so
+/// submitting the same request twice (a retry, a clone) is executed once by Bybit.
+/// Set it with `transfer_id` to make it idempotent across restarts.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransferRequest {
+    pub transfer_id: String,
+    pub coin: String,
+    pub amount: Number,
+    pub from_account_type: AccountType,
+    pub to_account_type: AccountType,
+}
+
+impl TransferRequest {
+    pub fn new(
+        coin: &str,
+        amount: Number,
+        from_account_type: AccountType,
+        to_account_type: AccountType,
+    ) -> Self {
+        TransferRequest {
+            transfer_id: utils::generate_uuid(),
+            coin: coin.to_string(),
+            amount,
+            from_account_type,
+            to_account_type,
+        }
+    }
+
+    /// A UUID chosen by the caller.
+    pub fn transfer_id(mut self, transfer_id: &str) -> Self {
+        self.transfer_id = transfer_id.to_string();
+        self
+    }
+}
+
+impl From<TransferRequest> for HashMap<String, String> {
+    fn from(request: TransferRequest) -> Self {
+        vec![
+            ("transferId", request.transfer_id),
+            ("coin", request.coin),
+            ("amount", format_number(request.amount)),
+            ("fromAccountType", request.from_account_type.to_string()),
+            ("toAccountType", request.to_account_type.to_string()),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value))
+        .collect()
+    }
+}
+
+/// Result of create_internal_transfer_typed, `status` is SUCCESS, PENDING or FAILED.
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct TransferResponse {
+    pub transfer_id: String,
+    #[serde(default)]
+    pub status: String,
+}
+
+///
+/// Query of get_transfer_history. Without a time range Bybit returns the last 7 days,
+/// a range may span 7 days at most.
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TransferHistoryRequest {
+    pub transfer_id: Option<String>,
+    pub coin: Option<String>,
+    /// SUCCESS, FAILED or PENDING.
+    pub status: Option<String>,
+    pub start_time: Option<u64>,
+    pub end_time: Option<u64>,
+    /// Page size, at most 50. get_transfer_history follows the cursor over every page.
+    pub limit: Option<u32>,
+}
+
+impl TransferHistoryRequest {
+    pub fn new() -> Self {
+        TransferHistoryRequest::default()
+    }
+
+    pub fn transfer_id(mut self, transfer_id: &str) -> Self {
+        self.transfer_id = Some(transfer_id.to_string());
+        self
+    }
+
+    pub fn coin(mut self, coin: &str) -> Self {
+        self.coin = Some(coin.to_string());
+        self
+    }
+
+    pub fn status(mut self, status: &str) -> Self {
+        self.status = Some(status.to_string());
+        self
+    }
+
+    pub fn start_time(mut self, start_time: u64) -> Self {
+        self.start_time = Some(start_time);
+        self
+    }
+
+    pub fn end_time(mut self, end_time: u64) -> Self {
+        self.end_time = Some(end_time);
+        self
+    }
+
+    pub fn limit(mut self, limit: u32) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+}
+
+impl From<TransferHistoryRequest> for HashMap<String, String> {
+    fn from(request: TransferHistoryRequest) -> Self {
+        let fields = vec![
+            ("transferId", request.transfer_id),
+            ("coin", request.coin),
+            ("status", request.status),
+            ("startTime", request.start_time.map(|time| time.to_string())),
+            ("endTime", request.end_time.map(|time| time.to_string())),
+            ("limit", request.limit.map(|limit| limit.to_string())),
+        ];
+        fields
+            .into_iter()
+            .filter_map(|(key, value)| Some((key.to_string(), value?)))
+            .collect()
+    }
+}
+
+///
+/// Parameters of withdraw_typed. The replay protection timestamp is added when sending.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct WithdrawRequest {
+    pub coin: String,
+    /// Chain name, e.g. ETH or TRX. Not needed with force_chain 2.
+    pub chain: Option<String>,
+    /// Wallet address, or the UID with force_chain 2.
+    pub address: String,
+    /// Memo / destination tag, required for chains that use one (XRP, XLM, EOS).
+    pub tag: Option<String>,
+    pub amount: Number,
+    /// 0 (default): an address belonging to a Bybit user is credited internally,
+    /// 1: always withdraw on-chain, 2: withdraw to a UID of the address book.
+    pub force_chain: Option<u8>,
+    /// Account the coin is taken from, FUND by default. UNIFIED takes from the unified account.
+    pub account_type: Option<AccountType>,
+}
+
+impl WithdrawRequest {
+    pub fn new(coin: &str, chain: &str, address: &str, amount: Number) -> Self {
+        WithdrawRequest {
+            coin: coin.to_string(),
+            chain: Some(chain.to_string()),
+            address: address.to_string(),
+            tag: None,
+            amount,
+            force_chain: None,
+            account_type: None,
+        }
+    }
+
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.tag = Some(tag.to_string());
+        self
+    }
+
+    pub fn force_chain(mut self, force_chain: u8) -> Self {
+        self.force_chain = Some(force_chain);
+        self
+    }
+
+    pub fn account_type(mut self, account_type: AccountType) -> Self {
+        self.account_type = Some(account_type);
+        self
+    }
+}
+
+impl From<WithdrawRequest> for HashMap<String, String> {
+    fn from(request: WithdrawRequest) -> Self {
+        let fields = vec![
+            ("coin", Some(request.coin)),
+            ("chain", request.chain),
+            ("address", Some(request.address)),
+            ("tag", request.tag),
+            ("amount", Some(format_number(request.amount))),
+            (
+                "forceChain",
+                request.force_chain.map(|force| force.to_string()),
+            ),
+            (
+                "accountType",
+                request.account_type.map(|account| account.to_string()),
+            ),
+        ];
+        fields
+            .into_iter()
+            .filter_map(|(key, value)| Some((key.to_string(), value?)))
+            .collect()
+    }
+}
+
+/// Id of a withdrawal created by withdraw_typed, see get_withdrawal_records.
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct WithdrawResponse {
+    pub id: String,
+}
+
+/// One chain a coin can be deposited or withdrawn on, see CoinInfo.
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ChainInfo {
+    /// Chain name to pass as `chain` to withdraw, e.g. ETH.
+    pub chain: String,
+    /// Display name, e.g. "ERC20".
+    pub chain_type: String,
+    /// Flat fee per withdrawal, None when withdrawals are not supported.
+    #[serde(default, deserialize_with = "de::string_as_option_number")]
+    pub withdraw_fee: Option<Number>,
+    /// Extra fee as a fraction of the amount, charged on top of withdraw_fee.
+    #[serde(default, deserialize_with = "de::string_as_option_number")]
+    pub withdraw_percentage_fee: Option<Number>,
+    #[serde(default, deserialize_with = "de::string_as_option_number")]
+    pub withdraw_min: Option<Number>,
+    #[serde(default, deserialize_with = "de::string_as_option_number")]
+    pub deposit_min: Option<Number>,
+    /// Decimals of the withdraw amount.
+    #[serde(default, deserialize_with = "de::string_as_option_u64")]
+    pub min_accuracy: Option<u64>,
+    #[serde(default, deserialize_with = "de::string_as_option_u64")]
+    pub confirmation: Option<u64>,
+    #[serde(default, deserialize_with = "string_as_flag")]
+    pub chain_deposit: bool,
+    #[serde(default, deserialize_with = "string_as_flag")]
+    pub chain_withdraw: bool,
+}
+
+/// A coin and its chains, as returned by get_coin_info_typed.
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CoinInfo {
+    pub coin: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub chains: Vec<ChainInfo>,
+}
+
+impl CoinInfo {
+    ///
+    /// Withdrawable chain with the lowest flat fee, e.g. to pick the network of a
+    /// withdrawal. The percentage fee is not taken into account.
+    ///
+    pub fn cheapest_withdraw_chain(&self) -> Option<&ChainInfo> {
+        self.chains
+            .iter()
+            .filter(|chain| chain.chain_withdraw)
+            .filter_map(|chain| chain.withdraw_fee.map(|fee| (fee, chain)))
+            .min_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(_, chain)| chain)
+    }
+}
+
+/// Deposit address of a coin on one chain, see get_master_deposit_address_typed.
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DepositAddress {
+    #[serde(default)]
+    pub coin: String,
+    pub chain: String,
+    #[serde(default)]
+    pub chain_type: String,
+    #[serde(rename = "addressDeposit")]
+    pub address: String,
+    /// Memo / tag to send along, empty for chains without one.
+    #[serde(rename = "tagDeposit", default)]
+    pub tag: String,
+    #[serde(default)]
+    pub contract_address: String,
+}
+
+#[derive(serde_derive::Deserialize)]
+struct DepositAddressResult {
+    coin: String,
+    chains: Vec<DepositAddress>,
+}
+
+/// Deserializes Bybit's "0" / "1" flags.
+fn string_as_flag<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<bool, D::Error> {
+    let flag: Value = serde::Deserialize::deserialize(deserializer)?;
+    Ok(match flag {
+        Value::String(flag) => flag == "1",
+        Value::Number(flag) => flag.as_u64() == Some(1),
+        Value::Bool(flag) => flag,
+        _ => false,
+    })
+}
+
+/// retCode of a key without the permission an endpoint requires.
+const PERMISSION_DENIED: i32 = 10005;
+
+/// One internal transfer of get_transfer_history.
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TransferRecord {
+    pub transfer_id: String,
+    pub coin: String,
+    #[serde(deserialize_with = "de::string_as_number")]
+    pub amount: Number,
+    pub from_account_type: AccountType,
+    pub to_account_type: AccountType,
+    #[serde(deserialize_with = "de::string_as_u64")]
+    pub timestamp: u64,
+    pub status: String,
+}
+
 #[async_trait]
 pub trait Asset {
     fn new(http_manager: Arc<HttpManager>) -> Self;
@@ -32,11 +383,23 @@ pub trait Asset {
 
     async fn get_coin_balance(&self, query: HashMap<String, String>) -> Result<Value>;
 
+    async fn get_coin_balance_typed(&self, account_type: &str, coin: &str) -> Result<CoinBalance>;
+
     async fn get_transferable_coin(&self, query: HashMap<String, String>) -> Result<Value>;
     async fn create_internal_transfer(&self, query: HashMap<String, String>) -> Result<Value>;
 
+    async fn create_internal_transfer_typed(
+        &self,
+        request: TransferRequest,
+    ) -> Result<TransferResponse>;
+
     async fn get_internal_transfer_records(&self, query: HashMap<String, String>) -> Result<Value>;
 
+    async fn get_transfer_history(
+        &self,
+        request: TransferHistoryRequest,
+    ) -> Result<Vec<TransferRecord>>;
+
     async fn get_sub_uid(&self, query: HashMap<String, String>) -> Result<Value>;
 
     async fn enable_universal_transfer_for_sub_uid(
@@ -61,16 +424,26 @@ pub trait Asset {
 
     async fn get_master_deposit_address(&self, query: HashMap<String, String>) -> Result<Value>;
 
+    async fn get_master_deposit_address_typed(
+        &self,
+        coin: &str,
+        chain: &str,
+    ) -> Result<DepositAddress>;
+
     async fn get_sub_deposit_address(&self, query: HashMap<String, String>) -> Result<Value>;
 
     async fn get_coin_info(&self, query: HashMap<String, String>) -> Result<Value>;
 
+    async fn get_coin_info_typed(&self, coin: Option<&str>) -> Result<Vec<CoinInfo>>;
+
     async fn get_withdrawal_records(&self, query: HashMap<String, String>) -> Result<Value>;
 
     async fn get_withdrawable_amount(&self, query: HashMap<String, String>) -> Result<Value>;
 
     async fn withdraw(&self, query: HashMap<String, String>) -> Result<Value>;
 
+    async fn withdraw_typed(&self, request: WithdrawRequest) -> Result<WithdrawResponse>;
+
     async fn cancel_withdrawal(&self, query: HashMap<String, String>) -> Result<Value>;
 }
 
@@ -213,6 +586,25 @@ impl Asset for AssetHTTP {
             .await
     }
 
+    /// Same as get_coin_balance, with the balance typed.
+
+    ///     Required args:
+    ///         account_type (string): Account type. UNIFIED, CONTRACT, SPOT, FUND...
+    ///         coin (string): Coin name
+
+    ///     Returns:
+    ///         CoinBalance with the wallet, transferable and bonus amounts.
+
+    ///     Additional information:
+    ///         https://bybit-exchange.github.io/docs/v5/asset/account-coin-balance
+    async fn get_coin_balance_typed(&self, account_type: &str, coin: &str) -> Result<CoinBalance> {
+        let mut query = HashMap::new();
+        query.insert("accountType".to_string(), account_type.to_string());
+        query.insert("coin".to_string(), coin.to_string());
+        let result: CoinBalanceResult = response::into_result(self.get_coin_balance(query).await?)?;
+        Ok(result.balance)
+    }
+
     /// Query the transferable coin list between each account type
 
     ///     Required args:
@@ -260,6 +652,16 @@ impl Asset for AssetHTTP {
             .await
     }
 
+    ///
+    /// create_internal_transfer from a TransferRequest, e.g. FUND to UNIFIED.
+    ///
+    async fn create_internal_transfer_typed(
+        &self,
+        request: TransferRequest,
+    ) -> Result<TransferResponse> {
+        response::into_result(self.create_internal_transfer(request.into()).await?)
+    }
+
     /// Query the internal transfer records between different account types under the same UID.
 
     ///     Returns:
@@ -278,6 +680,23 @@ impl Asset for AssetHTTP {
             .await
     }
 
+    ///
+    /// Every page of get_internal_transfer_records matching `request`.
+    ///
+    async fn get_transfer_history(
+        &self,
+        request: TransferHistoryRequest,
+    ) -> Result<Vec<TransferRecord>> {
+        let items = response::all_pages(request.into(), |query| {
+            self.get_internal_transfer_records(query)
+        })
+        .await?;
+        Ok(items
+            .into_iter()
+            .map(serde_json::from_value)
+            .collect::<std::result::Result<_, _>>()?)
+    }
+
     /// Query the sub UIDs under a main UID
 
     ///     Returns:
@@ -486,6 +905,31 @@ impl Asset for AssetHTTP {
             .await
     }
 
+    ///
+    /// Deposit address of `coin` on `chain` (e.g. ETH) of the master account.
+    /// Fails with `AppError::InvalidParameter` when the coin has no address on the chain.
+    ///
+    async fn get_master_deposit_address_typed(
+        &self,
+        coin: &str,
+        chain: &str,
+    ) -> Result<DepositAddress> {
+        let mut query = HashMap::new();
+        query.insert("coin".to_string(), coin.to_string());
+        query.insert("chainType".to_string(), chain.to_string());
+        let result: DepositAddressResult =
+            response::into_result(self.get_master_deposit_address(query).await?)?;
+        let mut address = result
+            .chains
+            .into_iter()
+            .find(|address| address.chain == chain)
+            .ok_or_else(|| {
+                AppError::InvalidParameter(format!("no {} deposit address on {}", coin, chain))
+            })?;
+        address.coin = result.coin;
+        Ok(address)
+    }
+
     /// Query the deposit address information of SUB account.
 
     ///     Required args:
@@ -526,6 +970,18 @@ impl Asset for AssetHTTP {
             .await
     }
 
+    ///
+    /// get_coin_info of one coin, or of every coin with None, with the fees and
+    /// limits of each chain.
+    ///
+    async fn get_coin_info_typed(&self, coin: Option<&str>) -> Result<Vec<CoinInfo>> {
+        let mut query = HashMap::new();
+        if let Some(coin) = coin {
+            query.insert("coin".to_string(), coin.to_string());
+        }
+        response::into_list(self.get_coin_info(query).await?)
+    }
+
     /// Query withdrawal records.
 
     ///     Returns:
@@ -590,6 +1046,31 @@ impl Asset for AssetHTTP {
             .await
     }
 
+    ///
+    /// withdraw from a WithdrawRequest, stamped with the current (clock offset
+    /// corrected) time and signed like every private endpoint.
+    ///
+    /// Additional information:
+    ///     Requires a manager with an api key and secret, otherwise fails with
+    ///     `AppError::MissingCredentials` before sending. A key without the Withdraw
+    ///     permission fails with `AppError::Api { ret_code: 10005, .. }`.
+    ///
+    async fn withdraw_typed(&self, request: WithdrawRequest) -> Result<WithdrawResponse> {
+        let mut query: HashMap<String, String> = request.into();
+        query.insert(
+            "timestamp".to_string(),
+            self.http_manager.timestamp()?.to_string(),
+        );
+        let result = self.withdraw(query).await.and_then(response::into_result);
+        result.map_err(|err| match err {
+            AppError::Api { ret_code, ret_msg } if ret_code == PERMISSION_DENIED => AppError::Api {
+                ret_code,
+                ret_msg: format!("api key lacks the Withdraw permission: {}", ret_msg),
+            },
+            err => err,
+        })
+    }
+
     /// Cancel the withdrawal
 
     ///     Required args:
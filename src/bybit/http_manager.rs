@@ -1,24 +1,238 @@
 #![allow(unused)]
 use async_trait::async_trait;
+use futures::Future;
 use http::method;
 use reqwest::{header, Method};
 use ring::hmac;
-use serde::Serialize;
+use serde::{de::DeserializeOwned, Serialize};
 use serde_json::{json, to_string, Value};
 use std::{
-    collections::{BTreeMap, HashMap},
-    sync::Arc,
-    time::{SystemTime, UNIX_EPOCH},
+    collections::{hash_map::RandomState, BTreeMap, HashMap, HashSet},
+    hash::{BuildHasher, Hasher},
+    sync::{
+        atomic::{AtomicI64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 use url::form_urlencoded::{self, Serializer};
 
 use sha2::Sha256;
 
-use crate::helpers::utils;
+use crate::{
+    endpoints::{limits, v5market},
+    errors::app_error::{AppError, REQUEST_EXPIRED},
+    helpers::utils,
+};
+
+use super::{
+    market::ServerTime,
+    proxy::Proxy,
+    response,
+    signer::{HmacSigner, Signer},
+    types::Environment,
+};
 
-type Error = Box<dyn std::error::Error + Send + Sync>;
+use super::Error;
 pub type HTTPManagerResult<T> = std::result::Result<T, Error>;
 
+/// How a proxy of the pool set with `HttpManager::with_proxies` is picked per request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProxySelection {
+    RoundRobin,
+    Random,
+}
+
+///
+/// Retry policy of HttpManager for transient failures: connection errors, the HTTP
+/// statuses and the retCodes listed here. Anything else fails on the first attempt.
+/// Retry `n` waits base_delay * 2^n capped at max_delay, jittered down by up to half
+/// so that concurrent clients don't retry in lockstep.
+/// The default makes 3 attempts on 5xx, 10006 (rate limit) and 10016 (server error).
+///
+/// A POST that timed out or got a 5xx may still have been executed, so it's only
+/// sent again when it carries an idempotency key Bybit deduplicates on (orderLinkId,
+/// requestId, transferId, on every entry of a batch), or with `retry_non_idempotent`.
+/// Otherwise the error is returned as is. Connection failures and the retCodes are
+/// retried for every request, Bybit never got or rejected those.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryConfig {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    pub retry_statuses: Vec<u16>,
+    pub retry_codes: Vec<i64>,
+    /// Also resend POSTs without an idempotency key on timeouts and retry_statuses.
+    pub retry_non_idempotent: bool,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        RetryConfig {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(250),
+            max_delay: Duration::from_secs(5),
+            retry_statuses: vec![500, 502, 503, 504],
+            retry_codes: vec![10006, 10016],
+            retry_non_idempotent: false,
+        }
+    }
+}
+
+impl RetryConfig {
+    ///
+    /// No retries, every request is attempted once.
+    ///
+    pub fn disabled() -> Self {
+        RetryConfig {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    ///
+    /// Jittered delay before retry number `retry` (starting at 0), or None once
+    /// `max_attempts` have been used.
+    ///
+    pub fn delay(&self, retry: u32) -> Option<Duration> {
+        if retry + 1 >= self.max_attempts {
+            return None;
+        }
+        let delay = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_delay);
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u32(retry);
+        let jitter = 0.5 + (hasher.finish() % 1000) as f64 / 2000.0;
+        Some(delay.mul_f64(jitter))
+    }
+}
+
+///
+/// Client side rate limits of HttpManager::with_rate_limiter, in requests per second
+/// per endpoint group (see limits::rate_limit_group). Groups without an override use
+/// the documented limit, limits::default_rate_limit. Each group is a token bucket
+/// holding one second worth of requests, so a burst up to the limit goes out at once
+/// and the requests after it are spaced out by sleeping instead of failing.
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RateLimiterConfig {
+    pub overrides: HashMap<String, u32>,
+}
+
+impl RateLimiterConfig {
+    /// Requests per second allowed for `group`, e.g. `("order", 5)`.
+    pub fn with_group(mut self, group: &str, per_second: u32) -> Self {
+        self.overrides.insert(group.to_string(), per_second);
+        self
+    }
+
+    pub fn limit(&self, group: &str) -> u32 {
+        self.overrides
+            .get(group)
+            .copied()
+            .unwrap_or_else(|| limits::default_rate_limit(group))
+    }
+}
+
+/// Token bucket of one endpoint group. Tokens go negative when requests are waiting.
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    per_second: f64,
+    updated: std::time::Instant,
+}
+
+impl TokenBucket {
+    fn new(per_second: u32) -> Self {
+        let per_second = per_second.max(1) as f64;
+        TokenBucket {
+            tokens: per_second,
+            per_second,
+            updated: std::time::Instant::now(),
+        }
+    }
+
+    ///
+    /// Takes a token, returning how long to wait before the request may be sent.
+    ///
+    fn reserve(&mut self) -> Duration {
+        let now = std::time::Instant::now();
+        let refill = now.duration_since(self.updated).as_secs_f64() * self.per_second;
+        self.tokens = (self.tokens + refill).min(self.per_second);
+        self.updated = now;
+        self.tokens -= 1.0;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.per_second)
+        }
+    }
+}
+
+///
+/// A parsed response with its HTTP metadata, see HttpManager::submit_request_full.
+/// `latency` is the round trip of the attempt that produced it, from sending the
+/// request to the end of the body, so retries and rate limit waits are not included.
+/// `received_at` is the local time in ms at the end of the body.
+///
+#[derive(Debug, Clone)]
+pub struct Response<T> {
+    pub body: T,
+    pub status: reqwest::StatusCode,
+    pub headers: header::HeaderMap,
+    pub latency: Duration,
+    pub received_at: u64,
+}
+
+impl Response<Value> {
+    /// The `time` of the envelope, server time in ms when the response was made.
+    pub fn server_time(&self) -> Option<u64> {
+        self.body.get("time").and_then(Value::as_u64)
+    }
+
+    ///
+    /// Local receive time minus server_time, in ms: the server to client delay plus
+    /// the clock offset of this machine, which sync_clock measures. Negative when the
+    /// local clock is behind. None without a `time`.
+    ///
+    pub fn server_delay_ms(&self) -> Option<i64> {
+        Some(self.received_at as i64 - self.server_time()? as i64)
+    }
+}
+
+/// Rate limit state of an endpoint, from the X-Bapi-Limit* headers of its last response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitInfo {
+    /// Requests allowed per window.
+    pub limit: u32,
+    /// Requests left in the current window.
+    pub limit_status: u32,
+    /// When the window resets, ms.
+    pub reset_timestamp: u64,
+}
+
+impl RateLimitInfo {
+    fn from_headers(headers: &header::HeaderMap) -> Option<Self> {
+        let number = |name: &str| headers.get(name)?.to_str().ok()?.trim().parse::<u64>().ok();
+        Some(RateLimitInfo {
+            limit: number("X-Bapi-Limit")? as u32,
+            limit_status: number("X-Bapi-Limit-Status")? as u32,
+            reset_timestamp: number("X-Bapi-Limit-Reset-Timestamp")?,
+        })
+    }
+}
+
+/// Callback receiving the endpoint path and the parsed body of every successful response.
+pub type AfterReceive = Arc<dyn Fn(&str, &mut Value) + Send + Sync>;
+
+///
+/// Request submission of the endpoint clients. HttpManager sends requests to Bybit,
+/// test_util::MockManager answers them with canned responses; MarketHTTP takes any
+/// `Arc<dyn Manager + Send + Sync>`, see MarketHTTP::with_manager.
+///
 #[async_trait]
 pub trait Manager {
     async fn auth(
@@ -43,44 +257,1072 @@ pub trait Manager {
         self.submit_request(method, path, query, true).await
     }
 
+    ///
+    /// submit_request taking borrowed pairs, `&[("category", "linear"), ("symbol", "BTCUSDT")]`,
+    /// for callers whose parameters are literals or borrowed already and would only
+    /// build a HashMap to pass them. HttpManager sends them as they are; this default
+    /// copies them into a HashMap.
+    ///
+    async fn submit_request_pairs(
+        &self,
+        method: Method,
+        path: &str,
+        query: &[(&str, &str)],
+        auth: bool,
+    ) -> HTTPManagerResult<Value> {
+        let query = query
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        self.submit_request(method, path, query, auth).await
+    }
+
     async fn submit_post_request<T: Serialize + Send>(
         &self,
         method: Method,
         path: &str,
         auth: bool,
         json_input: T,
-    ) -> HTTPManagerResult<Value>;
+    ) -> HTTPManagerResult<Value>
+    where
+        Self: Sized;
+}
+
+///
+/// Step by step construction of an HttpManager, for setups beyond `HttpManager::new`:
+///
+/// ```text
+/// let manager = HttpManager::builder()
+///     .api_key(&api_key)
+///     .api_secret(&api_secret)
+///     .testnet()
+///     .timeout(Duration::from_secs(5))
+///     .retry(RetryConfig::disabled())
+///     .build()?;
+/// ```
+///
+/// Without a key and secret the manager only serves public endpoints. Unset options
+/// keep the HttpManager defaults.
+///
+#[derive(Default)]
+pub struct HttpManagerBuilder {
+    api_key: Option<String>,
+    api_secret: Option<String>,
+    signer: Option<Arc<dyn Signer>>,
+    environment: Environment,
+    timeout: Option<Duration>,
+    recv_window: Option<u64>,
+    retry: Option<RetryConfig>,
+    rate_limiter: Option<RateLimiterConfig>,
+    proxy: Option<Proxy>,
+    client: Option<reqwest::Client>,
 }
+
+impl HttpManagerBuilder {
+    pub fn new() -> Self {
+        HttpManagerBuilder::default()
+    }
+
+    pub fn api_key(mut self, api_key: &str) -> Self {
+        self.api_key = Some(api_key.to_string());
+        self
+    }
+
+    pub fn api_secret(mut self, api_secret: &str) -> Self {
+        self.api_secret = Some(api_secret.to_string());
+        self
+    }
+
+    /// In place of api_key and api_secret, see `HttpManager::with_signer`.
+    pub fn signer(mut self, signer: Arc<dyn Signer>) -> Self {
+        self.signer = Some(signer);
+        self
+    }
+
+    /// Mainnet unless set, see also `testnet`.
+    pub fn environment(mut self, environment: Environment) -> Self {
+        self.environment = environment;
+        self
+    }
+
+    pub fn testnet(self) -> Self {
+        self.environment(Environment::Testnet)
+    }
+
+    /// See `HttpManager::with_timeout`.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// See `HttpManager::with_recv_window`.
+    pub fn recv_window(mut self, recv_window: u64) -> Self {
+        self.recv_window = Some(recv_window);
+        self
+    }
+
+    /// See `HttpManager::with_retry`.
+    pub fn retry(mut self, retry: RetryConfig) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// See `HttpManager::with_rate_limiter`.
+    pub fn rate_limiter(mut self, config: RateLimiterConfig) -> Self {
+        self.rate_limiter = Some(config);
+        self
+    }
+
+    /// See `HttpManager::with_proxy`.
+    pub fn proxy(mut self, proxy: Proxy) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// See `HttpManager::with_client`.
+    pub fn client(mut self, client: reqwest::Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    ///
+    /// Fails with `AppError::MissingCredentials` when only one of the api key and
+    /// secret is set, or one is empty, and with `AppError::InvalidParameter` for a
+    /// signer next to them, or a zero timeout or recv window.
+    ///
+    pub fn build(self) -> super::Result<Arc<HttpManager>> {
+        if self.signer.is_some() && (self.api_key.is_some() || self.api_secret.is_some()) {
+            return Err(AppError::InvalidParameter(
+                "set either a signer or an api key and secret".to_string(),
+            ));
+        }
+        let (api_key, api_secret) = match (self.api_key, self.api_secret) {
+            (None, None) => (String::new(), String::new()),
+            (Some(api_key), Some(api_secret)) if !api_key.is_empty() && !api_secret.is_empty() => {
+                (api_key, api_secret)
+            }
+            _ => return Err(AppError::MissingCredentials),
+        };
+        if self.timeout == Some(Duration::ZERO) {
+            return Err(AppError::InvalidParameter(
+                "timeout must be above zero".to_string(),
+            ));
+        }
+        if self.recv_window == Some(0) {
+            return Err(AppError::InvalidParameter(
+                "recv_window must be above zero".to_string(),
+            ));
+        }
+
+        let mut manager = match self.client {
+            Some(client) => HttpManager::with_client(client, api_key, api_secret, self.environment),
+            None => HttpManager::new(api_key, api_secret, self.environment),
+        };
+        if let Some(signer) = self.signer {
+            manager = manager.with_signer(signer);
+        }
+        if let Some(timeout) = self.timeout {
+            manager = manager.with_timeout(timeout);
+        }
+        if let Some(recv_window) = self.recv_window {
+            manager = manager.with_recv_window(recv_window);
+        }
+        if let Some(retry) = self.retry {
+            manager = manager.with_retry(retry);
+        }
+        if let Some(config) = self.rate_limiter {
+            manager = manager.with_rate_limiter(config);
+        }
+        if let Some(proxy) = &self.proxy {
+            manager = manager.with_proxy(proxy);
+        }
+        Ok(Arc::new(manager))
+    }
+}
+
 pub struct HttpManager {
-    pub api_key: String,
-    api_secret: String,
+    /// None without credentials, public endpoints only.
+    signer: Option<Arc<dyn Signer>>,
+    environment: Environment,
     base_url: String,
     recv_window: u64,
     ignore_codes: Vec<u64>,
-    max_retries: u64,
+    retry: RetryConfig,
+    timeout: Duration,
+    rate_limits: Mutex<HashMap<String, RateLimitInfo>>,
+    wait_on_rate_limit: bool,
+    rate_limiter: Option<RateLimiterConfig>,
+    token_buckets: Mutex<HashMap<&'static str, TokenBucket>>,
+    pinned_dns: bool,
+    clamp_limits: bool,
+    /// Shared with the PrivateWebSockets of with_clock_of.
+    clock_offset_ms: Arc<AtomicI64>,
+    retry_expired: bool,
+    numeric_strings: Option<HashSet<String>>,
+    after_receive: Option<AfterReceive>,
+    proxies: Vec<reqwest::Proxy>,
+    proxy_selection: ProxySelection,
+    next_proxy: AtomicUsize,
     client: reqwest::Client,
+    /// Set when the client was passed to with_client, which is then never rebuilt.
+    custom_client: bool,
+    proxy_clients: Vec<reqwest::Client>,
 }
 
 impl HttpManager {
     ///
     ///
-    /// Initializes a new HttpManager instance.
+    /// Initializes a new HttpManager instance for `environment`, an Environment or a
+    /// testnet flag. Requests are signed by a HmacSigner, unless the key or the secret
+    /// is empty, which leaves only the public endpoints.
     ///
     ///
-    pub fn new(api_key: String, api_secret: String, testnet: bool) -> Self {
-        let sub_domain = if testnet { "api-testnet" } else { "api" };
-        let url = format!("https://{}.{}.com", sub_domain, "bybit");
-        let client = reqwest::Client::new();
+    pub fn new(api_key: String, api_secret: String, environment: impl Into<Environment>) -> Self {
+        let mut manager =
+            HttpManager::with_client(reqwest::Client::new(), api_key, api_secret, environment);
+        manager.custom_client = false;
+        manager
+    }
+
+    ///
+    ///
+    /// Initializes a HttpManager sending every request through `client`, e.g. to share
+    /// one connection pool between managers or to use a client configured with
+    /// timeouts, a corporate proxy or custom root certificates.
+    /// The client is used as is: with_pinned_dns has no effect on it, and the clients
+    /// of a with_proxies pool are still built by the manager.
+    ///
+    ///
+    pub fn with_client(
+        client: reqwest::Client,
+        api_key: String,
+        api_secret: String,
+        environment: impl Into<Environment>,
+    ) -> Self {
+        let environment = environment.into();
+        let signer: Option<Arc<dyn Signer>> = if api_key.is_empty() || api_secret.is_empty() {
+            None
+        } else {
+            Some(Arc::new(HmacSigner::new(&api_key, &api_secret)))
+        };
 
         HttpManager {
-            api_key,
-            api_secret,
-            base_url: url,
+            signer,
+            environment,
+            base_url: environment.rest_url().to_string(),
             recv_window: 5000,
             ignore_codes: vec![],
-            max_retries: 10,
+            retry: RetryConfig::default(),
+            timeout: Duration::from_secs(10),
+            rate_limits: Mutex::new(HashMap::new()),
+            wait_on_rate_limit: false,
+            rate_limiter: None,
+            token_buckets: Mutex::new(HashMap::new()),
+            pinned_dns: false,
+            clamp_limits: true,
+            clock_offset_ms: Arc::new(AtomicI64::new(0)),
+            retry_expired: false,
+            numeric_strings: None,
+            after_receive: None,
+            proxies: vec![],
+            proxy_selection: ProxySelection::RoundRobin,
+            next_proxy: AtomicUsize::new(0),
             client,
+            custom_client: true,
+            proxy_clients: vec![],
+        }
+    }
+
+    ///
+    ///
+    /// Signs requests with `signer` instead of the api key and secret, e.g. one that
+    /// asks a KMS, so the secret never enters the process.
+    ///
+    ///
+    pub fn with_signer(mut self, signer: Arc<dyn Signer>) -> Self {
+        self.signer = Some(signer);
+        self
+    }
+
+    /// Api key of the signer, empty without credentials.
+    pub fn api_key(&self) -> &str {
+        self.signer.as_ref().map_or("", |signer| signer.api_key())
+    }
+
+    pub fn builder() -> HttpManagerBuilder {
+        HttpManagerBuilder::new()
+    }
+
+    pub fn environment(&self) -> Environment {
+        self.environment
+    }
+
+    ///
+    ///
+    /// Registers a callback run on every response before it is returned, e.g. to
+    /// log anomalies, assert invariants or strip fields for an audit trail.
+    /// It only sees responses whose retCode is 0, so error responses always reach
+    /// the caller untouched, and it cannot turn a response into an error.
+    ///
+    ///
+    pub fn with_after_receive<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&str, &mut Value) + Send + Sync + 'static,
+    {
+        self.after_receive = Some(Arc::new(callback));
+        self
+    }
+
+    ///
+    ///
+    /// Lowers a `limit` parameter above the endpoint's documented maximum to that
+    /// maximum (logging a warning) instead of letting Bybit reject it with 10001.
+    /// On by default; pass false to send `limit` exactly as given.
+    ///
+    ///
+    pub fn with_limit_clamping(mut self, clamp_limits: bool) -> Self {
+        self.clamp_limits = clamp_limits;
+        self
+    }
+
+    ///
+    /// Applies the limit clamping described on `with_limit_clamping`.
+    ///
+    fn clamp_limit(&self, path: &str, parameters: &mut HashMap<String, String>) {
+        let category = parameters.get("category").map(String::as_str);
+        let clamped = match parameters.get("limit") {
+            Some(limit) => self.clamped_limit(path, category, limit),
+            None => None,
+        };
+        if let Some(clamped) = clamped {
+            parameters.insert("limit".to_string(), clamped);
+        }
+    }
+
+    /// The maximum of `path` when `limit` is above it, None when it can be sent as is.
+    fn clamped_limit(&self, path: &str, category: Option<&str>, limit: &str) -> Option<String> {
+        if !self.clamp_limits {
+            return None;
+        }
+        let max = limits::max_limit(path, category)?;
+        if limit.parse::<u64>().map_or(true, |value| value <= max) {
+            return None;
+        }
+        log::warn!(
+            "{}: limit {} clamped to the maximum of {}",
+            path,
+            limit,
+            max
+        );
+        Some(max.to_string())
+    }
+
+    ///
+    ///
+    /// Converts numeric strings of every response into JSON numbers for users of the
+    /// raw Value API, see `utils::convert_numeric_strings` for what is and isn't converted.
+    /// Off by default as it changes the shape of the Value. `exclude` lists extra
+    /// field names to keep as strings on top of `utils::NUMERIC_STRING_EXCLUSIONS`.
+    /// The typed methods keep working, their number fields accept both forms.
+    ///
+    ///
+    pub fn with_numeric_strings_converted(mut self, exclude: &[&str]) -> Self {
+        self.numeric_strings = Some(
+            utils::NUMERIC_STRING_EXCLUSIONS
+                .iter()
+                .chain(exclude)
+                .map(|key| key.to_string())
+                .collect(),
+        );
+        self
+    }
+
+    ///
+    ///
+    /// Replaces the retry policy, `RetryConfig::disabled()` turns retries off.
+    ///
+    ///
+    pub fn with_retry(mut self, retry: RetryConfig) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    ///
+    ///
+    /// Sets how long a request may take before failing with `AppError::Timeout`,
+    /// 10s by default. Applies to each attempt, not to the whole retry sequence.
+    ///
+    ///
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    ///
+    ///
+    /// Sets how long after its timestamp Bybit still accepts a signed request, in
+    /// milliseconds, 5000 by default.
+    ///
+    ///
+    pub fn with_recv_window(mut self, recv_window: u64) -> Self {
+        self.recv_window = recv_window;
+        self
+    }
+
+    ///
+    ///
+    /// When a signed request is answered with retCode 10002 (request expired), runs
+    /// sync_clock and signs and sends it once more with twice the recv window. If that
+    /// fails the same way the error is `AppError::RequestExpired`, carrying the clock
+    /// offset measured for the retry. Off by default, 10002 bodies are then returned
+    /// as they are.
+    ///
+    ///
+    pub fn with_expired_retry(mut self, retry_expired: bool) -> Self {
+        self.retry_expired = retry_expired;
+        self
+    }
+
+    ///
+    ///
+    /// Rate limit state of an endpoint path (e.g. "/v5/order/create") as of its last
+    /// response, None until it has been called. Only signed endpoints send the headers.
+    ///
+    ///
+    pub fn rate_limit(&self, endpoint: &str) -> Option<RateLimitInfo> {
+        self.rate_limits.lock().unwrap().get(endpoint).copied()
+    }
+
+    ///
+    ///
+    /// When on, a request to an endpoint whose last response left no requests in the
+    /// window first sleeps until the window resets, instead of being rejected and
+    /// counting towards a ban. Off by default.
+    ///
+    ///
+    pub fn with_rate_limit_wait(mut self, wait_on_rate_limit: bool) -> Self {
+        self.wait_on_rate_limit = wait_on_rate_limit;
+        self
+    }
+
+    ///
+    ///
+    /// Throttles requests client side to the per-group limits of `config` before they
+    /// are sent, see RateLimiterConfig. Off by default.
+    ///
+    ///
+    pub fn with_rate_limiter(mut self, config: RateLimiterConfig) -> Self {
+        self.rate_limiter = Some(config);
+        self.token_buckets.lock().unwrap().clear();
+        self
+    }
+
+    ///
+    /// Sleeps until the endpoint group's token bucket has a token for this request.
+    ///
+    async fn acquire_permit(&self, path: &str) {
+        let config = match &self.rate_limiter {
+            Some(config) => config,
+            None => return,
+        };
+        let group = limits::rate_limit_group(path);
+        let wait = self
+            .token_buckets
+            .lock()
+            .unwrap()
+            .entry(group)
+            .or_insert_with(|| TokenBucket::new(config.limit(group)))
+            .reserve();
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    ///
+    /// Sleeps until the endpoint's rate limit window resets if it is used up.
+    ///
+    async fn wait_for_rate_limit(&self, path: &str) -> HTTPManagerResult<()> {
+        if !self.wait_on_rate_limit {
+            return Ok(());
+        }
+        let info = match self.rate_limit(path) {
+            Some(info) if info.limit_status == 0 => info,
+            _ => return Ok(()),
+        };
+        let now = self.timestamp()? as u64;
+        if info.reset_timestamp > now {
+            tokio::time::sleep(Duration::from_millis(info.reset_timestamp - now)).await;
         }
+        Ok(())
+    }
+
+    ///
+    /// Runs `send` under the retry policy and handles the final response body.
+    /// `send` builds and signs the request from scratch, so each attempt gets a
+    /// fresh timestamp.
+    ///
+    async fn execute<F, Fut>(
+        &self,
+        method: &Method,
+        path: &str,
+        resendable: bool,
+        send: F,
+    ) -> HTTPManagerResult<Response<Value>>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = HTTPManagerResult<reqwest::Response>>,
+    {
+        #[cfg(feature = "tracing")]
+        {
+            use tracing::Instrument;
+
+            let span = tracing::debug_span!("bybit_request", %method, path);
+            let started = std::time::Instant::now();
+            let result = self
+                .execute_attempts(path, resendable, send)
+                .instrument(span.clone())
+                .await;
+            let latency_ms = started.elapsed().as_millis() as u64;
+            span.in_scope(|| match &result {
+                Ok(response) => tracing::debug!(
+                    status = response.status.as_u16(),
+                    ret_code = response.body["retCode"].as_i64().unwrap_or(0),
+                    latency_ms,
+                    "request succeeded"
+                ),
+                Err(err) => tracing::warn!(
+                    ret_code = err.ret_code(),
+                    latency_ms,
+                    error = %err,
+                    "request failed"
+                ),
+            });
+            result
+        }
+        #[cfg(not(feature = "tracing"))]
+        {
+            let _ = method;
+            self.execute_attempts(path, resendable, send).await
+        }
+    }
+
+    ///
+    /// The attempts of execute: sends, parses and retries per the retry policy.
+    /// Without `resendable` only the retCodes and connection failures are retried,
+    /// see RetryConfig.
+    ///
+    async fn execute_attempts<F, Fut>(
+        &self,
+        path: &str,
+        resendable: bool,
+        mut send: F,
+    ) -> HTTPManagerResult<Response<Value>>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = HTTPManagerResult<reqwest::Response>>,
+    {
+        let mut retry = 0;
+        // Error of the last attempt when its body wasn't JSON, e.g. a maintenance page.
+        let mut non_json;
+        loop {
+            self.acquire_permit(path).await;
+            self.wait_for_rate_limit(path).await?;
+            let attempt = async {
+                let started = std::time::Instant::now();
+                let response = send().await?;
+                if let Some(info) = RateLimitInfo::from_headers(response.headers()) {
+                    self.rate_limits
+                        .lock()
+                        .unwrap()
+                        .insert(path.to_string(), info);
+                }
+                let status = response.status();
+                let headers = response.headers().clone();
+                let body_text = response.text().await?;
+                let received_at = utils::generate_timestamp()? as u64;
+                Ok::<_, Error>((status, headers, body_text, started.elapsed(), received_at))
+            }
+            .await;
+
+            let failure = match attempt {
+                Ok((status, headers, body_text, latency, received_at)) => {
+                    let body = serde_json::from_str::<Value>(&body_text).ok();
+                    let ret_code = body
+                        .as_ref()
+                        .and_then(|body| body.get("retCode").and_then(Value::as_i64));
+                    let retryable = self.retry.max_attempts > 1
+                        && ((resendable && self.retry.retry_statuses.contains(&status.as_u16()))
+                            || ret_code
+                                .map_or(false, |code| self.retry.retry_codes.contains(&code)));
+                    if !retryable {
+                        return Ok(Response {
+                            body: self.handle_body(path, status, &body_text)?,
+                            status,
+                            headers,
+                            latency,
+                            received_at,
+                        });
+                    }
+                    non_json = match body {
+                        Some(_) => None,
+                        None => Some(non_json_error(path, status, &body_text)),
+                    };
+                    match ret_code {
+                        Some(code) if status.is_success() => format!("retCode {}", code),
+                        _ => format!("HTTP {}", status),
+                    }
+                }
+                Err(err) => {
+                    let transient = match &err {
+                        AppError::Timeout => resendable,
+                        // A failed connect never reached Bybit.
+                        AppError::Http(e) => err.is_transient() && (resendable || e.is_connect()),
+                        _ => false,
+                    };
+                    if !transient || self.retry.max_attempts <= 1 {
+                        return Err(err);
+                    }
+                    non_json = None;
+                    err.to_string()
+                }
+            };
+
+            match self.retry.delay(retry) {
+                Some(delay) => {
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(retry, delay_ms = delay.as_millis() as u64, %failure, "retrying");
+                    tokio::time::sleep(delay).await
+                }
+                None => {
+                    return Err(non_json.unwrap_or(AppError::RetriesExhausted {
+                        retries: retry,
+                        last_error: failure,
+                    }))
+                }
+            }
+            retry += 1;
+        }
+    }
+
+    ///
+    /// Bybit's auth headers for a request carrying `payload`: the query string of a GET
+    /// or the body of a POST, exactly as sent. The signer signs
+    /// `timestamp + api_key + recv_window + payload`.
+    ///
+    async fn signed_headers(
+        &self,
+        payload: &str,
+        recv_window: u64,
+    ) -> HTTPManagerResult<header::HeaderMap> {
+        let signer = self.signer.as_deref().ok_or(AppError::MissingCredentials)?;
+        let timestamp = self.timestamp()?;
+        let val = format!(
+            "{time}{api_key}{recv_window}{params}",
+            time = timestamp,
+            api_key = signer.api_key(),
+            recv_window = recv_window,
+            params = payload,
+        );
+        let signature = signer.sign(&val).await?;
+        Ok(utils::build_private_headers(
+            signer.api_key(),
+            &signature,
+            timestamp,
+            &recv_window.to_string(),
+        ))
+    }
+
+    ///
+    /// Sends `parameters`, sorted by key, as a query string (GET, DELETE) or JSON body
+    /// (POST, PUT). The query string is built once, so the signed string and the one
+    /// sent can't differ.
+    ///
+    async fn send_query(
+        &self,
+        method: &Method,
+        path: &str,
+        parameters: &[(&str, &str)],
+        auth: bool,
+        timeout: Duration,
+        recv_window: u64,
+    ) -> HTTPManagerResult<reqwest::Response> {
+        let request_url = format!("{}{}", self.base_url, path);
+
+        let mut request_builder = match *method {
+            Method::GET | Method::DELETE => {
+                let query_string = serde_urlencoded::to_string(parameters)?;
+                let url = if query_string.is_empty() {
+                    request_url
+                } else {
+                    format!("{}?{}", request_url, query_string)
+                };
+                trace_request(method, &url, "");
+                let request_builder = self.client().request(method.clone(), &url);
+                if auth {
+                    request_builder.headers(self.signed_headers(&query_string, recv_window).await?)
+                } else {
+                    request_builder
+                }
+            }
+            Method::POST | Method::PUT => {
+                let body = serde_json::to_string(&PairsObject(parameters))?;
+                trace_request(method, &request_url, &body);
+                let request_builder = self
+                    .client()
+                    .request(method.clone(), &request_url)
+                    .header(header::CONTENT_TYPE, "application/json");
+                if auth {
+                    request_builder
+                        .headers(self.signed_headers(&body, recv_window).await?)
+                        .body(body)
+                } else {
+                    request_builder.body(body)
+                }
+            }
+            _ => {
+                return Err(AppError::InvalidParameter(format!(
+                    "unsupported HTTP method {}",
+                    method
+                )));
+            }
+        };
+        Ok(request_builder.timeout(timeout).send().await?)
+    }
+
+    ///
+    /// POSTs `json_string` as the body, signed over exactly these bytes.
+    ///
+    async fn send_json(
+        &self,
+        path: &str,
+        json_string: &str,
+        recv_window: u64,
+    ) -> HTTPManagerResult<reqwest::Response> {
+        let request_url = format!("{}{}", self.base_url, path);
+        trace_request(&Method::POST, &request_url, json_string);
+        Ok(self
+            .client()
+            .post(&request_url)
+            .header(header::CONTENT_TYPE, "application/json")
+            .headers(self.signed_headers(json_string, recv_window).await?)
+            .body(json_string.to_string())
+            .timeout(self.timeout)
+            .send()
+            .await?)
+    }
+
+    ///
+    /// submit_request with `result` deserialized into `T`. A non-zero retCode is
+    /// returned as `AppError::Api`, see ApiResponse::from_body.
+    ///
+    pub async fn submit_request_typed<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        query: HashMap<String, String>,
+        auth: bool,
+    ) -> HTTPManagerResult<T> {
+        let body = self.submit_request(method, path, query, auth).await?;
+        Ok(response::ApiResponse::from_body(body)?.result)
+    }
+
+    ///
+    /// submit_request failing with `AppError::Timeout` after `timeout` instead of the
+    /// manager's default, e.g. for a slow history query.
+    ///
+    pub async fn submit_request_with_timeout(
+        &self,
+        method: Method,
+        path: &str,
+        query: HashMap<String, String>,
+        auth: bool,
+        timeout: Duration,
+    ) -> HTTPManagerResult<Value> {
+        Ok(self
+            .submit_full(method, path, query, auth, timeout)
+            .await?
+            .body)
+    }
+
+    ///
+    /// submit_request keeping the HTTP status, the response headers and the latency
+    /// of the request next to the body, e.g. to tell network from server slowness.
+    ///
+    pub async fn submit_request_full(
+        &self,
+        method: Method,
+        path: &str,
+        query: HashMap<String, String>,
+        auth: bool,
+    ) -> HTTPManagerResult<Response<Value>> {
+        self.submit_full(method, path, query, auth, self.timeout)
+            .await
+    }
+
+    async fn submit_full(
+        &self,
+        method: Method,
+        path: &str,
+        mut query: HashMap<String, String>,
+        auth: bool,
+        timeout: Duration,
+    ) -> HTTPManagerResult<Response<Value>> {
+        self.clamp_limit(path, &mut query);
+        let mut pairs: Vec<(&str, &str)> = query
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect();
+        pairs.sort_unstable();
+        self.submit_pairs(method, path, &pairs, auth, timeout).await
+    }
+
+    ///
+    /// Sends pairs sorted by key, retrying once on 10002 with with_expired_retry.
+    ///
+    async fn submit_pairs(
+        &self,
+        method: Method,
+        path: &str,
+        query: &[(&str, &str)],
+        auth: bool,
+        timeout: Duration,
+    ) -> HTTPManagerResult<Response<Value>> {
+        let resendable = self.resendable(&method, || {
+            query
+                .iter()
+                .any(|(key, value)| IDEMPOTENCY_KEYS.contains(key) && !value.is_empty())
+        });
+        let response = self
+            .execute(&method, path, resendable, || {
+                self.send_query(&method, path, query, auth, timeout, self.recv_window)
+            })
+            .await?;
+        if !auth || !self.is_expired(&response.body) {
+            return Ok(response);
+        }
+        let recv_window = self.resync_expired().await?;
+        let response = self
+            .execute(&method, path, resendable, || {
+                self.send_query(&method, path, query, auth, timeout, recv_window)
+            })
+            .await?;
+        self.check_expired(response, recv_window)
+    }
+
+    ///
+    /// Whether a timed out or 5xx request may be sent again: anything but a POST, a
+    /// POST for which `has_key` finds an idempotency key, or any with retry_non_idempotent.
+    ///
+    fn resendable(&self, method: &Method, has_key: impl FnOnce() -> bool) -> bool {
+        *method != Method::POST || self.retry.retry_non_idempotent || has_key()
+    }
+
+    /// Whether `body` is a 10002 that with_expired_retry should retry.
+    fn is_expired(&self, body: &Value) -> bool {
+        self.retry_expired
+            && body.get("retCode").and_then(Value::as_i64) == Some(REQUEST_EXPIRED.into())
+    }
+
+    /// Syncs the clock before retrying a 10002, returns the recv window to retry with.
+    async fn resync_expired(&self) -> HTTPManagerResult<u64> {
+        self.sync_clock().await?;
+        Ok(self.recv_window.saturating_mul(2))
+    }
+
+    fn check_expired(
+        &self,
+        response: Response<Value>,
+        recv_window: u64,
+    ) -> HTTPManagerResult<Response<Value>> {
+        if !self.is_expired(&response.body) {
+            return Ok(response);
+        }
+        Err(AppError::RequestExpired {
+            ret_msg: response.body["retMsg"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+            clock_offset_ms: self.clock_offset_ms(),
+            recv_window,
+        })
+    }
+
+    ///
+    /// Signed request to a private endpoint, the same as submit_request with auth:
+    /// GET and DELETE send `query` as the sorted query string, POST and PUT as a JSON
+    /// body. Fails with `AppError::MissingCredentials` without an api key and secret.
+    ///
+    pub async fn submit_signed_request(
+        &self,
+        method: Method,
+        path: &str,
+        query: HashMap<String, String>,
+    ) -> HTTPManagerResult<Value> {
+        self.submit_request(method, path, query, true).await
+    }
+
+    ///
+    /// Parses a response body and runs the after_receive callback on success.
+    /// A body that isn't JSON is reported by non_json_error. When retries run out
+    /// on one, that error is returned rather than `AppError::RetriesExhausted`.
+    ///
+    fn handle_body(
+        &self,
+        path: &str,
+        status: reqwest::StatusCode,
+        body_text: &str,
+    ) -> HTTPManagerResult<Value> {
+        let mut body: Value = match serde_json::from_str(body_text) {
+            Ok(body) => body,
+            Err(_) => return Err(non_json_error(path, status, body_text)),
+        };
+        if let Some(exclude) = &self.numeric_strings {
+            utils::convert_numeric_strings(&mut body, exclude);
+        }
+        if let Some(callback) = &self.after_receive {
+            if body.get("retCode").and_then(Value::as_i64) == Some(0) {
+                callback(path, &mut body);
+            }
+        }
+        Ok(body)
+    }
+
+    ///
+    ///
+    /// Resolves the Bybit host once and pins the address in the client, skipping
+    /// the DNS lookup for every new connection.
+    /// Trade-off: if Bybit fails over to another IP the pinned address goes stale
+    /// until the manager is rebuilt. Resolution happens here, blocking; when it
+    /// fails the client keeps using regular DNS.
+    ///
+    ///
+    pub fn with_pinned_dns(mut self, pinned_dns: bool) -> Self {
+        self.pinned_dns = pinned_dns;
+        self.rebuild_clients();
+        self
+    }
+
+    ///
+    ///
+    /// Sends requests through a pool of proxies, one picked per request by `selection`,
+    /// spreading the load over several egress IPs.
+    /// Bybit rate limits public endpoints per IP, so the pool raises that ceiling, while
+    /// signed endpoints are limited per UID and gain nothing from it. An API key bound to
+    /// IPs needs every proxy's egress IP whitelisted, otherwise signed requests fail
+    /// depending on the proxy picked. An empty pool goes back to direct connections.
+    ///
+    ///
+    pub fn with_proxies(mut self, proxies: Vec<reqwest::Proxy>, selection: ProxySelection) -> Self {
+        self.proxies = proxies;
+        self.proxy_selection = selection;
+        self.rebuild_clients();
+        self
+    }
+
+    ///
+    /// Sends every request through `proxy`, see Proxy for the supported kinds.
+    /// Shorthand for a pool of one with `with_proxies`.
+    ///
+    pub fn with_proxy(self, proxy: &Proxy) -> Self {
+        self.with_proxies(vec![proxy.to_reqwest()], ProxySelection::RoundRobin)
+    }
+
+    fn rebuild_clients(&mut self) {
+        if !self.custom_client {
+            self.client = self.build_client(None);
+        }
+        self.proxy_clients = self
+            .proxies
+            .iter()
+            .map(|proxy| self.build_client(Some(proxy.clone())))
+            .collect();
+    }
+
+    ///
+    /// Client for the next request, going through the proxy pool when one is set.
+    ///
+    fn client(&self) -> &reqwest::Client {
+        if self.proxy_clients.is_empty() {
+            return &self.client;
+        }
+        let count = self.next_proxy.fetch_add(1, Ordering::Relaxed);
+        let index = match self.proxy_selection {
+            ProxySelection::RoundRobin => count,
+            ProxySelection::Random => {
+                let mut hasher = RandomState::new().build_hasher();
+                hasher.write_usize(count);
+                hasher.finish() as usize
+            }
+        };
+        &self.proxy_clients[index % self.proxy_clients.len()]
+    }
+
+    ///
+    /// Builds the reqwest client from the manager's connection options.
+    ///
+    fn build_client(&self, proxy: Option<reqwest::Proxy>) -> reqwest::Client {
+        let mut builder = reqwest::Client::builder();
+        if let Some(proxy) = proxy {
+            builder = builder.proxy(proxy);
+        }
+        if self.pinned_dns {
+            if let Some(host) = url::Url::parse(&self.base_url)
+                .ok()
+                .and_then(|url| url.host_str().map(str::to_string))
+            {
+                if let Some(addr) = std::net::ToSocketAddrs::to_socket_addrs(&(host.as_str(), 443))
+                    .ok()
+                    .and_then(|mut addrs| addrs.next())
+                {
+                    builder = builder.resolve(&host, addr);
+                }
+            }
+        }
+        builder.build().unwrap_or_default()
+    }
+
+    ///
+    ///
+    /// Measures the offset between the local clock and Bybit's and applies it to the
+    /// timestamp of every signed request from then on, avoiding 10002 errors when the
+    /// local clock drifts outside the recv window. The offset is taken against the
+    /// midpoint of the request's round trip. Returns the offset in ms, server minus local.
+    /// Call it at startup and periodically on long running processes.
+    ///
+    ///
+    pub async fn sync_clock(&self) -> HTTPManagerResult<i64> {
+        let path = v5market::MarketEnum::GetServerTime.to_string();
+        let sent = utils::generate_timestamp()?;
+        let server_time: ServerTime = self
+            .submit_request_typed(Method::GET, &path, HashMap::new(), false)
+            .await?;
+        let received = utils::generate_timestamp()?;
+        let local = (sent + received) / 2;
+        let offset = server_time.as_millis() as i64 - local as i64;
+        self.clock_offset_ms.store(offset, Ordering::Relaxed);
+        Ok(offset)
+    }
+
+    ///
+    /// Offset applied to request timestamps, server minus local, as measured by the
+    /// last sync_clock. 0 until sync_clock is called.
+    ///
+    pub fn clock_offset_ms(&self) -> i64 {
+        self.clock_offset_ms.load(Ordering::Relaxed)
+    }
+
+    ///
+    /// Local time in ms corrected by the measured clock offset.
+    ///
+    pub(crate) fn timestamp(&self) -> HTTPManagerResult<u128> {
+        Ok(utils::generate_timestamp_with_offset(
+            self.clock_offset_ms(),
+        )?)
+    }
+
+    /// The offset sync_clock stores, for signers outside the manager to read.
+    pub(crate) fn shared_clock_offset(&self) -> Arc<AtomicI64> {
+        self.clock_offset_ms.clone()
     }
 
     ///
@@ -111,21 +1353,24 @@ impl Manager for HttpManager {
         recv_window: u64,
         timestamp: u128,
     ) -> Result<String, String> {
-        if self.api_key.is_empty() || self.api_secret.is_empty() {
-            return Err("Authenticated endpoints require keys.".to_string());
-        }
+        let signer = match self.signer.as_deref() {
+            Some(signer) => signer,
+            None => return Err("Authenticated endpoints require keys.".to_string()),
+        };
         let param_string =
             serde_urlencoded::to_string(req_params).map_err(|e| format!("Error: {:?}", e))?;
 
         let val = format!(
             "{time}{api_key}{recv_window}{params}",
             time = timestamp,
-            api_key = self.api_key,
+            api_key = signer.api_key(),
             recv_window = recv_window,
             params = param_string,
         );
-        let sign_result = self.generate_signature(&self.api_secret, &val).await;
-        let sign = sign_result.map_err(|e| format!("Error: {:?}", e))?;
+        let sign = signer
+            .sign(&val)
+            .await
+            .map_err(|e| format!("Error: {:?}", e))?;
         Ok(format!("{}&sign={}", param_string, sign))
     }
 
@@ -146,57 +1391,41 @@ impl Manager for HttpManager {
         parameters: HashMap<String, String>,
         auth: bool,
     ) -> HTTPManagerResult<Value> {
-        let request_url = format!("{}{}", self.base_url, path);
+        self.submit_request_with_timeout(method, path, parameters, auth, self.timeout)
+            .await
+    }
 
-        let mut request_builder = self.client.request(method.clone(), &request_url);
-
-        if auth {
-            let timestamp = utils::generate_timestamp()? as u128;
-            let param_string = serde_urlencoded::to_string(&parameters)?;
-            let val = format!(
-                "{time}{api_key}{recv_window}{params}",
-                time = timestamp,
-                api_key = self.api_key,
-                recv_window = self.recv_window,
-                params = param_string,
-            );
-
-            let signature = self
-                .generate_signature(&self.api_secret, &val)
-                .await
-                .map_err(|e| format!("Error: {:?}", e))?;
-
-            let headers = utils::build_private_headers(
-                &self.api_key,
-                &signature,
-                timestamp,
-                &self.recv_window.to_string(),
-            );
-            request_builder = request_builder.headers(headers);
-        } // do we need to handle the else block if the one is not authenticated?
-
-        let response = match method {
-            Method::GET | Method::DELETE => request_builder
-                .query(&parameters)
-                .send()
-                .await
-                .map_err(|e| format!("Error: {:?}", e))?,
-            Method::POST | Method::PUT => {
-                request_builder = request_builder.header(header::CONTENT_TYPE, "application/json");
-                request_builder.json(&parameters).send().await?
-            }
-            _ => {
-                return Err(Box::new(std::io::Error::new(
-                    std::io::ErrorKind::InvalidInput,
-                    "Unsupported HTTP method",
-                )));
-            }
+    ///
+    /// Sends the pairs without copying them into owned Strings, only the limit is
+    /// replaced when it's clamped. Keys are expected to be unique.
+    ///
+    async fn submit_request_pairs(
+        &self,
+        method: Method,
+        path: &str,
+        query: &[(&str, &str)],
+        auth: bool,
+    ) -> HTTPManagerResult<Value> {
+        let value_of = |name: &str| {
+            query
+                .iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| *value)
         };
-
-        let body_text = response.text().await?;
-        let body: Value = serde_json::from_str(&body_text)?;
-
-        Ok(body)
+        let clamped = value_of("limit")
+            .and_then(|limit| self.clamped_limit(path, value_of("category"), limit));
+        let mut pairs: Vec<(&str, &str)> = query
+            .iter()
+            .map(|&(key, value)| match &clamped {
+                Some(limit) if key == "limit" => (key, limit.as_str()),
+                _ => (key, value),
+            })
+            .collect();
+        pairs.sort_unstable();
+        Ok(self
+            .submit_pairs(method, path, &pairs, auth, self.timeout)
+            .await?
+            .body)
     }
 
     async fn submit_post_request<T: Serialize + Send>(
@@ -206,40 +1435,360 @@ impl Manager for HttpManager {
         auth: bool,
         json_input: T,
     ) -> HTTPManagerResult<Value> {
-        let timestamp = utils::generate_timestamp()? as u128;
-
         let json_string = serde_json::to_string(&json_input)?; // Convert the HashMap into a JSON string.
+        let resendable = self.resendable(&method, || {
+            serde_json::from_str(&json_string).map_or(false, |body| has_idempotency_key(&body))
+        });
+        let response = self
+            .execute(&method, path, resendable, || {
+                self.send_json(path, &json_string, self.recv_window)
+            })
+            .await?;
+        if !self.is_expired(&response.body) {
+            return Ok(response.body);
+        }
+        let recv_window = self.resync_expired().await?;
+        let response = self
+            .execute(&method, path, resendable, || {
+                self.send_json(path, &json_string, recv_window)
+            })
+            .await?;
+        Ok(self.check_expired(response, recv_window)?.body)
+    }
+}
 
-        let val = format!(
-            "{time}{api_key}{recv_window}{params}",
-            time = timestamp,
-            api_key = self.api_key,
-            recv_window = self.recv_window,
-            params = json_string,
-        );
+///
+/// Classifies a response that isn't JSON, which never comes from the API itself:
+/// a 403 is the CDN in front of Bybit (rate abuse, geo-block), `AppError::Blocked`,
+/// a 503 or a page mentioning maintenance `AppError::SystemMaintenance`, anything
+/// else `AppError::UnexpectedResponse`.
+///
+fn non_json_error(path: &str, status: reqwest::StatusCode, body_text: &str) -> AppError {
+    let snippet: String = body_text.trim().chars().take(200).collect();
+    match status {
+        reqwest::StatusCode::FORBIDDEN => AppError::Blocked {
+            status: status.as_u16(),
+            snippet,
+        },
+        reqwest::StatusCode::TOO_MANY_REQUESTS => {
+            AppError::RateLimited(format!("HTTP 429 on {}", path))
+        }
+        _ if status == reqwest::StatusCode::SERVICE_UNAVAILABLE
+            || body_text.to_lowercase().contains("maintenance") =>
+        {
+            AppError::SystemMaintenance(format!(
+                "HTTP {} on {}: {}",
+                status.as_u16(),
+                path,
+                snippet
+            ))
+        }
+        _ => AppError::UnexpectedResponse {
+            status: status.as_u16(),
+            snippet,
+        },
+    }
+}
+
+/// Fields Bybit deduplicates a write on, resending a request carrying one can't execute it twice.
+const IDEMPOTENCY_KEYS: [&str; 3] = ["orderLinkId", "requestId", "transferId"];
+
+///
+/// Whether a POST body carries an idempotency key, on every entry for the batch
+/// endpoints (`{ "category": ..., "request": [...] }`).
+///
+fn has_idempotency_key(body: &Value) -> bool {
+    let has_key = |item: &Value| {
+        IDEMPOTENCY_KEYS.iter().any(|key| {
+            item.get(key)
+                .and_then(Value::as_str)
+                .map_or(false, |value| !value.is_empty())
+        })
+    };
+    match body.get("request").and_then(Value::as_array) {
+        Some(entries) => !entries.is_empty() && entries.iter().all(has_key),
+        None => has_key(body),
+    }
+}
+
+/// Serializes sorted pairs as a JSON object of strings, the body of a POST.
+struct PairsObject<'a>(&'a [(&'a str, &'a str)]);
+
+impl Serialize for PairsObject<'_> {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.iter().map(|(key, value)| (key, value)))
+    }
+}
+
+///
+/// Logs an outgoing request at trace level: the url with its query string and the JSON
+/// body of a POST. The auth headers are never logged, so neither is the signature.
+///
+#[cfg(feature = "tracing")]
+fn trace_request(method: &Method, url: &str, body: &str) {
+    tracing::trace!(%method, url, body, "sending request");
+}
+
+#[cfg(not(feature = "tracing"))]
+fn trace_request(_method: &Method, _url: &str, _body: &str) {}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
 
-        let signature = self
-            .generate_signature(&self.api_secret, &val)
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+
+    /// A raw HTTP/1.1 response closing the connection.
+    fn http(status: &str, content_type: &str, body: &str) -> Option<String> {
+        Some(format!(
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            content_type,
+            body.len(),
+            body
+        ))
+    }
+
+    ///
+    /// Serves `responses` in order, one per connection, on a local port. None never
+    /// answers. Returns the base url and the head of every request received.
+    ///
+    async fn serve(responses: Vec<Option<String>>) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(vec![]));
+        let received = requests.clone();
+        tokio::spawn(async move {
+            for response in responses {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = vec![];
+                let mut buffer = [0; 4096];
+                while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+                    match socket.read(&mut buffer).await {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => request.extend_from_slice(&buffer[..n]),
+                    }
+                }
+                received
+                    .lock()
+                    .unwrap()
+                    .push(String::from_utf8_lossy(&request).to_string());
+                match response {
+                    Some(response) => {
+                        socket.write_all(response.as_bytes()).await.ok();
+                        socket.shutdown().await.ok();
+                    }
+                    None => {
+                        tokio::spawn(async move {
+                            tokio::time::sleep(Duration::from_secs(60)).await;
+                            drop(socket);
+                        });
+                    }
+                }
+            }
+        });
+        (url, requests)
+    }
+
+    /// Value of the header `name` in a request head.
+    fn header<'a>(request: &'a str, name: &str) -> Option<&'a str> {
+        request.lines().find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            key.eq_ignore_ascii_case(name).then(|| value.trim())
+        })
+    }
+
+    fn manager(base_url: String) -> HttpManager {
+        let mut manager = HttpManager::new("key".to_string(), "secret".to_string(), false)
+            .with_retry(RetryConfig::disabled());
+        manager.base_url = base_url;
+        manager
+    }
+
+    #[tokio::test]
+    async fn a_stalled_response_times_out() {
+        let (url, _) = serve(vec![None]).await;
+        let manager = manager(url).with_timeout(Duration::from_millis(200));
+        let started = Instant::now();
+        let err = manager
+            .submit_request(Method::GET, "/v5/market/time", HashMap::new(), false)
             .await
-            .map_err(|e| format!("Error: {:?}", e))?;
+            .unwrap_err();
+        assert!(matches!(err, AppError::Timeout), "{:?}", err);
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
 
-        let request_url = format!("{}{}", self.base_url, path);
-        let response = self
-            .client
-            .post(&request_url)
-            .json(&json_input) // Pass a reference to the HashMap.
-            .headers(utils::build_private_headers(
-                &self.api_key,
-                &signature,
-                timestamp,
-                &self.recv_window.to_string(),
-            ))
-            .send()
-            .await?;
+    #[test]
+    fn only_posts_with_an_idempotency_key_are_resendable() {
+        assert!(has_idempotency_key(
+            &json!({ "symbol": "BTCUSDT", "orderLinkId": "abc" })
+        ));
+        assert!(has_idempotency_key(
+            &json!({ "coin": "USDT", "requestId": "r1" })
+        ));
+        assert!(!has_idempotency_key(&json!({ "symbol": "BTCUSDT" })));
+        assert!(!has_idempotency_key(&json!({ "orderLinkId": "" })));
+        assert!(has_idempotency_key(&json!({
+            "category": "linear",
+            "request": [{ "orderLinkId": "a" }, { "orderLinkId": "b" }],
+        })));
+        assert!(!has_idempotency_key(&json!({
+            "category": "linear",
+            "request": [{ "orderLinkId": "a" }, { "symbol": "ETHUSDT" }],
+        })));
 
-        let body_text = response.text().await?;
-        let body: Value = serde_json::from_str(&body_text)?;
+        let manager = HttpManager::new(String::new(), String::new(), false);
+        assert!(manager.resendable(&Method::GET, || false));
+        assert!(!manager.resendable(&Method::POST, || false));
+        assert!(manager.resendable(&Method::POST, || true));
+        let manager = manager.with_retry(RetryConfig {
+            retry_non_idempotent: true,
+            ..Default::default()
+        });
+        assert!(manager.resendable(&Method::POST, || false));
+    }
 
-        Ok(body)
+    #[tokio::test]
+    async fn an_expired_request_is_resent_after_syncing_the_clock() {
+        let server_now = utils::generate_timestamp().unwrap() + 5_000;
+        let server_time = json!({
+            "retCode": 0,
+            "retMsg": "OK",
+            "result": {
+                "timeSecond": (server_now / 1000).to_string(),
+                "timeNano": (server_now * 1_000_000).to_string(),
+            },
+            "retExtInfo": {},
+            "time": server_now,
+        });
+        let (url, requests) = serve(vec![
+            http(
+                "200 OK",
+                "application/json",
+                r#"{"retCode":10002,"retMsg":"invalid request, please check your server timestamp or recv_window param","result":{},"retExtInfo":{},"time":1672211918471}"#,
+            ),
+            http("200 OK", "application/json", &server_time.to_string()),
+            http(
+                "200 OK",
+                "application/json",
+                r#"{"retCode":0,"retMsg":"OK","result":{"list":[]},"retExtInfo":{},"time":1672211918471}"#,
+            ),
+        ])
+        .await;
+        let manager = manager(url).with_expired_retry(true);
+
+        let body = manager
+            .submit_request(Method::GET, "/v5/order/realtime", HashMap::new(), true)
+            .await
+            .unwrap();
+        assert_eq!(body["retCode"], 0);
+        let offset = manager.clock_offset_ms();
+        assert!((4_000..6_000).contains(&offset), "offset {}", offset);
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 3);
+        assert!(requests[1].starts_with("GET /v5/market/time"));
+        assert_eq!(header(&requests[0], "X-BAPI-RECV-WINDOW"), Some("5000"));
+        assert_eq!(header(&requests[2], "X-BAPI-RECV-WINDOW"), Some("10000"));
+        let first: i64 = header(&requests[0], "X-BAPI-TIMESTAMP")
+            .unwrap()
+            .parse()
+            .unwrap();
+        let retried: i64 = header(&requests[2], "X-BAPI-TIMESTAMP")
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(retried - first >= 4_000, "{} then {}", first, retried);
+    }
+
+    /// submit_request answered by one `status` response with an HTML `page`.
+    async fn html_error(status: &str, page: &str) -> AppError {
+        let (url, _) = serve(vec![http(status, "text/html", page)]).await;
+        manager(url)
+            .submit_request(Method::GET, "/v5/market/tickers", HashMap::new(), false)
+            .await
+            .unwrap_err()
+    }
+
+    #[tokio::test]
+    async fn an_html_403_is_blocked() {
+        let page = "<HTML><HEAD><META HTTP-EQUIV=\"Content-Type\" CONTENT=\"text/html; charset=iso-8859-1\">\n\
+                    <TITLE>ERROR: The request could not be satisfied</TITLE>\n</HEAD><BODY>\n\
+                    <H1>403 ERROR</H1>\n<H2>The request could not be satisfied.</H2>\n\
+                    Generated by cloudfront (CloudFront)\n</BODY></HTML>";
+        match html_error("403 Forbidden", page).await {
+            AppError::Blocked { status, snippet } => {
+                assert_eq!(status, 403);
+                assert!(snippet.contains("403 ERROR"));
+            }
+            err => panic!("{:?}", err),
+        }
+    }
+
+    #[tokio::test]
+    async fn an_html_503_or_maintenance_page_is_system_maintenance() {
+        let page = "<html><head><title>503 Service Temporarily Unavailable</title></head>\n\
+                    <body><center><h1>503 Service Temporarily Unavailable</h1></center></body></html>";
+        let err = html_error("503 Service Unavailable", page).await;
+        assert!(matches!(err, AppError::SystemMaintenance(_)), "{:?}", err);
+
+        let page = "<!DOCTYPE html><html><head><title>Bybit</title></head>\n\
+                    <body><h1>System maintenance in progress</h1></body></html>";
+        let err = html_error("200 OK", page).await;
+        assert!(matches!(err, AppError::SystemMaintenance(_)), "{:?}", err);
+    }
+
+    #[tokio::test]
+    async fn any_other_html_page_is_an_unexpected_response() {
+        let page = "<html><head><title>502 Bad Gateway</title></head>\n\
+                    <body><center><h1>502 Bad Gateway</h1></center><hr><center>nginx</center></body></html>";
+        match html_error("502 Bad Gateway", page).await {
+            AppError::UnexpectedResponse { status, snippet } => {
+                assert_eq!(status, 502);
+                assert!(snippet.starts_with("<html>"));
+            }
+            err => panic!("{:?}", err),
+        }
+    }
+
+    #[tokio::test]
+    async fn pairs_are_sent_like_the_same_hash_map() {
+        let ok = r#"{"retCode":0,"retMsg":"OK","result":{"list":[]},"retExtInfo":{},"time":1672211918471}"#;
+        let (url, requests) = serve(vec![
+            http("200 OK", "application/json", ok),
+            http("200 OK", "application/json", ok),
+        ])
+        .await;
+        let manager = manager(url);
+        let pairs = [
+            ("symbol", "BTCUSDT"),
+            ("category", "linear"),
+            ("limit", "5"),
+        ];
+        manager
+            .submit_request_pairs(Method::GET, "/v5/market/orderbook", &pairs, false)
+            .await
+            .unwrap();
+        let query = pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        manager
+            .submit_request(Method::GET, "/v5/market/orderbook", query, false)
+            .await
+            .unwrap();
+
+        let requests = requests.lock().unwrap();
+        let request_line = |request: &str| request.lines().next().unwrap_or_default().to_string();
+        assert_eq!(
+            request_line(&requests[0]),
+            "GET /v5/market/orderbook?category=linear&limit=5&symbol=BTCUSDT HTTP/1.1"
+        );
+        assert_eq!(request_line(&requests[0]), request_line(&requests[1]));
     }
 }
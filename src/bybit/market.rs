@@ -3,47 +3,1124 @@ use async_trait::async_trait;
 use std::{
     collections::{BTreeMap, HashMap},
     pin::Pin,
-    sync::Arc,
+    sync::{Arc, RwLock},
 };
 
-use futures::Future;
+use futures::{stream, Future, Stream, StreamExt};
 use reqwest::Method;
 use serde_json::Value;
 
-use crate::endpoints::v5market;
+use crate::{
+    endpoints::{limits, v5market},
+    errors::app_error::AppError,
+    helpers::{de, symbols, utils},
+};
 
 use super::{
     http_manager::{HttpManager, Manager},
+    orderbook::{OrderBookData, OrderBookSnapshot},
+    response::{self, PartiallyTyped},
+    types::{format_number, round_to_step, Category, Interval, Number, Period, Side},
     Result,
 };
 
+/// get_orderbook result: the levels plus the time the snapshot was generated.
+#[derive(serde_derive::Deserialize)]
+struct RawOrderBook {
+    #[serde(flatten)]
+    data: OrderBookData,
+    ts: u64,
+}
+
+/// A single candle. Bybit sends klines as positional string arrays:
+/// [startTime, open, high, low, close, volume, turnover]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Kline {
+    pub start_time: u64,
+    pub open: Number,
+    pub high: Number,
+    pub low: Number,
+    pub close: Number,
+    pub volume: Number,
+    pub turnover: Number,
+}
+
+/// A mark, index or premium index price candle, which carry no volume:
+/// [startTime, open, high, low, close]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PriceKline {
+    pub start_time: u64,
+    pub open: Number,
+    pub high: Number,
+    pub low: Number,
+    pub close: Number,
+}
+
+///
+/// Parses the positional fields shared by every kline endpoint: the start time in ms
+/// followed by `numbers` values (4 OHLC, plus volume and turnover for trade klines).
+///
+fn parse_candle<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+    numbers: usize,
+) -> std::result::Result<(u64, Vec<Number>), D::Error> {
+    use serde::de::Error;
+
+    let fields: Vec<String> = serde::Deserialize::deserialize(deserializer)?;
+    if fields.len() < numbers + 1 {
+        return Err(D::Error::invalid_length(
+            fields.len(),
+            &format!("{} kline fields", numbers + 1).as_str(),
+        ));
+    }
+    let start_time = fields[0].parse().map_err(D::Error::custom)?;
+    let values = fields[1..=numbers]
+        .iter()
+        .map(|field| field.parse::<Number>().map_err(D::Error::custom))
+        .collect::<std::result::Result<_, _>>()?;
+    Ok((start_time, values))
+}
+
+impl<'de> serde::Deserialize<'de> for Kline {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        let (start_time, v) = parse_candle(deserializer, 6)?;
+        Ok(Kline {
+            start_time,
+            open: v[0],
+            high: v[1],
+            low: v[2],
+            close: v[3],
+            volume: v[4],
+            turnover: v[5],
+        })
+    }
+}
+
+/// Serialized back into Bybit's positional form, so a saved kline reloads as is.
+impl serde::Serialize for Kline {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        vec![
+            self.start_time.to_string(),
+            format_number(self.open),
+            format_number(self.high),
+            format_number(self.low),
+            format_number(self.close),
+            format_number(self.volume),
+            format_number(self.turnover),
+        ]
+        .serialize(serializer)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for PriceKline {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        let (start_time, v) = parse_candle(deserializer, 4)?;
+        Ok(PriceKline {
+            start_time,
+            open: v[0],
+            high: v[1],
+            low: v[2],
+            close: v[3],
+        })
+    }
+}
+
+impl serde::Serialize for PriceKline {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        vec![
+            self.start_time.to_string(),
+            format_number(self.open),
+            format_number(self.high),
+            format_number(self.low),
+            format_number(self.close),
+        ]
+        .serialize(serializer)
+    }
+}
+
+/// Result of the kline endpoints; `K` is `PriceKline` for mark, index and premium klines.
+/// Bybit sends `list` newest first, the typed methods sort it oldest first.
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, PartialEq)]
+pub struct KlineResponse<K = Kline> {
+    pub category: Category,
+    pub symbol: String,
+    pub list: Vec<K>,
+}
+
+/// Query of the kline endpoints, converts into the query map the methods send.
+/// Works for get_kline as well as the mark, index and premium kline methods.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KlineRequest {
+    pub category: Category,
+    pub symbol: String,
+    pub interval: Interval,
+    pub start: Option<u64>,
+    pub end: Option<u64>,
+    pub limit: Option<u32>,
+}
+
+impl KlineRequest {
+    pub fn new(category: Category, symbol: &str, interval: Interval) -> Self {
+        KlineRequest {
+            category,
+            symbol: symbol.to_string(),
+            interval,
+            start: None,
+            end: None,
+            limit: None,
+        }
+    }
+
+    /// Oldest candle start, ms.
+    pub fn start(mut self, start: u64) -> Self {
+        self.start = Some(start);
+        self
+    }
+
+    /// Newest candle start, ms.
+    pub fn end(mut self, end: u64) -> Self {
+        self.end = Some(end);
+        self
+    }
+
+    /// Number of candles, at most 1000.
+    pub fn limit(mut self, limit: u32) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+}
+
+impl From<KlineRequest> for HashMap<String, String> {
+    fn from(request: KlineRequest) -> Self {
+        let mut query = HashMap::new();
+        query.insert("category".to_string(), request.category.to_string());
+        query.insert("symbol".to_string(), request.symbol);
+        query.insert("interval".to_string(), request.interval.to_string());
+        if let Some(start) = request.start {
+            query.insert("start".to_string(), start.to_string());
+        }
+        if let Some(end) = request.end {
+            query.insert("end".to_string(), end.to_string());
+        }
+        if let Some(limit) = request.limit {
+            query.insert("limit".to_string(), limit.to_string());
+        }
+        query
+    }
+}
+
+///
+/// Converts a typed request or query map, rejecting an unknown category or interval
+/// before it costs a round trip.
+///
+fn checked_query(query: impl Into<HashMap<String, String>>) -> Result<HashMap<String, String>> {
+    let query = query.into();
+    if let Some(category) = query.get("category") {
+        category.parse::<Category>()?;
+    }
+    if let Some(interval) = query.get("interval") {
+        interval.parse::<Interval>()?;
+    }
+    Ok(query)
+}
+
+///
+/// Deserializes a kline response with its candles sorted oldest first.
+///
+fn candles_oldest_first<K: serde::de::DeserializeOwned>(
+    body: Value,
+    start_time: fn(&K) -> u64,
+) -> Result<KlineResponse<K>> {
+    let mut klines: KlineResponse<K> = response::into_result(body)?;
+    klines.list.sort_by_key(start_time);
+    Ok(klines)
+}
+
+/// Allowed leverage range of an instrument, absent for spot and option.
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LeverageFilter {
+    #[serde(deserialize_with = "de::string_as_f64")]
+    pub min_leverage: f64,
+    #[serde(deserialize_with = "de::string_as_f64")]
+    pub max_leverage: f64,
+    #[serde(deserialize_with = "de::string_as_f64")]
+    pub leverage_step: f64,
+}
+
+impl LeverageFilter {
+    /// Tolerance for float error when checking a value sits on the step grid.
+    const EPSILON: f64 = 1e-6;
+
+    pub fn is_valid(&self, leverage: f64) -> bool {
+        if leverage < self.min_leverage - Self::EPSILON
+            || leverage > self.max_leverage + Self::EPSILON
+        {
+            return false;
+        }
+        if self.leverage_step <= 0.0 {
+            return true;
+        }
+        let steps = (leverage - self.min_leverage) / self.leverage_step;
+        (steps - steps.round()).abs() < Self::EPSILON
+    }
+
+    ///
+    /// Clamps to [min_leverage, max_leverage] and rounds down onto the leverage step.
+    ///
+    pub fn clamp(&self, leverage: f64) -> f64 {
+        let leverage = leverage.clamp(self.min_leverage, self.max_leverage);
+        if self.leverage_step <= 0.0 {
+            return leverage;
+        }
+        let steps = ((leverage - self.min_leverage) / self.leverage_step + Self::EPSILON).floor();
+        let snapped = self.min_leverage + steps * self.leverage_step;
+        (snapped * 1e8).round() / 1e8
+    }
+}
+
+/// Price rules of an instrument. Spot only has a tick size.
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PriceFilter {
+    #[serde(default, deserialize_with = "de::string_as_option_number")]
+    pub min_price: Option<Number>,
+    #[serde(default, deserialize_with = "de::string_as_option_number")]
+    pub max_price: Option<Number>,
+    #[serde(deserialize_with = "de::string_as_number")]
+    pub tick_size: Number,
+}
+
+/// Quantity rules of an instrument.
+/// Derivatives use `qty_step` and `min_notional_value`, spot uses `base_precision`
+/// and `min_order_amt` (in the quote coin).
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LotSizeFilter {
+    #[serde(deserialize_with = "de::string_as_number")]
+    pub min_order_qty: Number,
+    #[serde(deserialize_with = "de::string_as_number")]
+    pub max_order_qty: Number,
+    #[serde(default, deserialize_with = "de::string_as_option_number")]
+    pub qty_step: Option<Number>,
+    #[serde(default, deserialize_with = "de::string_as_option_number")]
+    pub base_precision: Option<Number>,
+    #[serde(default, deserialize_with = "de::string_as_option_number")]
+    pub min_notional_value: Option<Number>,
+    #[serde(default, deserialize_with = "de::string_as_option_number")]
+    pub min_order_amt: Option<Number>,
+    #[serde(default, deserialize_with = "de::string_as_option_number")]
+    pub max_mkt_order_qty: Option<Number>,
+}
+
+impl LotSizeFilter {
+    ///
+    /// Quantity increment, whichever of qty_step / base_precision the category uses.
+    ///
+    pub fn step(&self) -> Option<Number> {
+        self.qty_step.or(self.base_precision)
+    }
+
+    ///
+    /// Smallest order value accepted, in the quote / settle coin.
+    ///
+    pub fn min_notional(&self) -> Option<Number> {
+        self.min_notional_value.or(self.min_order_amt)
+    }
+}
+
+/// Trading rules of a symbol as returned by get_instruments_info.
+/// The option only fields are None for the other categories.
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Instrument {
+    pub symbol: String,
+    pub status: String,
+    pub base_coin: String,
+    pub quote_coin: String,
+    /// Empty for spot.
+    #[serde(default)]
+    pub settle_coin: String,
+    /// Option only: Call or Put.
+    #[serde(default)]
+    pub options_type: Option<String>,
+    /// Expiry of an option or a dated future, 0 for perpetuals.
+    #[serde(default, deserialize_with = "de::string_as_option_u64")]
+    pub delivery_time: Option<u64>,
+    #[serde(default)]
+    pub leverage_filter: Option<LeverageFilter>,
+    #[serde(default)]
+    pub price_filter: Option<PriceFilter>,
+    #[serde(default)]
+    pub lot_size_filter: Option<LotSizeFilter>,
+}
+
+impl Instrument {
+    ///
+    /// Whether `leverage` can be passed to set_leverage for this symbol.
+    /// Always false for instruments without a leverage filter.
+    ///
+    pub fn valid_leverage(&self, leverage: f64) -> bool {
+        self.leverage_filter
+            .as_ref()
+            .map_or(false, |filter| filter.is_valid(leverage))
+    }
+
+    ///
+    /// Nearest leverage accepted for this symbol, not above `leverage` unless it is
+    /// below the minimum. None for instruments without a leverage filter.
+    ///
+    pub fn clamp_leverage(&self, leverage: f64) -> Option<f64> {
+        self.leverage_filter
+            .as_ref()
+            .map(|filter| filter.clamp(leverage))
+    }
+
+    ///
+    /// `price` on the nearest multiple of the tick size, e.g. 65000.27 -> 65000.3 for
+    /// BTCUSDT (tick 0.10). Unchanged without a price filter.
+    ///
+    pub fn round_price(&self, price: Number) -> Number {
+        match &self.price_filter {
+            Some(filter) => round_to_step(price, filter.tick_size, false),
+            None => price,
+        }
+    }
+
+    ///
+    /// `qty` rounded down onto the qty step (base precision for spot), so an order never
+    /// spends more than intended, e.g. 0.0159 -> 0.015 for BTCUSDT (step 0.001).
+    /// Unchanged without a lot size filter.
+    ///
+    pub fn round_qty(&self, qty: Number) -> Number {
+        match self.lot_size_filter.as_ref().and_then(LotSizeFilter::step) {
+            Some(step) => round_to_step(qty, step, true),
+            None => qty,
+        }
+    }
+
+    ///
+    /// round_qty, clamped to [min_order_qty, max_order_qty].
+    /// A qty below the minimum is raised to it, check the result before sending if
+    /// that would spend more than intended.
+    ///
+    pub fn clamp_qty(&self, qty: Number) -> Number {
+        let qty = self.round_qty(qty);
+        match &self.lot_size_filter {
+            Some(filter) => qty.max(filter.min_order_qty).min(filter.max_order_qty),
+            None => qty,
+        }
+    }
+}
+
+/// A risk limit tier of a symbol as returned by get_risk_limit.
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RiskLimit {
+    pub id: u64,
+    pub symbol: String,
+    /// Position value cap of the tier.
+    #[serde(deserialize_with = "de::string_as_f64")]
+    pub risk_limit_value: f64,
+    #[serde(deserialize_with = "de::string_as_f64")]
+    pub maintenance_margin: f64,
+    #[serde(deserialize_with = "de::string_as_f64")]
+    pub initial_margin: f64,
+    #[serde(default)]
+    pub is_lowest_risk: u8,
+    #[serde(deserialize_with = "de::string_as_f64")]
+    pub max_leverage: f64,
+    #[serde(default, deserialize_with = "de::string_as_option_f64")]
+    pub mm_deduction: Option<f64>,
+}
+
+/// A public trade as returned by get_public_trade_history.
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PublicTrade {
+    pub exec_id: String,
+    pub symbol: String,
+    #[serde(deserialize_with = "de::string_as_number")]
+    pub price: Number,
+    #[serde(deserialize_with = "de::string_as_number")]
+    pub size: Number,
+    pub side: Side,
+    #[serde(deserialize_with = "de::string_as_u64")]
+    pub time: u64,
+    #[serde(default)]
+    pub is_block_trade: bool,
+}
+
+/// Share of accounts long and short on a symbol over one period, get_long_short_ratio.
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LongShortRatio {
+    pub symbol: String,
+    /// Between 0 and 1, buy_ratio + sell_ratio = 1.
+    #[serde(deserialize_with = "de::string_as_number")]
+    pub buy_ratio: Number,
+    #[serde(deserialize_with = "de::string_as_number")]
+    pub sell_ratio: Number,
+    /// Start of the period, in ms.
+    #[serde(deserialize_with = "de::string_as_u64")]
+    pub timestamp: u64,
+}
+
+/// Historical volatility of an option base coin over one period, get_historical_volatility_typed.
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, PartialEq)]
+pub struct VolatilityPoint {
+    /// Window of the volatility, in days.
+    #[serde(deserialize_with = "de::string_as_u64")]
+    pub period: u64,
+    /// Annualized, 0.2766 is 27.66%.
+    #[serde(deserialize_with = "de::string_as_number")]
+    pub value: Number,
+    /// In ms, on the hour.
+    #[serde(deserialize_with = "de::string_as_u64")]
+    pub time: u64,
+}
+
+/// Settlement price of an expired option, get_option_delivery_price_typed.
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DeliveryPrice {
+    pub symbol: String,
+    #[serde(deserialize_with = "de::string_as_number")]
+    pub delivery_price: Number,
+    /// In ms.
+    #[serde(deserialize_with = "de::string_as_u64")]
+    pub delivery_time: u64,
+}
+
+///
+/// Fails with `AppError::InvalidParameter` unless `category` is option, for the
+/// endpoints only Bybit's options have.
+///
+fn require_option(category: Category, endpoint: &str) -> Result<()> {
+    if category != Category::Option {
+        return Err(AppError::InvalidParameter(format!(
+            "{} only applies to option, not {}",
+            endpoint, category
+        )));
+    }
+    Ok(())
+}
+
+///
+/// Last complete non-empty line of the file at `path`, None when there's no such file
+/// or no complete line. A last line without its newline, left by an interrupted
+/// write, is cut off the file first. Reads the tail only, exports can be large.
+///
+#[cfg(feature = "export")]
+async fn last_csv_row(path: &std::path::Path) -> Result<Option<String>> {
+    use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+    let mut file = match tokio::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .await
+    {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let len = file.metadata().await?.len();
+    // Far longer than a row.
+    let tail = len.min(4096);
+    file.seek(std::io::SeekFrom::Start(len - tail)).await?;
+    let mut bytes = Vec::with_capacity(tail as usize);
+    file.read_to_end(&mut bytes).await?;
+    let complete = match bytes.iter().rposition(|byte| *byte == b'\n') {
+        Some(newline) => newline + 1,
+        None if tail == len => 0,
+        None => {
+            return Err(AppError::InvalidParameter(format!(
+                "{} is not a kline export",
+                path.display()
+            )))
+        }
+    };
+    if complete < bytes.len() {
+        file.set_len(len - tail + complete as u64).await?;
+    }
+    Ok(String::from_utf8_lossy(&bytes[..complete])
+        .lines()
+        .rev()
+        .find(|line| !line.trim().is_empty())
+        .map(|line| line.trim().to_string()))
+}
+
+/// Bybit server time as returned by get_server_time.
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerTime {
+    #[serde(deserialize_with = "de::string_as_u64")]
+    pub time_second: u64,
+    #[serde(deserialize_with = "de::string_as_u128")]
+    pub time_nano: u128,
+}
+
+impl ServerTime {
+    pub fn as_millis(&self) -> u128 {
+        self.time_nano / 1_000_000
+    }
+}
+
+/// get_tickers_for queries up to this many symbols one by one, concurrently, and
+/// more with a single call for the whole category.
+const MAX_TICKERS_PER_SYMBOL: usize = 5;
+
+/// Latest price snapshot of a symbol as returned by get_tickers.
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Ticker {
+    pub symbol: String,
+    #[serde(deserialize_with = "de::string_as_number")]
+    pub last_price: Number,
+    /// None while that side of the book is empty.
+    #[serde(default, deserialize_with = "de::string_as_option_number")]
+    pub bid1_price: Option<Number>,
+    #[serde(default, deserialize_with = "de::string_as_option_number")]
+    pub ask1_price: Option<Number>,
+    /// Traded volume of the last 24 hours, in the base coin.
+    #[serde(default, deserialize_with = "de::string_as_number")]
+    pub volume_24h: Number,
+    /// Only present for linear and inverse perpetuals.
+    #[serde(default, deserialize_with = "de::string_as_option_f64")]
+    pub funding_rate: Option<f64>,
+    /// Next funding settlement in ms, only present for perpetuals.
+    #[serde(default, deserialize_with = "de::string_as_option_u64")]
+    pub next_funding_time: Option<u64>,
+}
+
+impl Ticker {
+    ///
+    /// Time left until the next funding settlement, zero once it is due.
+    /// None for symbols without funding (Bybit sends "" or "0").
+    ///
+    pub fn time_to_next_funding(&self, now_ms: u64) -> Option<std::time::Duration> {
+        let next = self.next_funding_time.filter(|time| *time > 0)?;
+        Some(std::time::Duration::from_millis(
+            next.saturating_sub(now_ms),
+        ))
+    }
+}
+
 #[async_trait]
 pub trait Market {
     fn new(http_manager: Arc<HttpManager>) -> Self;
+    async fn get_server_time(&self) -> Result<ServerTime>;
     async fn get_kline(&self, query: HashMap<String, String>) -> Result<Value>;
+    async fn get_kline_range(
+        &self,
+        category: &str,
+        symbol: &str,
+        interval: &str,
+        start: u64,
+        end: u64,
+    ) -> Result<Vec<Kline>>;
+    async fn download_klines(
+        &self,
+        category: &str,
+        symbol: &str,
+        interval: &str,
+        start: Option<u64>,
+        end: Option<u64>,
+        max_rows: Option<usize>,
+    ) -> Result<Vec<Kline>>;
     async fn get_mark_price_kline(&self, query: HashMap<String, String>) -> Result<Value>;
     async fn get_index_price_kline(&self, query: HashMap<String, String>) -> Result<Value>;
     async fn get_premium_index_price_kline(&self, query: HashMap<String, String>) -> Result<Value>;
+    async fn get_kline_typed(
+        &self,
+        query: impl Into<HashMap<String, String>> + Send,
+    ) -> Result<KlineResponse>;
+    async fn get_mark_price_kline_typed(
+        &self,
+        query: impl Into<HashMap<String, String>> + Send,
+    ) -> Result<KlineResponse<PriceKline>>;
+    async fn get_index_price_kline_typed(
+        &self,
+        query: impl Into<HashMap<String, String>> + Send,
+    ) -> Result<KlineResponse<PriceKline>>;
+    async fn get_premium_index_price_kline_typed(
+        &self,
+        query: impl Into<HashMap<String, String>> + Send,
+    ) -> Result<KlineResponse<PriceKline>>;
     async fn get_instruments_info(&self, query: HashMap<String, String>) -> Result<Value>;
+    async fn get_instruments_info_typed(
+        &self,
+        query: HashMap<String, String>,
+    ) -> Result<Vec<Instrument>>;
+    async fn get_all_instruments(&self, category: Category) -> Result<Vec<Instrument>>;
     async fn get_orderbook(&self, query: HashMap<String, String>) -> Result<Value>;
+    async fn get_orderbook_typed(
+        &self,
+        category: Category,
+        symbol: &str,
+        depth: u32,
+    ) -> Result<OrderBookSnapshot>;
     async fn get_tickers(&self, query: HashMap<String, String>) -> Result<Value>;
+    async fn get_tickers_typed(
+        &self,
+        query: HashMap<String, String>,
+    ) -> Result<Vec<PartiallyTyped<Ticker>>>;
+    async fn get_tickers_for(&self, category: Category, symbols: &[&str]) -> Result<Vec<Ticker>>;
+    async fn get_all_funding_rates(&self, category: &str) -> Result<Vec<(String, f64)>>;
+    async fn time_to_next_funding(&self, symbol: &str) -> Result<std::time::Duration>;
     async fn get_funding_rate_history(&self, query: HashMap<String, String>) -> Result<Value>;
 
     async fn get_public_trade_history(&self, query: HashMap<String, String>) -> Result<Value>;
 
+    async fn download_public_trades(
+        &self,
+        category: &str,
+        symbol: &str,
+        max_rows: Option<usize>,
+    ) -> Result<Vec<PublicTrade>>;
+
     async fn get_open_interest(&self, query: HashMap<String, String>) -> Result<Value>;
 
+    async fn get_long_short_ratio(
+        &self,
+        category: Category,
+        symbol: &str,
+        period: Period,
+        limit: u32,
+    ) -> Result<Vec<LongShortRatio>>;
+
     async fn get_historical_volatility(&self, query: HashMap<String, String>) -> Result<Value>;
 
+    async fn get_historical_volatility_typed(
+        &self,
+        category: Category,
+        query: HashMap<String, String>,
+    ) -> Result<Vec<VolatilityPoint>>;
+
     async fn get_insurance(&self, query: HashMap<String, String>) -> Result<Value>;
 
     async fn get_risk_limit(&self, query: HashMap<String, String>) -> Result<Value>;
 
+    async fn get_risk_limit_typed(&self, query: HashMap<String, String>) -> Result<Vec<RiskLimit>>;
+
     async fn get_option_delivery_price(&self, query: HashMap<String, String>) -> Result<Value>;
+
+    async fn get_option_delivery_price_typed(
+        &self,
+        category: Category,
+        query: HashMap<String, String>,
+    ) -> Result<Vec<DeliveryPrice>>;
 }
 
 pub struct MarketHTTP {
-    http_manager: Arc<HttpManager>,
+    http_manager: Arc<dyn Manager + Send + Sync>,
+    universe: RwLock<HashMap<String, HashMap<String, Instrument>>>,
+}
+
+/// Categories loaded by load_symbol_universe.
+pub const ALL_CATEGORIES: [&str; 4] = ["spot", "linear", "inverse", "option"];
+
+impl MarketHTTP {
+    ///
+    /// MarketHTTP on any Manager, e.g. a test_util::MockManager in tests.
+    ///
+    pub fn with_manager(http_manager: Arc<dyn Manager + Send + Sync>) -> Self {
+        MarketHTTP {
+            http_manager,
+            universe: RwLock::new(HashMap::new()),
+        }
+    }
+
+    ///
+    /// Follows `nextPageCursor` of a cursor paginated endpoint, such as
+    /// `MarketEnum::GetInstrumentsInfo`, yielding the items of each page as it arrives.
+    /// The stream ends after the page whose cursor is absent or empty. A failed request
+    /// is yielded as an error and ends the stream, as there is no cursor to go on from.
+    /// Endpoints paginated by time rather than cursor (funding rate history, recent
+    /// trades) have no cursor and yield a single page.
+    ///
+    pub fn paginate<T>(
+        &self,
+        initial_query: HashMap<String, String>,
+        endpoint: v5market::MarketEnum,
+    ) -> impl Stream<Item = Result<Vec<T>>> + '_
+    where
+        T: serde::de::DeserializeOwned + 'static,
+    {
+        let path = endpoint.to_string();
+        stream::unfold(Some(String::new()), move |cursor| {
+            let mut query = initial_query.clone();
+            let path = path.clone();
+            async move {
+                let cursor = cursor?;
+                if !cursor.is_empty() {
+                    query.insert("cursor".to_string(), cursor);
+                }
+                let page = async {
+                    let body = self
+                        .http_manager
+                        .submit_request(Method::GET, &path, query, false)
+                        .await?;
+                    let result = response::take_result(body)?;
+                    let next_cursor = result
+                        .get("nextPageCursor")
+                        .and_then(Value::as_str)
+                        .unwrap_or_default()
+                        .to_string();
+                    let items = response::extract_list(result)
+                        .into_iter()
+                        .map(serde_json::from_value)
+                        .collect::<std::result::Result<Vec<T>, _>>()?;
+                    Ok::<_, super::Error>((items, next_cursor))
+                }
+                .await;
+                match page {
+                    Ok((items, next_cursor)) if next_cursor.is_empty() => Some((Ok(items), None)),
+                    Ok((items, next_cursor)) => Some((Ok(items), Some(next_cursor))),
+                    Err(e) => Some((Err(e), None)),
+                }
+            }
+        })
+    }
+
+    ///
+    /// Every candle starting within [start, end] (ms), oldest first, fetched lazily in
+    /// windows of 1000 candles walking forward from start. Candles are yielded once
+    /// even if windows overlap. The stream ends after the window reaching end. When the
+    /// first window comes back empty, e.g. with a start before the symbol was listed, the
+    /// earliest candle is located with `first_kline_start` and the walk jumps there
+    /// instead of requesting every empty window. A failed request is yielded as an
+    /// error and ends the stream.
+    ///
+    pub fn stream_klines(
+        &self,
+        category: Category,
+        symbol: &str,
+        interval: Interval,
+        start: u64,
+        end: u64,
+    ) -> impl Stream<Item = Result<Kline>> + '_ {
+        // Months vary in length, a 28 day step keeps a window within 1000 candles.
+        let step = interval.duration_ms().unwrap_or(28 * 24 * 60 * 60_000);
+        let request = KlineRequest::new(category, symbol, interval).limit(1000);
+        stream::unfold(Some((start, None)), move |state| {
+            let request = request.clone();
+            async move {
+                let (from, last): (u64, Option<u64>) = state?;
+                if from > end {
+                    return None;
+                }
+                let to = from.saturating_add(999 * step).min(end);
+                let page = async {
+                    let body = self
+                        .get_kline(request.clone().start(from).end(to).into())
+                        .await?;
+                    response::into_list::<Kline>(body)
+                }
+                .await;
+                let mut klines = match page {
+                    Ok(klines) => klines,
+                    Err(e) => return Some((vec![Err(e)], None)),
+                };
+                klines.sort_by_key(|kline| kline.start_time);
+                klines.retain(|kline| {
+                    kline.start_time >= from
+                        && kline.start_time <= end
+                        && last.map_or(true, |last| kline.start_time > last)
+                });
+                if klines.is_empty() && last.is_none() && to < end {
+                    return match self
+                        .first_kline_start(&request, to + 1, end, 999 * step)
+                        .await
+                    {
+                        Ok(Some(first)) => Some((vec![], Some((first, None)))),
+                        Ok(None) => None,
+                        Err(e) => Some((vec![Err(e)], None)),
+                    };
+                }
+                let last = klines.last().map(|kline| kline.start_time).or(last);
+                let next = if to >= end {
+                    None
+                } else {
+                    Some((to + 1, last))
+                };
+                Some((klines.into_iter().map(Ok).collect::<Vec<_>>(), next))
+            }
+        })
+        .flat_map(stream::iter)
+    }
+
+    ///
+    /// A time within `window` ms before the earliest candle of `request` in [from, end],
+    /// or None when the range has none. Bybit returns the newest candles of a range, so
+    /// a one-candle request tells whether a range has any and bounds the earliest one;
+    /// bisecting takes about log2 of the range in windows requests.
+    ///
+    async fn first_kline_start(
+        &self,
+        request: &KlineRequest,
+        mut from: u64,
+        end: u64,
+        window: u64,
+    ) -> Result<Option<u64>> {
+        let newest = |from: u64, to: u64| async move {
+            let query = request.clone().start(from).end(to).limit(1);
+            let klines = response::into_list::<Kline>(self.get_kline(query.into()).await?)?;
+            Ok::<_, super::Error>(klines.first().map(|kline| kline.start_time))
+        };
+        let mut to = match newest(from, end).await? {
+            Some(first) => first,
+            None => return Ok(None),
+        };
+        // [from, to] holds the earliest candle and ends with a known one
+        while to - from > window {
+            let mid = from + (to - from) / 2;
+            match newest(from, mid).await? {
+                Some(newest) => to = newest,
+                None => from = mid + 1,
+            }
+        }
+        Ok(Some(from))
+    }
+
+    ///
+    /// Writes the candles of stream_klines to a CSV file at `path`, a header row then one
+    /// row per candle (`start_time,open,high,low,close,volume,turnover`), and returns
+    /// the number of rows written. An existing file is resumed: the download continues
+    /// after its last complete row and appends, so an interrupted download (a failed
+    /// request ends it with the error, keeping the rows written so far) only needs
+    /// rerunning. A row cut short by a crash is removed before resuming.
+    /// A file that isn't one of these exports fails with `AppError::InvalidParameter`.
+    ///
+    #[cfg(feature = "export")]
+    pub async fn download_klines_to_csv(
+        &self,
+        category: Category,
+        symbol: &str,
+        interval: Interval,
+        start: u64,
+        end: u64,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<usize> {
+        use tokio::io::AsyncWriteExt;
+
+        const HEADER: &str = "start_time,open,high,low,close,volume,turnover";
+
+        let path = path.as_ref();
+        let (start, resumed) = match last_csv_row(path).await? {
+            None => (start, false),
+            Some(line) if line == HEADER => (start, true),
+            Some(line) => {
+                let fields: Vec<&str> = line.split(',').collect();
+                let last = fields
+                    .first()
+                    .and_then(|field| field.parse::<u64>().ok())
+                    .filter(|_| {
+                        fields.len() == 7
+                            && fields[1..].iter().all(|field| field.parse::<f64>().is_ok())
+                    })
+                    .ok_or_else(|| {
+                        AppError::InvalidParameter(format!(
+                            "{} is not a kline export",
+                            path.display()
+                        ))
+                    })?;
+                (start.max(last + 1), true)
+            }
+        };
+        let file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await?;
+        let mut writer = tokio::io::BufWriter::new(file);
+        if !resumed {
+            writer.write_all(format!("{}\n", HEADER).as_bytes()).await?;
+        }
+
+        let mut written = 0;
+        let mut klines = Box::pin(self.stream_klines(category, symbol, interval, start, end));
+        let outcome = loop {
+            let kline = match klines.next().await {
+                Some(Ok(kline)) => kline,
+                Some(Err(e)) => break Err(e),
+                None => break Ok(()),
+            };
+            let row = format!(
+                "{},{},{},{},{},{},{}\n",
+                kline.start_time,
+                format_number(kline.open),
+                format_number(kline.high),
+                format_number(kline.low),
+                format_number(kline.close),
+                format_number(kline.volume),
+                format_number(kline.turnover),
+            );
+            if let Err(e) = writer.write_all(row.as_bytes()).await {
+                break Err(e.into());
+            }
+            written += 1;
+        };
+        writer.flush().await?;
+        outcome.map(|_| written)
+    }
+
+    ///
+    /// Fetches one page of instruments and the cursor of the next one (empty on the last page).
+    ///
+    async fn instruments_page(
+        &self,
+        category: &str,
+        cursor: &str,
+    ) -> Result<(Vec<Instrument>, String)> {
+        let mut query = HashMap::new();
+        query.insert("category".to_string(), category.to_string());
+        query.insert("limit".to_string(), "1000".to_string());
+        if !cursor.is_empty() {
+            query.insert("cursor".to_string(), cursor.to_string());
+        }
+        let result = response::take_result(self.get_instruments_info(query).await?)?;
+        let next_cursor = result
+            .get("nextPageCursor")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+        let instruments = response::extract_list(result)
+            .into_iter()
+            .map(serde_json::from_value)
+            .collect::<std::result::Result<_, _>>()?;
+        Ok((instruments, next_cursor))
+    }
+
+    ///
+    ///
+    /// Fetches the instruments of every category and indexes them in memory, so
+    /// that instrument() lookups are synchronous afterwards. Returns the number of
+    /// instruments loaded.
+    /// Memory: spot, linear and inverse are a few thousand instruments in total,
+    /// the option universe alone is several thousand more (one per strike and expiry),
+    /// a few MB altogether. Use load_symbol_universe_for to leave options out.
+    ///
+    ///
+    pub async fn load_symbol_universe(&self) -> Result<usize> {
+        self.load_symbol_universe_for(&ALL_CATEGORIES).await
+    }
+
+    ///
+    /// Same as load_symbol_universe, for the given categories only.
+    /// Categories already loaded are replaced, others are kept.
+    ///
+    pub async fn load_symbol_universe_for(&self, categories: &[&str]) -> Result<usize> {
+        let mut loaded = 0;
+        for category in categories {
+            loaded += self.refresh_symbol_universe(category).await?;
+        }
+        Ok(loaded)
+    }
+
+    ///
+    /// Reloads the instruments of one category, e.g. to pick up new listings.
+    ///
+    pub async fn refresh_symbol_universe(&self, category: &str) -> Result<usize> {
+        let mut index = HashMap::new();
+        let mut cursor = String::new();
+        loop {
+            let (instruments, next_cursor) = self.instruments_page(category, &cursor).await?;
+            for instrument in instruments {
+                index.insert(instrument.symbol.clone(), instrument);
+            }
+            if next_cursor.is_empty() {
+                break;
+            }
+            cursor = next_cursor;
+        }
+        let loaded = index.len();
+        self.universe
+            .write()
+            .unwrap()
+            .insert(category.to_string(), index);
+        Ok(loaded)
+    }
+
+    ///
+    /// Looks up a loaded instrument. The same symbol can exist in several
+    /// categories (BTCUSDT is both spot and linear), hence the category.
+    ///
+    pub fn instrument(&self, category: &str, symbol: &str) -> Option<Instrument> {
+        self.universe
+            .read()
+            .unwrap()
+            .get(category)
+            .and_then(|index| index.get(symbol))
+            .cloned()
+    }
+
+    ///
+    ///
+    /// Converts a Bybit symbol to the base/quote form used by multi exchange
+    /// systems: BTCUSDT -> BTC/USDT, and option expiries to YYMMDD:
+    /// BTC-29MAR24-70000-C -> BTC-240329-70000-C.
+    /// Base and quote coins come from the loaded symbol universe when the symbol is
+    /// in it, otherwise the symbol is split on well known quote coins.
+    ///
+    ///
+    pub fn to_unified(&self, symbol: &str) -> Option<String> {
+        if symbol.contains('-') {
+            return symbols::option_to_unified(symbol);
+        }
+        let known = {
+            let universe = self.universe.read().unwrap();
+            universe
+                .values()
+                .find_map(|index| index.get(symbol))
+                .map(|i| (i.base_coin.clone(), i.quote_coin.clone()))
+        };
+        let (base, quote) = known.or_else(|| symbols::split_symbol(symbol))?;
+        Some(format!("{}/{}", base, quote))
+    }
+
+    ///
+    ///
+    /// Converts a unified symbol (BTC/USDT, BTC-240329-70000-C) back to the Bybit
+    /// symbol of `category`. When that category of the symbol universe is loaded,
+    /// symbols which don't exist in it give None.
+    ///
+    ///
+    pub fn from_unified(&self, unified: &str, category: &str) -> Option<String> {
+        let symbol = if category == "option" {
+            symbols::option_from_unified(unified)?
+        } else {
+            let (base, quote) = unified.split_once('/')?;
+            format!("{}{}", base, quote)
+        };
+        match self.universe.read().unwrap().get(category) {
+            Some(index) if !index.contains_key(&symbol) => None,
+            _ => Some(symbol),
+        }
+    }
+
+    ///
+    /// Symbols loaded for a category.
+    ///
+    pub fn symbols(&self, category: &str) -> Vec<String> {
+        self.universe
+            .read()
+            .unwrap()
+            .get(category)
+            .map(|index| index.keys().cloned().collect())
+            .unwrap_or_default()
+    }
 }
 
 #[async_trait]
@@ -54,7 +1131,22 @@ impl Market for MarketHTTP {
     ///
     ///
     fn new(http_manager: Arc<HttpManager>) -> Self {
-        MarketHTTP { http_manager }
+        MarketHTTP::with_manager(http_manager)
+    }
+    /// Query Bybit's server time. Use HttpManager::sync_clock to correct request timestamps with it.
+
+    ///     Returns:
+    ///         ServerTime in seconds and nanoseconds.
+
+    ///     Additional information:
+    ///         https://bybit-exchange.github.io/docs/v5/market/time
+    async fn get_server_time(&self) -> Result<ServerTime> {
+        let url = v5market::MarketEnum::GetServerTime.to_string();
+        let body = self
+            .http_manager
+            .submit_request(Method::GET, &url, HashMap::new(), false)
+            .await?;
+        response::into_result(body)
     }
     /// Query the kline data. Charts are returned in groups based on the requested interval.
 
@@ -71,9 +1163,92 @@ impl Market for MarketHTTP {
     async fn get_kline(&self, query: HashMap<String, String>) -> Result<Value> {
         let url = v5market::MarketEnum::GetKline.to_string();
         self.http_manager
-            .submit_request(Method::GET, &url, query, true)
+            .submit_request(Method::GET, &url, query, false)
+            .await
+    }
+    /// Download every candle starting within [start, end] (ms), paging over the 1000 candle limit.
+    ///     Used to backfill a live kline feed after a reconnect.
+
+    ///     Returns:
+    ///         Candles sorted oldest first.
+    async fn get_kline_range(
+        &self,
+        category: &str,
+        symbol: &str,
+        interval: &str,
+        start: u64,
+        end: u64,
+    ) -> Result<Vec<Kline>> {
+        self.download_klines(category, symbol, interval, Some(start), Some(end), None)
             .await
     }
+    /// Download candles page by page, walking backward from end since Bybit returns
+    ///     the newest candles of a range first.
+
+    ///     Required args:
+    ///         category (string): Product type. spot,linear,inverse
+    ///         symbol (string): Symbol name
+    ///         interval (string): Kline interval
+    ///         start (Option<u64>): Oldest candle start (ms). None: as far back as Bybit has data
+    ///         end (Option<u64>): Newest candle start (ms). None: now
+    ///         max_rows (Option<usize>): Stop once this many candles are collected
+
+    ///     Pagination stops at whichever of start, the earliest available candle or
+    ///     max_rows comes first. With max_rows the newest max_rows candles of the range
+    ///     are kept, so `max_rows: Some(500)` with no start/end is "the last 500 candles".
+
+    ///     Returns:
+    ///         Candles sorted oldest first.
+    async fn download_klines(
+        &self,
+        category: &str,
+        symbol: &str,
+        interval: &str,
+        start: Option<u64>,
+        end: Option<u64>,
+        max_rows: Option<usize>,
+    ) -> Result<Vec<Kline>> {
+        let mut klines: Vec<Kline> = vec![];
+        if max_rows == Some(0) {
+            return Ok(klines);
+        }
+        let mut to = end;
+        loop {
+            let limit = max_rows.map_or(1000, |max| (max - klines.len()).min(1000));
+            let mut query = HashMap::new();
+            query.insert("category".to_string(), category.to_string());
+            query.insert("symbol".to_string(), symbol.to_string());
+            query.insert("interval".to_string(), interval.to_string());
+            query.insert("limit".to_string(), limit.to_string());
+            if let Some(start) = start {
+                query.insert("start".to_string(), start.to_string());
+            }
+            if let Some(to) = to {
+                query.insert("end".to_string(), to.to_string());
+            }
+
+            let mut page: Vec<Kline> = response::into_list(self.get_kline(query).await?)?;
+            page.sort_by_key(|kline| std::cmp::Reverse(kline.start_time));
+            let oldest = match page.last() {
+                Some(kline) => kline.start_time,
+                None => break,
+            };
+            let full = page.len() >= limit;
+            klines.extend(page);
+            if !full || max_rows.map_or(false, |max| klines.len() >= max) || oldest == 0 {
+                break;
+            }
+            if start.map_or(false, |start| oldest <= start) {
+                break;
+            }
+            to = Some(oldest - 1);
+        }
+        if let Some(max) = max_rows {
+            klines.truncate(max);
+        }
+        klines.reverse();
+        Ok(klines)
+    }
     /// Query the mark price kline data. Charts are returned in groups based on the requested interval.
 
     ///     Required args:
@@ -89,7 +1264,7 @@ impl Market for MarketHTTP {
     async fn get_mark_price_kline(&self, query: HashMap<String, String>) -> Result<Value> {
         let url = v5market::MarketEnum::GetMarkPriceKline.to_string();
         self.http_manager
-            .submit_request(Method::GET, &url, query, true)
+            .submit_request(Method::GET, &url, query, false)
             .await
     }
     /// Query the index price kline data. Charts are returned in groups based on the requested interval.
@@ -107,7 +1282,7 @@ impl Market for MarketHTTP {
     async fn get_index_price_kline(&self, query: HashMap<String, String>) -> Result<Value> {
         let url = v5market::MarketEnum::GetIndexPriceKline.to_string();
         self.http_manager
-            .submit_request(Method::GET, &url, query, true)
+            .submit_request(Method::GET, &url, query, false)
             .await
     }
     /// Retrieve the premium index price kline data. Charts are returned in groups based on the requested interval.
@@ -125,9 +1300,57 @@ impl Market for MarketHTTP {
     async fn get_premium_index_price_kline(&self, query: HashMap<String, String>) -> Result<Value> {
         let url = v5market::MarketEnum::GetPremiumIndexPriceKline.to_string();
         self.http_manager
-            .submit_request(Method::GET, &url, query, true)
+            .submit_request(Method::GET, &url, query, false)
             .await
     }
+    /// The typed kline methods take a KlineRequest, or the same query map as their raw
+    ///     counterparts, and all return candles sorted oldest first, start times in ms.
+    ///     An unknown category or interval is rejected locally.
+
+    ///     Returns:
+    ///         KlineResponse of candles with volume and turnover.
+    async fn get_kline_typed(
+        &self,
+        query: impl Into<HashMap<String, String>> + Send,
+    ) -> Result<KlineResponse> {
+        let query = checked_query(query)?;
+        candles_oldest_first(self.get_kline(query).await?, |k: &Kline| k.start_time)
+    }
+    ///     Returns:
+    ///         KlineResponse of mark price candles.
+    async fn get_mark_price_kline_typed(
+        &self,
+        query: impl Into<HashMap<String, String>> + Send,
+    ) -> Result<KlineResponse<PriceKline>> {
+        let query = checked_query(query)?;
+        candles_oldest_first(self.get_mark_price_kline(query).await?, |k: &PriceKline| {
+            k.start_time
+        })
+    }
+    ///     Returns:
+    ///         KlineResponse of index price candles.
+    async fn get_index_price_kline_typed(
+        &self,
+        query: impl Into<HashMap<String, String>> + Send,
+    ) -> Result<KlineResponse<PriceKline>> {
+        let query = checked_query(query)?;
+        candles_oldest_first(
+            self.get_index_price_kline(query).await?,
+            |k: &PriceKline| k.start_time,
+        )
+    }
+    ///     Returns:
+    ///         KlineResponse of premium index candles.
+    async fn get_premium_index_price_kline_typed(
+        &self,
+        query: impl Into<HashMap<String, String>> + Send,
+    ) -> Result<KlineResponse<PriceKline>> {
+        let query = checked_query(query)?;
+        candles_oldest_first(
+            self.get_premium_index_price_kline(query).await?,
+            |k: &PriceKline| k.start_time,
+        )
+    }
     /// Query a list of instruments of online trading pair.
 
     ///     Required args:
@@ -141,9 +1364,42 @@ impl Market for MarketHTTP {
     async fn get_instruments_info(&self, query: HashMap<String, String>) -> Result<Value> {
         let url = v5market::MarketEnum::GetInstrumentsInfo.to_string();
         self.http_manager
-            .submit_request(Method::GET, &url, query, true)
+            .submit_request(Method::GET, &url, query, false)
             .await
     }
+    /// Same as get_instruments_info, with the instruments typed.
+
+    ///     Required args:
+    ///         category (string): Product type. spot,linear,inverse,option
+
+    ///     Returns:
+    ///         Vec<Instrument> of the requested page.
+
+    ///     Additional information:
+    ///         https://bybit-exchange.github.io/docs/v5/market/instrument
+    async fn get_instruments_info_typed(
+        &self,
+        query: HashMap<String, String>,
+    ) -> Result<Vec<Instrument>> {
+        response::into_list(self.get_instruments_info(query).await?)
+    }
+
+    ///
+    /// Every instrument of `category`, following nextPageCursor over all pages.
+    ///
+    async fn get_all_instruments(&self, category: Category) -> Result<Vec<Instrument>> {
+        let query = vec![
+            ("category".to_string(), category.to_string()),
+            ("limit".to_string(), "1000".to_string()),
+        ]
+        .into_iter()
+        .collect();
+        let items = response::all_pages(query, |query| self.get_instruments_info(query)).await?;
+        Ok(items
+            .into_iter()
+            .map(serde_json::from_value)
+            .collect::<std::result::Result<_, _>>()?)
+    }
     /// Query orderbook data
 
     ///     Required args:
@@ -158,9 +1414,48 @@ impl Market for MarketHTTP {
     async fn get_orderbook(&self, query: HashMap<String, String>) -> Result<Value> {
         let url = v5market::MarketEnum::GetOrderbook.to_string();
         self.http_manager
-            .submit_request(Method::GET, &url, query, true)
+            .submit_request(Method::GET, &url, query, false)
             .await
     }
+
+    ///
+    /// get_orderbook for the top `depth` levels of each side.
+    ///
+    /// Additional information:
+    ///     depth must be 1-200 for spot, 1-500 for linear and inverse, 1-25 for option,
+    ///     anything else fails with `AppError::InvalidParameter` without a request.
+    ///
+    async fn get_orderbook_typed(
+        &self,
+        category: Category,
+        symbol: &str,
+        depth: u32,
+    ) -> Result<OrderBookSnapshot> {
+        let category = category.to_string();
+        let url = v5market::MarketEnum::GetOrderbook.to_string();
+        let max_depth = limits::max_limit(&url, Some(&category)).unwrap_or(u64::MAX);
+        if depth == 0 || u64::from(depth) > max_depth {
+            return Err(AppError::InvalidParameter(format!(
+                "orderbook depth for {} must be 1-{}, got {}",
+                category, max_depth, depth
+            )));
+        }
+        let query = vec![
+            ("category".to_string(), category),
+            ("symbol".to_string(), symbol.to_string()),
+            ("limit".to_string(), depth.to_string()),
+        ]
+        .into_iter()
+        .collect();
+        let book: RawOrderBook = response::into_result(self.get_orderbook(query).await?)?;
+        Ok(OrderBookSnapshot {
+            symbol: book.data.symbol,
+            bids: book.data.bids,
+            asks: book.data.asks,
+            ts: book.ts,
+            update_id: book.data.update_id,
+        })
+    }
     /// Query the latest price snapshot, best bid/ask price, and trading volume in the last 24 hours.
 
     ///     Required args:
@@ -174,9 +1469,125 @@ impl Market for MarketHTTP {
     async fn get_tickers(&self, query: HashMap<String, String>) -> Result<Value> {
         let url = v5market::MarketEnum::GetTickers.to_string();
         self.http_manager
-            .submit_request(Method::GET, &url, query, true)
+            .submit_request(Method::GET, &url, query, false)
             .await
     }
+    /// Same as get_tickers, with the modelled Ticker fields typed.
+    ///     Fields that differ per category are still reachable through the raw JSON.
+
+    ///     Required args:
+    ///         category (string): Product type. spot,linear,inverse,option
+
+    ///     Returns:
+    ///         Vec<PartiallyTyped<Ticker>>
+
+    ///     Additional information:
+    ///         https://bybit-exchange.github.io/docs/v5/market/tickers
+    async fn get_tickers_typed(
+        &self,
+        query: HashMap<String, String>,
+    ) -> Result<Vec<PartiallyTyped<Ticker>>> {
+        response::into_list(self.get_tickers(query).await?)
+    }
+
+    ///
+    /// Tickers of `symbols`, in the same order. A few symbols are requested one by one,
+    /// concurrently; past MAX_TICKERS_PER_SYMBOL a single call fetches the whole
+    /// category, which is then filtered. Either way a symbol Bybit doesn't list fails
+    /// the call.
+    ///
+    async fn get_tickers_for(&self, category: Category, symbols: &[&str]) -> Result<Vec<Ticker>> {
+        let category = category.to_string();
+        let query = |symbol: Option<&str>| {
+            let mut query = HashMap::new();
+            query.insert("category".to_string(), category.clone());
+            if let Some(symbol) = symbol {
+                query.insert("symbol".to_string(), symbol.to_string());
+            }
+            query
+        };
+        let mut tickers: HashMap<String, Ticker> = if symbols.len() <= MAX_TICKERS_PER_SYMBOL {
+            let queries: Vec<HashMap<String, String>> =
+                symbols.iter().map(|symbol| query(Some(symbol))).collect();
+            let pages: Vec<Result<Vec<Ticker>>> = stream::iter(queries)
+                .map(|query| async move { response::into_list(self.get_tickers(query).await?) })
+                .buffer_unordered(MAX_TICKERS_PER_SYMBOL)
+                .collect()
+                .await;
+            let mut tickers = HashMap::new();
+            for page in pages {
+                for ticker in page? {
+                    tickers.insert(ticker.symbol.clone(), ticker);
+                }
+            }
+            tickers
+        } else {
+            let all: Vec<Ticker> = response::into_list(self.get_tickers(query(None)).await?)?;
+            all.into_iter()
+                .map(|ticker| (ticker.symbol.clone(), ticker))
+                .collect()
+        };
+        symbols
+            .iter()
+            .map(|symbol| {
+                tickers.remove(*symbol).ok_or_else(|| {
+                    AppError::InvalidParameter(format!("no {} ticker for {}", category, symbol))
+                })
+            })
+            .collect()
+    }
+    /// Query the current funding rate of every symbol in a category with a single tickers call.
+    ///     Symbols without a funding rate are skipped.
+
+    ///     Required args:
+    ///         category (string): Product type. linear,inverse
+
+    ///     Returns:
+    ///         (symbol, funding rate) pairs sorted from the highest rate to the lowest.
+
+    ///     Additional information:
+    ///         https://bybit-exchange.github.io/docs/v5/market/tickers
+    async fn get_all_funding_rates(&self, category: &str) -> Result<Vec<(String, f64)>> {
+        let mut query = HashMap::new();
+        query.insert("category".to_string(), category.to_string());
+        let tickers: Vec<Ticker> = response::into_list(self.get_tickers(query).await?)?;
+
+        let mut rates: Vec<(String, f64)> = tickers
+            .into_iter()
+            .filter_map(|ticker| Some((ticker.symbol, ticker.funding_rate?)))
+            .collect();
+        rates.sort_by(|a, b| b.1.total_cmp(&a.1));
+        Ok(rates)
+    }
+    /// Countdown to the next funding settlement of a perpetual.
+    ///     The category is taken from the symbol: USD quoted symbols are inverse, the rest linear.
+
+    ///     Required args:
+    ///         symbol (string): Symbol name
+
+    ///     Returns:
+    ///         Time left until nextFundingTime of the symbol's ticker.
+
+    ///     Additional information:
+    ///         https://bybit-exchange.github.io/docs/v5/market/tickers
+    async fn time_to_next_funding(&self, symbol: &str) -> Result<std::time::Duration> {
+        let category = match symbols::split_symbol(symbol) {
+            Some((_, quote)) if quote == "USD" => "inverse",
+            _ => "linear",
+        };
+        let mut query = HashMap::new();
+        query.insert("category".to_string(), category.to_string());
+        query.insert("symbol".to_string(), symbol.to_string());
+        let tickers: Vec<Ticker> = response::into_list(self.get_tickers(query).await?)?;
+        let now_ms = utils::generate_timestamp()? as u64;
+        tickers
+            .iter()
+            .find(|ticker| ticker.symbol == symbol)
+            .and_then(|ticker| ticker.time_to_next_funding(now_ms))
+            .ok_or_else(|| {
+                AppError::InvalidParameter(format!("{} has no funding schedule", symbol))
+            })
+    }
     /// Query historical funding rate. Each symbol has a different funding interval.
     ///     For example, if the interval is 8 hours and the current time is UTC 12, then it returns the last funding rate, which settled at UTC 8.
     ///     To query the funding rate interval, please refer to instruments-info.
@@ -193,7 +1604,7 @@ impl Market for MarketHTTP {
     async fn get_funding_rate_history(&self, query: HashMap<String, String>) -> Result<Value> {
         let url = v5market::MarketEnum::GetFundingRateHistory.to_string();
         self.http_manager
-            .submit_request(Method::GET, &url, query, true)
+            .submit_request(Method::GET, &url, query, false)
             .await
     }
     /// Query recent public trading data in Bybit.
@@ -210,9 +1621,41 @@ impl Market for MarketHTTP {
     async fn get_public_trade_history(&self, query: HashMap<String, String>) -> Result<Value> {
         let url = v5market::MarketEnum::GetPublicTradingHistory.to_string();
         self.http_manager
-            .submit_request(Method::GET, &url, query, true)
+            .submit_request(Method::GET, &url, query, false)
             .await
     }
+    /// Download the most recent public trades, newest first.
+    ///     Bybit only serves recent trades here, without a time range or cursor: up to
+    ///     60 for spot and 1000 for the other categories. max_rows caps the request
+    ///     limit, None asks for as many as the category allows.
+
+    ///     Required args:
+    ///         category (string): Product type. spot,linear,inverse,option
+    ///         symbol (string): Symbol name
+    ///         max_rows (Option<usize>): Number of trades wanted
+
+    ///     Returns:
+    ///         Vec<PublicTrade>
+    async fn download_public_trades(
+        &self,
+        category: &str,
+        symbol: &str,
+        max_rows: Option<usize>,
+    ) -> Result<Vec<PublicTrade>> {
+        let category_max = if category == "spot" { 60 } else { 1000 };
+        let limit = max_rows.map_or(category_max, |max| max.min(category_max));
+        if limit == 0 {
+            return Ok(vec![]);
+        }
+        let mut query = HashMap::new();
+        query.insert("category".to_string(), category.to_string());
+        query.insert("symbol".to_string(), symbol.to_string());
+        query.insert("limit".to_string(), limit.to_string());
+        let mut trades: Vec<PublicTrade> =
+            response::into_list(self.get_public_trade_history(query).await?)?;
+        trades.truncate(limit);
+        Ok(trades)
+    }
     /// Get open interest of each symbol.
 
     ///     Required args:
@@ -231,11 +1674,51 @@ impl Market for MarketHTTP {
                 Method::GET,
                 &v5market::MarketEnum::GetOpenInterest.to_string(),
                 query,
-                true,
+                false,
             )
             .await
     }
 
+    ///
+    /// Long/short account ratio of a linear or inverse symbol, the last `limit`
+    /// periods (at most 500) oldest first.
+    /// Additional information:
+    ///     Spot and option fail with `AppError::InvalidParameter`, Bybit only
+    ///     publishes the ratio for derivatives.
+    ///     https://bybit-exchange.github.io/docs/v5/market/long-short-ratio
+    ///
+    async fn get_long_short_ratio(
+        &self,
+        category: Category,
+        symbol: &str,
+        period: Period,
+        limit: u32,
+    ) -> Result<Vec<LongShortRatio>> {
+        if !matches!(category, Category::Linear | Category::Inverse) {
+            return Err(AppError::InvalidParameter(format!(
+                "no long/short ratio for {}",
+                category
+            )));
+        }
+        let mut query = HashMap::new();
+        query.insert("category".to_string(), category.to_string());
+        query.insert("symbol".to_string(), symbol.to_string());
+        query.insert("period".to_string(), period.to_string());
+        query.insert("limit".to_string(), limit.to_string());
+        let mut ratios: Vec<LongShortRatio> = response::into_list(
+            self.http_manager
+                .submit_request(
+                    Method::GET,
+                    &v5market::MarketEnum::GetLongShortRatio.to_string(),
+                    query,
+                    false,
+                )
+                .await?,
+        )?;
+        ratios.sort_by_key(|ratio| ratio.timestamp);
+        Ok(ratios)
+    }
+
     /// Query option historical volatility
 
     ///     Required args:
@@ -252,11 +1735,30 @@ impl Market for MarketHTTP {
                 Method::GET,
                 &v5market::MarketEnum::GetHistoricalVolatility.to_string(),
                 query,
-                true,
+                false,
             )
             .await
     }
 
+    /// Same as get_historical_volatility, with the points typed.
+
+    ///     Required args:
+    ///         category (Category): Category::Option, anything else fails with
+    ///             `AppError::InvalidParameter` before sending.
+    ///         query: the other filters (baseCoin, period, startTime, endTime).
+
+    ///     Returns:
+    ///         Vec<VolatilityPoint>
+    async fn get_historical_volatility_typed(
+        &self,
+        category: Category,
+        mut query: HashMap<String, String>,
+    ) -> Result<Vec<VolatilityPoint>> {
+        require_option(category, "historical volatility")?;
+        query.insert("category".to_string(), category.to_string());
+        response::into_list(self.get_historical_volatility(query).await?)
+    }
+
     /// Query Bybit insurance pool data (BTC/USDT/USDC etc).
     ///     The data is updated every 24 hours.
 
@@ -271,7 +1773,7 @@ impl Market for MarketHTTP {
                 Method::GET,
                 &v5market::MarketEnum::GetInsurance.to_string(),
                 query,
-                true,
+                false,
             )
             .await
     }
@@ -288,10 +1790,20 @@ impl Market for MarketHTTP {
                 Method::GET,
                 &v5market::MarketEnum::GetRiskLimit.to_string(),
                 query,
-                true,
+                false,
             )
             .await
     }
+    /// Same as get_risk_limit, with the tiers typed.
+
+    ///     Returns:
+    ///         Vec<RiskLimit>
+
+    ///     Additional information:
+    ///         https://bybit-exchange.github.io/docs/v5/market/risk-limit
+    async fn get_risk_limit_typed(&self, query: HashMap<String, String>) -> Result<Vec<RiskLimit>> {
+        response::into_list(self.get_risk_limit(query).await?)
+    }
     /// Query Bybit insurance pool data (BTC/USDT/USDC etc).
     ///     The data is updated every 24 hours.
 
@@ -306,8 +1818,122 @@ impl Market for MarketHTTP {
                 Method::GET,
                 &v5market::MarketEnum::GetOptionDeliveryPrice.to_string(),
                 query,
-                true,
+                false,
             )
             .await
     }
+
+    /// Same as get_option_delivery_price, with the prices typed and every page fetched.
+
+    ///     Required args:
+    ///         category (Category): Category::Option, anything else fails with
+    ///             `AppError::InvalidParameter` before sending.
+    ///         query: the other filters (symbol, baseCoin, limit).
+
+    ///     Returns:
+    ///         Vec<DeliveryPrice>
+    async fn get_option_delivery_price_typed(
+        &self,
+        category: Category,
+        mut query: HashMap<String, String>,
+    ) -> Result<Vec<DeliveryPrice>> {
+        require_option(category, "delivery price")?;
+        query.insert("category".to_string(), category.to_string());
+        let items =
+            response::all_pages(query, |query| self.get_option_delivery_price(query)).await?;
+        Ok(items
+            .into_iter()
+            .map(serde_json::from_value)
+            .collect::<std::result::Result<_, _>>()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn number(value: &str) -> Number {
+        value.parse().unwrap()
+    }
+
+    /// BTCUSDT of /v5/market/instruments-info?category=linear.
+    fn btcusdt() -> Instrument {
+        serde_json::from_str(
+            r#"{
+                "symbol": "BTCUSDT",
+                "contractType": "LinearPerpetual",
+                "status": "Trading",
+                "baseCoin": "BTC",
+                "quoteCoin": "USDT",
+                "launchTime": "1585526400000",
+                "deliveryTime": "0",
+                "deliveryFeeRate": "",
+                "priceScale": "2",
+                "leverageFilter": { "minLeverage": "1", "maxLeverage": "100.00", "leverageStep": "0.01" },
+                "priceFilter": { "minPrice": "0.10", "maxPrice": "1999999.80", "tickSize": "0.10" },
+                "lotSizeFilter": {
+                    "maxOrderQty": "1190.000",
+                    "minOrderQty": "0.001",
+                    "qtyStep": "0.001",
+                    "postOnlyMaxOrderQty": "1190.000",
+                    "maxMktOrderQty": "119.000",
+                    "minNotionalValue": "5"
+                },
+                "unifiedMarginTrade": true,
+                "fundingInterval": 480,
+                "settleCoin": "USDT",
+                "copyTrading": "both",
+                "upperFundingRate": "0.00375",
+                "lowerFundingRate": "-0.00375"
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn prices_are_rounded_to_the_nearest_tick() {
+        let instrument = btcusdt();
+        assert_eq!(
+            instrument.round_price(number("65000.27")),
+            number("65000.3")
+        );
+        assert_eq!(
+            instrument.round_price(number("65000.24")),
+            number("65000.2")
+        );
+        assert_eq!(instrument.round_price(number("65000.1")), number("65000.1"));
+    }
+
+    #[test]
+    fn quantities_are_rounded_down_to_the_step_and_clamped() {
+        let instrument = btcusdt();
+        assert_eq!(instrument.round_qty(number("0.0159")), number("0.015"));
+        assert_eq!(instrument.round_qty(number("0.123")), number("0.123"));
+        assert_eq!(instrument.clamp_qty(number("0.0004")), number("0.001"));
+        assert_eq!(instrument.clamp_qty(number("2000")), number("1190"));
+        assert_eq!(instrument.clamp_qty(number("1.2345")), number("1.234"));
+    }
+
+    #[cfg(feature = "export")]
+    #[tokio::test]
+    async fn last_csv_row_cuts_off_a_partial_row() {
+        let path = std::env::temp_dir().join(format!("bybit_rs_klines_{}.csv", std::process::id()));
+        let rows = "start_time,open,high,low,close,volume,turnover\n\
+                    1670601600000,17071,17073,17027,17055.5,268611,1.5731E7\n";
+        tokio::fs::write(&path, format!("{}1670601660000,17055.5,170", rows))
+            .await
+            .unwrap();
+
+        let last = last_csv_row(&path).await.unwrap();
+        assert_eq!(
+            last.as_deref(),
+            Some("1670601600000,17071,17073,17027,17055.5,268611,1.5731E7")
+        );
+        assert_eq!(tokio::fs::read_to_string(&path).await.unwrap(), rows);
+
+        tokio::fs::write(&path, "start_time,op").await.unwrap();
+        assert_eq!(last_csv_row(&path).await.unwrap(), None);
+        assert!(tokio::fs::read_to_string(&path).await.unwrap().is_empty());
+        tokio::fs::remove_file(&path).await.unwrap();
+    }
 }
@@ -3,11 +3,18 @@ pub mod asset;
 pub mod broker;
 pub mod http_manager;
 pub mod market;
+pub mod orderbook;
 pub mod position;
+pub mod proxy;
+pub mod response;
+pub mod signer;
 pub mod spot_leverage_token;
 pub mod spot_margin_trade;
 pub mod trade;
+pub mod types;
 pub mod user;
+pub mod validation;
+pub mod websocket_stream;
 
-type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
-type Result<T> = std::result::Result<T, Error>;
+pub type Error = crate::errors::app_error::AppError;
+pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,320 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    time::{Duration, Instant},
+};
+
+use futures::{stream, Stream, StreamExt};
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+
+use crate::helpers::de;
+
+use super::websocket_stream::{OrderBookMessage, UpdateType};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BookSide {
+    Bid,
+    Ask,
+}
+
+///
+/// Deserializes `[["price", "size"], ...]` level arrays into (price, size) pairs.
+///
+fn levels<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Vec<(Decimal, Decimal)>, D::Error> {
+    let raw: Vec<(String, String)> = serde::Deserialize::deserialize(deserializer)?;
+    raw.into_iter()
+        .map(|(price, size)| {
+            Ok((
+                de::parse_decimal_str(&price)?,
+                de::parse_decimal_str(&size)?,
+            ))
+        })
+        .collect()
+}
+
+///
+/// Order book levels as sent by get_orderbook and the `orderbook.{depth}.{symbol}` topic,
+/// which share this shape. `u` is the update id, `seq` the cross sequence.
+///
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, PartialEq)]
+pub struct OrderBookData {
+    #[serde(rename = "s")]
+    pub symbol: String,
+    #[serde(rename = "b", deserialize_with = "levels")]
+    pub bids: Vec<(Decimal, Decimal)>,
+    #[serde(rename = "a", deserialize_with = "levels")]
+    pub asks: Vec<(Decimal, Decimal)>,
+    #[serde(rename = "u", default)]
+    pub update_id: u64,
+    #[serde(default)]
+    pub seq: u64,
+}
+
+///
+/// Top of an order book at one point in time, each side from the best price outwards.
+/// `ts` and `update_id` are those of the last update included, so a snapshot from
+/// get_orderbook_typed can seed an OrderBook (see `From<OrderBookSnapshot>`).
+///
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, PartialEq)]
+pub struct OrderBookSnapshot {
+    pub symbol: String,
+    pub bids: Vec<(Decimal, Decimal)>,
+    pub asks: Vec<(Decimal, Decimal)>,
+    pub ts: u64,
+    pub update_id: u64,
+}
+
+///
+/// A delta that doesn't follow the last applied update, or arrived before any snapshot.
+/// The local book can't be trusted anymore, resubscribe to get a fresh snapshot.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SequenceGap {
+    pub symbol: String,
+    /// Update id of the last applied message, None before the first snapshot.
+    pub last_update_id: Option<u64>,
+    pub update_id: u64,
+}
+
+impl fmt::Display for SequenceGap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.last_update_id {
+            Some(last) => write!(
+                f,
+                "{} order book gap: update {} after {}",
+                self.symbol, self.update_id, last
+            ),
+            None => write!(
+                f,
+                "{} order book delta {} before any snapshot",
+                self.symbol, self.update_id
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SequenceGap {}
+
+///
+/// Local copy of an order book, price levels keyed by price.
+///
+#[derive(Debug, Clone, Default)]
+pub struct OrderBook {
+    pub symbol: String,
+    bids: BTreeMap<Decimal, Decimal>,
+    asks: BTreeMap<Decimal, Decimal>,
+    last_update_id: Option<u64>,
+    ts: u64,
+}
+
+impl From<OrderBookSnapshot> for OrderBook {
+    fn from(snapshot: OrderBookSnapshot) -> Self {
+        OrderBook {
+            symbol: snapshot.symbol,
+            bids: snapshot.bids.into_iter().collect(),
+            asks: snapshot.asks.into_iter().collect(),
+            last_update_id: Some(snapshot.update_id),
+            ts: snapshot.ts,
+        }
+    }
+}
+
+impl OrderBook {
+    pub fn new(symbol: &str) -> Self {
+        OrderBook {
+            symbol: symbol.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn bids(&self) -> &BTreeMap<Decimal, Decimal> {
+        &self.bids
+    }
+
+    pub fn asks(&self) -> &BTreeMap<Decimal, Decimal> {
+        &self.asks
+    }
+
+    /// Update id of the last message given to `apply`.
+    pub fn last_update_id(&self) -> Option<u64> {
+        self.last_update_id
+    }
+
+    ///
+    /// Applies a push of the orderbook topic. A snapshot replaces the whole book, a delta
+    /// sets the size of its levels (zero removes them) and must carry the update id
+    /// right after the previous one. On a gap the book is cleared and `SequenceGap` is
+    /// returned; it stays empty until the next snapshot, so resubscribe to get one.
+    ///
+    pub fn apply(&mut self, msg: OrderBookMessage) -> std::result::Result<(), SequenceGap> {
+        let data = msg.data;
+        match msg.update_type {
+            UpdateType::Snapshot => {
+                self.bids.clear();
+                self.asks.clear();
+            }
+            UpdateType::Delta => {
+                if self.last_update_id.map(|last| last + 1) != Some(data.update_id) {
+                    let gap = SequenceGap {
+                        symbol: data.symbol,
+                        last_update_id: self.last_update_id,
+                        update_id: data.update_id,
+                    };
+                    self.bids.clear();
+                    self.asks.clear();
+                    self.last_update_id = None;
+                    return Err(gap);
+                }
+            }
+        }
+        self.symbol = data.symbol;
+        for (price, size) in data.bids {
+            self.update_level(BookSide::Bid, price, size);
+        }
+        for (price, size) in data.asks {
+            self.update_level(BookSide::Ask, price, size);
+        }
+        self.last_update_id = Some(data.update_id);
+        self.ts = msg.ts;
+        Ok(())
+    }
+
+    ///
+    /// Sets the size of a price level, a zero size removes the level.
+    ///
+    pub fn update_level(&mut self, side: BookSide, price: Decimal, size: Decimal) {
+        let levels = match side {
+            BookSide::Bid => &mut self.bids,
+            BookSide::Ask => &mut self.asks,
+        };
+        if size.is_zero() {
+            levels.remove(&price);
+        } else {
+            levels.insert(price, size);
+        }
+    }
+
+    pub fn best_bid(&self) -> Option<(Decimal, Decimal)> {
+        self.bids.iter().next_back().map(|(p, s)| (*p, *s))
+    }
+
+    pub fn best_ask(&self) -> Option<(Decimal, Decimal)> {
+        self.asks.iter().next().map(|(p, s)| (*p, *s))
+    }
+
+    ///
+    /// Levels of one side from the best price outwards.
+    ///
+    pub fn levels(&self, side: BookSide) -> Box<dyn Iterator<Item = (Decimal, Decimal)> + '_> {
+        match side {
+            BookSide::Bid => Box::new(self.bids.iter().rev().map(|(p, s)| (*p, *s))),
+            BookSide::Ask => Box::new(self.asks.iter().map(|(p, s)| (*p, *s))),
+        }
+    }
+
+    ///
+    /// Copy of the top `depth` levels of each side.
+    ///
+    pub fn snapshot(&self, depth: usize) -> OrderBookSnapshot {
+        OrderBookSnapshot {
+            symbol: self.symbol.clone(),
+            bids: self.levels(BookSide::Bid).take(depth).collect(),
+            asks: self.levels(BookSide::Ask).take(depth).collect(),
+            ts: self.ts,
+            update_id: self.last_update_id.unwrap_or_default(),
+        }
+    }
+
+    ///
+    /// Order book imbalance over the top `depth` levels of each side:
+    /// (bid_volume - ask_volume) / (bid_volume + ask_volume), in [-1, 1].
+    /// Returns None when both sides are empty.
+    ///
+    pub fn imbalance(&self, depth: usize) -> Option<f64> {
+        let bid_volume: Decimal = self.levels(BookSide::Bid).take(depth).map(|(_, s)| s).sum();
+        let ask_volume: Decimal = self.levels(BookSide::Ask).take(depth).map(|(_, s)| s).sum();
+        let total = bid_volume + ask_volume;
+        if total.is_zero() {
+            return None;
+        }
+        ((bid_volume - ask_volume) / total).to_f64()
+    }
+
+    ///
+    /// Total size resting on `side` from the best price up to and including `price`,
+    /// i.e. bids at or above `price`, asks at or below it.
+    ///
+    pub fn cumulative_depth(&self, side: BookSide, price: Decimal) -> Decimal {
+        match side {
+            BookSide::Bid => self.bids.range(price..).map(|(_, s)| s).sum(),
+            BookSide::Ask => self.asks.range(..=price).map(|(_, s)| s).sum(),
+        }
+    }
+}
+
+///
+/// Maintains `book` from a stream of level changes `(side, price, size)` and yields a
+/// top-`depth` snapshot at most once per `interval`, for consumers that can't keep up
+/// with every delta (e.g. a render loop). Changes arriving in between are applied but
+/// coalesced into the next snapshot. Nothing is emitted while the book is unchanged,
+/// and a last snapshot is emitted when `updates` ends with unreported changes.
+///
+pub fn throttled_snapshots<S>(
+    book: OrderBook,
+    updates: S,
+    depth: usize,
+    interval: Duration,
+) -> impl Stream<Item = OrderBookSnapshot>
+where
+    S: Stream<Item = Vec<(BookSide, Decimal, Decimal)>> + Unpin,
+{
+    struct State<S> {
+        book: OrderBook,
+        updates: S,
+        next_emit: Instant,
+        dirty: bool,
+        done: bool,
+    }
+
+    let state = State {
+        book,
+        updates,
+        next_emit: Instant::now(),
+        dirty: false,
+        done: false,
+    };
+
+    stream::unfold(state, move |mut state| async move {
+        loop {
+            if state.dirty && (state.done || Instant::now() >= state.next_emit) {
+                state.dirty = false;
+                state.next_emit = Instant::now() + interval;
+                return Some((state.book.snapshot(depth), state));
+            }
+            if state.done {
+                return None;
+            }
+
+            let update = if state.dirty {
+                let deadline = tokio::time::sleep_until(state.next_emit.into());
+                tokio::select! {
+                    update = state.updates.next() => update,
+                    _ = deadline => continue,
+                }
+            } else {
+                state.updates.next().await
+            };
+            match update {
+                Some(levels) => {
+                    for (side, price, size) in levels {
+                        state.book.update_level(side, price, size);
+                    }
+                    state.dirty = true;
+                }
+                None => state.done = true,
+            }
+        }
+    })
+}
@@ -3,51 +3,910 @@ use async_trait::async_trait;
 use std::{
     collections::{BTreeMap, HashMap},
     pin::Pin,
-    sync::Arc,
+    sync::{Arc, Mutex},
 };
 
 use futures::Future;
 use reqwest::Method;
 use serde_json::Value;
 
-use crate::endpoints::v5position;
+use crate::{
+    endpoints::{limits, v5market, v5position},
+    errors::app_error::AppError,
+    helpers::{de, symbols, utils},
+};
 
 use super::{
     http_manager::{HttpManager, Manager},
+    market::{Instrument, RiskLimit},
+    response,
+    types::{
+        self, position_idx_hint, Category, ExecType, Number, OrderType, PositionIdx, PositionMode,
+        Side, TradeMode,
+    },
     Result,
 };
 
+/// Risk limit applied by set_risk_limit.
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SetRiskLimitResult {
+    pub category: String,
+    pub risk_id: u64,
+    #[serde(deserialize_with = "de::string_as_f64")]
+    pub risk_limit_value: f64,
+}
+
+///
+/// An execution record, from get_executions_typed or the private `execution` topic.
+/// The REST endpoint sends `category` next to the list, get_executions_typed copies
+/// it onto each record.
+///
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Execution {
+    #[serde(default)]
+    pub category: String,
+    pub symbol: String,
+    pub exec_id: String,
+    pub order_id: String,
+    #[serde(default)]
+    pub order_link_id: String,
+    pub side: Side,
+    /// None where Bybit leaves it empty.
+    #[serde(default, deserialize_with = "de::empty_string_as_none")]
+    pub order_type: Option<OrderType>,
+    pub exec_type: ExecType,
+    #[serde(deserialize_with = "de::string_as_number")]
+    pub exec_qty: Number,
+    #[serde(deserialize_with = "de::string_as_number")]
+    pub exec_price: Number,
+    #[serde(deserialize_with = "de::string_as_number")]
+    pub exec_value: Number,
+    /// Negative for maker rebates.
+    #[serde(deserialize_with = "de::string_as_number")]
+    pub exec_fee: Number,
+    #[serde(deserialize_with = "de::string_as_f64")]
+    pub fee_rate: f64,
+    pub is_maker: bool,
+    #[serde(deserialize_with = "de::string_as_u64")]
+    pub exec_time: u64,
+}
+
+impl Execution {
+    pub fn is_inverse(&self) -> bool {
+        self.category == "inverse"
+    }
+
+    pub fn is_taker(&self) -> bool {
+        !self.is_maker
+    }
+
+    ///
+    /// Value traded. In the quote coin for spot, linear and option; in the base coin
+    /// for inverse, where `exec_qty` counts contracts worth `contract_value` quote
+    /// each (1 USD for Bybit's inverse contracts). `contract_value` is ignored elsewhere.
+    ///
+    pub fn notional(&self, contract_value: f64) -> f64 {
+        let qty = types::number_to_f64(self.exec_qty);
+        let price = types::number_to_f64(self.exec_price);
+        if self.is_inverse() {
+            if price == 0.0 {
+                return 0.0;
+            }
+            qty * contract_value / price
+        } else {
+            qty * price
+        }
+    }
+
+    ///
+    /// Fee converted to the quote coin, negative for maker rebates.
+    /// Inverse fees are charged in the base coin, as are spot buy fees, and are converted
+    /// at the execution price; linear, option and spot sell fees already are in quote.
+    ///
+    pub fn fee_in_quote(&self) -> f64 {
+        let fee_in_base = self.is_inverse() || (self.category == "spot" && self.side == Side::Buy);
+        let fee = types::number_to_f64(self.exec_fee);
+        if fee_in_base {
+            fee * types::number_to_f64(self.exec_price)
+        } else {
+            fee
+        }
+    }
+}
+
+/// A position as returned by get_position. `side` is empty for a flat one-way position.
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PositionInfo {
+    pub symbol: String,
+    #[serde(default)]
+    pub side: String,
+    #[serde(default)]
+    pub position_idx: PositionIdx,
+    #[serde(default)]
+    pub risk_id: u64,
+    #[serde(deserialize_with = "de::string_as_number")]
+    pub size: Number,
+    /// Missing from older `position` topic pushes, which only send `entryPrice`.
+    #[serde(default, deserialize_with = "de::string_as_number")]
+    pub avg_price: Number,
+    #[serde(deserialize_with = "de::string_as_number")]
+    pub position_value: Number,
+    #[serde(deserialize_with = "de::string_as_number")]
+    pub mark_price: Number,
+    #[serde(default, deserialize_with = "de::string_as_option_f64")]
+    pub leverage: Option<f64>,
+    #[serde(default, deserialize_with = "de::string_as_option_number")]
+    pub liq_price: Option<Number>,
+    #[serde(default, deserialize_with = "de::string_as_number")]
+    pub unrealised_pnl: Number,
+}
+
+///
+/// Query of get_positions. Linear queries need a symbol or a coin filter; without one
+/// the default settle coin is used, see PositionHTTP::with_default_settle_coin.
+///
+/// Filters allowed, checked by `validate` before sending:
+///
+/// ```text
+/// linear    symbol or settleCoin
+/// inverse   symbol or settleCoin, or nothing
+/// option    one of symbol, baseCoin, settleCoin, or baseCoin with settleCoin
+/// ```
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct PositionRequest {
+    pub category: Category,
+    pub symbol: Option<String>,
+    pub base_coin: Option<String>,
+    pub settle_coin: Option<String>,
+    /// Page size, at most 200. get_positions follows the cursor over every page.
+    pub limit: Option<u32>,
+}
+
+impl PositionRequest {
+    pub fn new(category: Category) -> Self {
+        PositionRequest {
+            category,
+            symbol: None,
+            base_coin: None,
+            settle_coin: None,
+            limit: None,
+        }
+    }
+
+    pub fn symbol(mut self, symbol: &str) -> Self {
+        self.symbol = Some(symbol.to_string());
+        self
+    }
+
+    /// Option only.
+    pub fn base_coin(mut self, base_coin: &str) -> Self {
+        self.base_coin = Some(base_coin.to_string());
+        self
+    }
+
+    pub fn settle_coin(mut self, settle_coin: &str) -> Self {
+        self.settle_coin = Some(settle_coin.to_string());
+        self
+    }
+
+    pub fn limit(mut self, limit: u32) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// See the type docs, fails with `AppError::InvalidParameter`.
+    pub fn validate(&self) -> Result<()> {
+        symbols::check_coin_filters(
+            self.category,
+            self.symbol.as_deref(),
+            self.base_coin.as_deref(),
+            self.settle_coin.as_deref(),
+            &[Category::Option],
+        )
+    }
+}
+
+impl From<PositionRequest> for HashMap<String, String> {
+    fn from(request: PositionRequest) -> Self {
+        let fields = vec![
+            ("category", Some(request.category.to_string())),
+            ("symbol", request.symbol),
+            ("baseCoin", request.base_coin),
+            ("settleCoin", request.settle_coin),
+            ("limit", request.limit.map(|limit| limit.to_string())),
+        ];
+        fields
+            .into_iter()
+            .filter_map(|(key, value)| Some((key.to_string(), value?)))
+            .collect()
+    }
+}
+
+///
+/// Query of get_executions_typed. Without a window Bybit returns the last 7 days,
+/// and a window can't span more than 7 days.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExecutionRequest {
+    pub category: Category,
+    pub symbol: Option<String>,
+    pub order_id: Option<String>,
+    pub base_coin: Option<String>,
+    /// Milliseconds.
+    pub start_time: Option<u64>,
+    pub end_time: Option<u64>,
+    /// Page size, at most 100. get_executions_typed follows the cursor over every page.
+    pub limit: Option<u32>,
+}
+
+impl ExecutionRequest {
+    pub fn new(category: Category) -> Self {
+        ExecutionRequest {
+            category,
+            symbol: None,
+            order_id: None,
+            base_coin: None,
+            start_time: None,
+            end_time: None,
+            limit: None,
+        }
+    }
+
+    pub fn symbol(mut self, symbol: &str) -> Self {
+        self.symbol = Some(symbol.to_string());
+        self
+    }
+
+    pub fn order_id(mut self, order_id: &str) -> Self {
+        self.order_id = Some(order_id.to_string());
+        self
+    }
+
+    pub fn base_coin(mut self, base_coin: &str) -> Self {
+        self.base_coin = Some(base_coin.to_string());
+        self
+    }
+
+    pub fn start_time(mut self, start_time: u64) -> Self {
+        self.start_time = Some(start_time);
+        self
+    }
+
+    pub fn end_time(mut self, end_time: u64) -> Self {
+        self.end_time = Some(end_time);
+        self
+    }
+
+    pub fn limit(mut self, limit: u32) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+}
+
+impl From<ExecutionRequest> for HashMap<String, String> {
+    fn from(request: ExecutionRequest) -> Self {
+        let fields = vec![
+            ("category", Some(request.category.to_string())),
+            ("symbol", request.symbol),
+            ("orderId", request.order_id),
+            ("baseCoin", request.base_coin),
+            ("startTime", request.start_time.map(|time| time.to_string())),
+            ("endTime", request.end_time.map(|time| time.to_string())),
+            ("limit", request.limit.map(|limit| limit.to_string())),
+        ];
+        fields
+            .into_iter()
+            .filter_map(|(key, value)| Some((key.to_string(), value?)))
+            .collect()
+    }
+}
+
+/// A closed position as returned by get_closed_pnl_typed.
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ClosedPnl {
+    pub symbol: String,
+    pub order_id: String,
+    /// Side of the closing order, Sell for a long position.
+    pub side: Side,
+    #[serde(deserialize_with = "de::string_as_number")]
+    pub qty: Number,
+    #[serde(deserialize_with = "de::string_as_number")]
+    pub order_price: Number,
+    #[serde(deserialize_with = "de::string_as_number")]
+    pub avg_entry_price: Number,
+    #[serde(deserialize_with = "de::string_as_number")]
+    pub avg_exit_price: Number,
+    /// Realized profit net of fees, negative for a loss.
+    #[serde(deserialize_with = "de::string_as_number")]
+    pub closed_pnl: Number,
+    #[serde(deserialize_with = "de::string_as_u64")]
+    pub created_time: u64,
+}
+
+///
+/// Query of get_closed_pnl_typed. Without a window Bybit returns the last 7 days,
+/// and a window can't span more than 7 days.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClosedPnlRequest {
+    pub category: Category,
+    pub symbol: Option<String>,
+    /// Milliseconds.
+    pub start_time: Option<u64>,
+    pub end_time: Option<u64>,
+    /// Page size, at most 100. get_closed_pnl_typed follows the cursor over every page.
+    pub limit: Option<u32>,
+}
+
+impl ClosedPnlRequest {
+    pub fn new(category: Category) -> Self {
+        ClosedPnlRequest {
+            category,
+            symbol: None,
+            start_time: None,
+            end_time: None,
+            limit: None,
+        }
+    }
+
+    pub fn symbol(mut self, symbol: &str) -> Self {
+        self.symbol = Some(symbol.to_string());
+        self
+    }
+
+    pub fn start_time(mut self, start_time: u64) -> Self {
+        self.start_time = Some(start_time);
+        self
+    }
+
+    pub fn end_time(mut self, end_time: u64) -> Self {
+        self.end_time = Some(end_time);
+        self
+    }
+
+    pub fn limit(mut self, limit: u32) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+}
+
+impl From<ClosedPnlRequest> for HashMap<String, String> {
+    fn from(request: ClosedPnlRequest) -> Self {
+        let fields = vec![
+            ("category", Some(request.category.to_string())),
+            ("symbol", request.symbol),
+            ("startTime", request.start_time.map(|time| time.to_string())),
+            ("endTime", request.end_time.map(|time| time.to_string())),
+            ("limit", request.limit.map(|limit| limit.to_string())),
+        ];
+        fields
+            .into_iter()
+            .filter_map(|(key, value)| Some((key.to_string(), value?)))
+            .collect()
+    }
+}
+
+///
+/// Rejects a startTime to endTime window Bybit would refuse, see limits::max_time_window.
+///
+fn check_time_window(
+    path: &str,
+    start_time: Option<u64>,
+    end_time: Option<u64>,
+) -> std::result::Result<(), AppError> {
+    let (start_time, end_time) = match (start_time, end_time) {
+        (Some(start_time), Some(end_time)) => (start_time, end_time),
+        _ => return Ok(()),
+    };
+    if end_time < start_time {
+        return Err(AppError::InvalidParameter(format!(
+            "endTime {} is before startTime {}",
+            end_time, start_time
+        )));
+    }
+    match limits::max_time_window(path) {
+        Some(max) if end_time - start_time > max => Err(AppError::InvalidParameter(format!(
+            "{} takes a window of at most {} days, got {} ms",
+            path,
+            max / (24 * 60 * 60 * 1000),
+            end_time - start_time
+        ))),
+        _ => Ok(()),
+    }
+}
+
+///
+/// New value of a TP/SL field of set_trading_stop_typed. Leaving the field None keeps
+/// the current value, `Clear` sends "0" which removes it.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StopPrice {
+    Set(Number),
+    Clear,
+}
+
+impl StopPrice {
+    fn to_param(self) -> String {
+        match self {
+            StopPrice::Set(price) => types::format_number(price),
+            StopPrice::Clear => "0".to_string(),
+        }
+    }
+}
+
+///
+/// Parameters of set_trading_stop_typed. Only the fields that are set are sent:
+/// `take_profit(p)` sets the take profit, `clear_take_profit()` removes it, and
+/// neither leaves it as it is. The same goes for the stop loss and trailing stop.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct TradingStopRequest {
+    pub category: Category,
+    pub symbol: String,
+    pub take_profit: Option<StopPrice>,
+    pub stop_loss: Option<StopPrice>,
+    /// Trailing distance in price, not a percentage.
+    pub trailing_stop: Option<StopPrice>,
+    /// MarkPrice, IndexPrice or LastPrice (default).
+    pub tp_trigger_by: Option<String>,
+    pub sl_trigger_by: Option<String>,
+    /// Full closes the whole position at market, Partial closes tp_size / sl_size.
+    pub tpsl_mode: Option<String>,
+    pub tp_size: Option<Number>,
+    pub sl_size: Option<Number>,
+    /// OneWay in one-way mode, the leg to protect in hedge mode.
+    pub position_idx: PositionIdx,
+}
+
+impl TradingStopRequest {
+    pub fn new(category: Category, symbol: &str) -> Self {
+        TradingStopRequest {
+            category,
+            symbol: symbol.to_string(),
+            take_profit: None,
+            stop_loss: None,
+            trailing_stop: None,
+            tp_trigger_by: None,
+            sl_trigger_by: None,
+            tpsl_mode: None,
+            tp_size: None,
+            sl_size: None,
+            position_idx: PositionIdx::OneWay,
+        }
+    }
+
+    pub fn take_profit(mut self, take_profit: Number) -> Self {
+        self.take_profit = Some(StopPrice::Set(take_profit));
+        self
+    }
+
+    pub fn clear_take_profit(mut self) -> Self {
+        self.take_profit = Some(StopPrice::Clear);
+        self
+    }
+
+    pub fn stop_loss(mut self, stop_loss: Number) -> Self {
+        self.stop_loss = Some(StopPrice::Set(stop_loss));
+        self
+    }
+
+    pub fn clear_stop_loss(mut self) -> Self {
+        self.stop_loss = Some(StopPrice::Clear);
+        self
+    }
+
+    pub fn trailing_stop(mut self, trailing_stop: Number) -> Self {
+        self.trailing_stop = Some(StopPrice::Set(trailing_stop));
+        self
+    }
+
+    pub fn clear_trailing_stop(mut self) -> Self {
+        self.trailing_stop = Some(StopPrice::Clear);
+        self
+    }
+
+    pub fn tp_trigger_by(mut self, tp_trigger_by: &str) -> Self {
+        self.tp_trigger_by = Some(tp_trigger_by.to_string());
+        self
+    }
+
+    pub fn sl_trigger_by(mut self, sl_trigger_by: &str) -> Self {
+        self.sl_trigger_by = Some(sl_trigger_by.to_string());
+        self
+    }
+
+    pub fn tpsl_mode(mut self, tpsl_mode: &str) -> Self {
+        self.tpsl_mode = Some(tpsl_mode.to_string());
+        self
+    }
+
+    /// Partial mode only.
+    pub fn tp_size(mut self, tp_size: Number) -> Self {
+        self.tp_size = Some(tp_size);
+        self
+    }
+
+    /// Partial mode only.
+    pub fn sl_size(mut self, sl_size: Number) -> Self {
+        self.sl_size = Some(sl_size);
+        self
+    }
+
+    pub fn position_idx(mut self, position_idx: PositionIdx) -> Self {
+        self.position_idx = position_idx;
+        self
+    }
+}
+
+impl From<TradingStopRequest> for HashMap<String, String> {
+    fn from(request: TradingStopRequest) -> Self {
+        let fields = vec![
+            ("category", Some(request.category.to_string())),
+            ("symbol", Some(request.symbol)),
+            ("takeProfit", request.take_profit.map(StopPrice::to_param)),
+            ("stopLoss", request.stop_loss.map(StopPrice::to_param)),
+            (
+                "trailingStop",
+                request.trailing_stop.map(StopPrice::to_param),
+            ),
+            ("tpTriggerBy", request.tp_trigger_by),
+            ("slTriggerBy", request.sl_trigger_by),
+            ("tpslMode", request.tpsl_mode),
+            ("tpSize", request.tp_size.map(types::format_number)),
+            ("slSize", request.sl_size.map(types::format_number)),
+            ("positionIdx", Some(request.position_idx.to_string())),
+        ];
+        fields
+            .into_iter()
+            .filter_map(|(key, value)| Some((key.to_string(), value?)))
+            .collect()
+    }
+}
+
+/// One step of a symbol's margin ladder, see margin_ladder.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarginRung {
+    pub risk_id: u64,
+    /// Largest position value of the tier.
+    pub position_value_cap: f64,
+    pub max_leverage: f64,
+    pub maintenance_margin_rate: f64,
+    /// Maintenance margin of a position sitting exactly at the cap.
+    pub maintenance_margin_at_cap: f64,
+}
+
+/// Where a position stands on its margin ladder, see margin_status.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarginStatus {
+    pub risk_id: u64,
+    pub maintenance_margin_rate: f64,
+    /// Maintenance margin currently required, in the settle coin.
+    pub maintenance_margin: f64,
+    /// Position value at which the next tier starts, None on the last tier.
+    pub next_tier_value: Option<f64>,
+    /// How far the mark price can rise before the position value reaches the next tier.
+    /// Position value grows with the price for longs and shorts alike, so this is
+    /// always upwards. None on the last tier or for a flat position.
+    pub price_to_next_tier: Option<f64>,
+}
+
+fn maintenance_margin(tier: &RiskLimit, position_value: f64) -> f64 {
+    (position_value * tier.maintenance_margin - tier.mm_deduction.unwrap_or(0.0)).max(0.0)
+}
+
+///
+/// Risk limit tiers sorted by position value cap, with the maintenance margin each
+/// tier requires at its cap, e.g. to chart how requirements step up with size.
+///
+pub fn margin_ladder(tiers: &[RiskLimit]) -> Vec<MarginRung> {
+    let mut tiers: Vec<&RiskLimit> = tiers.iter().collect();
+    tiers.sort_by(|a, b| a.risk_limit_value.total_cmp(&b.risk_limit_value));
+    tiers
+        .into_iter()
+        .map(|tier| MarginRung {
+            risk_id: tier.id,
+            position_value_cap: tier.risk_limit_value,
+            max_leverage: tier.max_leverage,
+            maintenance_margin_rate: tier.maintenance_margin,
+            maintenance_margin_at_cap: maintenance_margin(tier, tier.risk_limit_value),
+        })
+        .collect()
+}
+
+///
+/// Maintenance margin of a position and its distance to the next tier.
+/// The tier is the position's riskId when it is one of `tiers`, otherwise the smallest
+/// tier whose cap covers the position value (how unified accounts pick it).
+/// None when `tiers` is empty.
+///
+pub fn margin_status(position: &PositionInfo, tiers: &[RiskLimit]) -> Option<MarginStatus> {
+    let position_value = types::number_to_f64(position.position_value);
+    let size = types::number_to_f64(position.size);
+    let mark_price = types::number_to_f64(position.mark_price);
+    let ladder = margin_ladder(tiers);
+    let index = ladder
+        .iter()
+        .position(|rung| rung.risk_id == position.risk_id)
+        .or_else(|| {
+            ladder
+                .iter()
+                .position(|rung| rung.position_value_cap >= position_value)
+        })
+        .unwrap_or(ladder.len().checked_sub(1)?);
+    let rung = &ladder[index];
+    let tier = tiers.iter().find(|tier| tier.id == rung.risk_id)?;
+
+    let next_tier_value = ladder.get(index + 1).map(|_| rung.position_value_cap);
+    let price_to_next_tier = next_tier_value
+        .filter(|_| size > 0.0)
+        .map(|boundary| (boundary / size - mark_price).max(0.0));
+    Some(MarginStatus {
+        risk_id: rung.risk_id,
+        maintenance_margin_rate: rung.maintenance_margin_rate,
+        maintenance_margin: maintenance_margin(tier, position_value),
+        next_tier_value,
+        price_to_next_tier,
+    })
+}
+
+/// Leverage applied by set_leverage_typed. Both sides are equal in one-way mode.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AppliedLeverage {
+    pub buy_leverage: f64,
+    pub sell_leverage: f64,
+}
+
+/// Bybit's retCode when the requested leverage is already set.
+const LEVERAGE_NOT_MODIFIED: i32 = 110043;
+/// Bybit's retCode when the requested position mode is already set.
+const POSITION_MODE_NOT_MODIFIED: i32 = 110025;
+/// Bybit's retCode when the requested cross / isolated margin mode is already set.
+const MARGIN_MODE_NOT_MODIFIED: i32 = 110026;
+
+/// What switch_position_mode_typed switches: one symbol, or every symbol settled in a coin.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ModeScope {
+    Symbol(String),
+    Coin(String),
+}
+
 #[async_trait]
 pub trait Position {
     fn new(http_manager: Arc<HttpManager>) -> Self;
     async fn get_position(&self, query: HashMap<String, String>) -> Result<Value>;
 
+    async fn get_position_typed(&self, query: HashMap<String, String>)
+        -> Result<Vec<PositionInfo>>;
+
+    async fn get_positions(&self, request: PositionRequest) -> Result<Vec<PositionInfo>>;
+
     async fn set_leverage(&self, query: HashMap<String, String>) -> Result<Value>;
 
+    async fn set_leverage_typed(
+        &self,
+        category: &str,
+        symbol: &str,
+        buy_leverage: f64,
+        sell_leverage: Option<f64>,
+    ) -> Result<AppliedLeverage>;
+
+    async fn set_leverage_with_risk_limit(
+        &self,
+        category: &str,
+        symbol: &str,
+        leverage: f64,
+        position_idx: PositionIdx,
+    ) -> Result<Value>;
+
     async fn switch_margin_mode(&self, query: HashMap<String, String>) -> Result<Value>;
 
+    async fn switch_margin_mode_typed(
+        &self,
+        category: Category,
+        symbol: &str,
+        trade_mode: TradeMode,
+        leverage: f64,
+    ) -> Result<Value>;
+
     async fn set_tp_sl_mode(&self, query: HashMap<String, String>) -> Result<Value>;
 
     async fn switch_position_mode(&self, query: HashMap<String, String>) -> Result<Value>;
 
+    async fn switch_position_mode_typed(
+        &self,
+        category: Category,
+        scope: ModeScope,
+        mode: PositionMode,
+    ) -> Result<Value>;
+
     async fn set_risk_limit(&self, query: HashMap<String, String>) -> Result<Value>;
 
+    async fn set_risk_limit_typed(
+        &self,
+        category: &str,
+        symbol: &str,
+        risk_id: u64,
+        position_idx: PositionIdx,
+    ) -> Result<SetRiskLimitResult>;
+
     async fn set_trading_stop(&self, query: HashMap<String, String>) -> Result<Value>;
+
+    async fn set_trading_stop_typed(&self, request: TradingStopRequest) -> Result<Value>;
     async fn set_auto_add_margin(&self, query: HashMap<String, String>) -> Result<Value>;
 
     async fn get_executions(&self, query: HashMap<String, String>) -> Result<Value>;
 
+    async fn get_executions_typed(&self, request: ExecutionRequest) -> Result<Vec<Execution>>;
+
     async fn get_closed_pnl(&self, query: HashMap<String, String>) -> Result<Value>;
+
+    async fn get_closed_pnl_typed(&self, request: ClosedPnlRequest) -> Result<Vec<ClosedPnl>>;
 }
 
 pub struct PositionHTTP {
     http_manager: Arc<HttpManager>,
+    risk_limits: Mutex<HashMap<(String, String), Vec<RiskLimit>>>,
+    position_modes: Mutex<HashMap<(String, String), PositionMode>>,
+    default_settle_coin: Option<String>,
+}
+
+impl PositionHTTP {
+    ///
+    /// Settle coin sent by get_position for linear queries without symbol, baseCoin
+    /// or settleCoin. Defaults to USDT, None turns it off.
+    ///
+    pub fn with_default_settle_coin(mut self, settle_coin: Option<&str>) -> Self {
+        self.default_settle_coin = settle_coin.map(str::to_string);
+        self
+    }
+
+    ///
+    /// Risk limit tiers of a symbol, fetched once per (category, symbol) and cached.
+    ///
+    pub async fn risk_limit_tiers(&self, category: &str, symbol: &str) -> Result<Vec<RiskLimit>> {
+        let key = (category.to_string(), symbol.to_string());
+        if let Some(tiers) = self.risk_limits.lock().unwrap().get(&key) {
+            return Ok(tiers.clone());
+        }
+
+        let mut query = HashMap::new();
+        query.insert("category".to_string(), category.to_string());
+        query.insert("symbol".to_string(), symbol.to_string());
+        let tiers: Vec<RiskLimit> = response::into_list(
+            self.http_manager
+                .submit_request(
+                    Method::GET,
+                    &v5market::MarketEnum::GetRiskLimit.to_string(),
+                    query,
+                    false,
+                )
+                .await?,
+        )?;
+        self.risk_limits.lock().unwrap().insert(key, tiers.clone());
+        Ok(tiers)
+    }
+
+    ///
+    /// Drops the cached risk limit tiers, e.g. after Bybit updated them.
+    ///
+    pub fn clear_risk_limit_cache(&self) {
+        self.risk_limits.lock().unwrap().clear();
+    }
+
+    ///
+    /// Maintenance margin and next tier distance of each position of a symbol
+    /// (one per leg in hedge mode), see margin_status.
+    ///
+    pub async fn margin_status(
+        &self,
+        category: &str,
+        symbol: &str,
+    ) -> Result<Vec<(PositionInfo, MarginStatus)>> {
+        let tiers = self.risk_limit_tiers(category, symbol).await?;
+        let mut query = HashMap::new();
+        query.insert("category".to_string(), category.to_string());
+        query.insert("symbol".to_string(), symbol.to_string());
+        Ok(self
+            .get_position_typed(query)
+            .await?
+            .into_iter()
+            .filter_map(|position| {
+                margin_status(&position, &tiers).map(|status| (position, status))
+            })
+            .collect())
+    }
+
+    ///
+    /// Current position mode of a linear / inverse symbol, cached per (category, symbol).
+    /// Bybit has no endpoint for it, it is read from the positionIdx of the symbol's
+    /// position list, which lists both legs in hedge mode even when flat.
+    /// The cache is dropped for the category by switch_position_mode.
+    ///
+    pub async fn get_position_mode(&self, category: &str, symbol: &str) -> Result<PositionMode> {
+        let key = (category.to_string(), symbol.to_string());
+        if let Some(mode) = self.position_modes.lock().unwrap().get(&key) {
+            return Ok(*mode);
+        }
+
+        let mut query = HashMap::new();
+        query.insert("category".to_string(), category.to_string());
+        query.insert("symbol".to_string(), symbol.to_string());
+        let positions: Vec<Value> = response::into_list(self.get_position(query).await?)?;
+        let mode = positions
+            .iter()
+            .filter_map(|p| p.get("positionIdx").and_then(Value::as_u64))
+            .find_map(PositionMode::from_position_idx)
+            .ok_or_else(|| {
+                AppError::InvalidParameter(format!(
+                    "no position entry to read the position mode of {} from",
+                    symbol
+                ))
+            })?;
+        self.position_modes.lock().unwrap().insert(key, mode);
+        Ok(mode)
+    }
+
+    ///
+    /// Fills in `positionIdx` of a linear / inverse place_order query from the symbol's
+    /// position mode, taking side and reduceOnly into account in hedge mode.
+    /// A positionIdx already set is checked against the mode instead, so the order
+    /// fails here rather than with a 10001 from Bybit. Other categories are left untouched.
+    ///
+    pub async fn apply_position_idx(&self, order: &mut HashMap<String, String>) -> Result<()> {
+        let category = order.get("category").cloned().unwrap_or_default();
+        if category != "linear" && category != "inverse" {
+            return Ok(());
+        }
+        let symbol = order.get("symbol").cloned().unwrap_or_default();
+        let side = match order.get("side").map(String::as_str) {
+            Some("Buy") => Side::Buy,
+            Some("Sell") => Side::Sell,
+            _ => {
+                return Err(AppError::InvalidParameter(
+                    "side must be Buy or Sell".to_string(),
+                ))
+            }
+        };
+        let reduce_only = order.get("reduceOnly").map(String::as_str) == Some("true");
+
+        let mode = self.get_position_mode(&category, &symbol).await?;
+        match order.get("positionIdx") {
+            Some(idx) => {
+                let matches = idx
+                    .parse::<u64>()
+                    .ok()
+                    .and_then(PositionMode::from_position_idx)
+                    == Some(mode);
+                if !matches {
+                    return Err(AppError::InvalidParameter(format!(
+                        "positionIdx {} does not match the {:?} position mode of {}",
+                        idx, mode, symbol
+                    )));
+                }
+            }
+            None => {
+                let idx = mode.position_idx(side, reduce_only);
+                order.insert("positionIdx".to_string(), idx.to_string());
+            }
+        }
+        Ok(())
+    }
 }
 
 #[async_trait]
 impl Position for PositionHTTP {
     fn new(http_manager: Arc<HttpManager>) -> Self {
-        PositionHTTP { http_manager }
+        PositionHTTP {
+            http_manager,
+            risk_limits: Mutex::new(HashMap::new()),
+            position_modes: Mutex::new(HashMap::new()),
+            default_settle_coin: Some("USDT".to_string()),
+        }
     }
 
     /// Query real-time position data, such as position size, cumulative realizedPNL.
@@ -64,13 +923,37 @@ impl Position for PositionHTTP {
 
     ///     Additional information:
     ///         https://bybit-exchange.github.io/docs/v5/position
-    async fn get_position(&self, query: HashMap<String, String>) -> Result<Value> {
+    async fn get_position(&self, mut query: HashMap<String, String>) -> Result<Value> {
+        symbols::apply_default_settle_coin(&mut query, self.default_settle_coin.as_deref());
         let path = v5position::Position::GetPositions.to_string();
         self.http_manager
             .submit_request(Method::GET, &path, query, true)
             .await
     }
 
+    ///
+    /// get_position deserialized into `PositionInfo`s, see get_position for the arguments.
+    ///
+    async fn get_position_typed(
+        &self,
+        query: HashMap<String, String>,
+    ) -> Result<Vec<PositionInfo>> {
+        response::into_list(self.get_position(query).await?)
+    }
+
+    ///
+    /// Every position matching `request`, following nextPageCursor over all pages.
+    /// Flat one-way positions are included with a zero size and an empty side.
+    ///
+    async fn get_positions(&self, request: PositionRequest) -> Result<Vec<PositionInfo>> {
+        request.validate()?;
+        let items = response::all_pages(request.into(), |query| self.get_position(query)).await?;
+        Ok(items
+            .into_iter()
+            .map(serde_json::from_value)
+            .collect::<std::result::Result<_, _>>()?)
+    }
+
     ///Set the leverage
 
     ///    Required args:
@@ -97,6 +980,137 @@ impl Position for PositionHTTP {
             .await
     }
 
+    /// Set the leverage with the one-way / hedge rules applied.
+    ///     One-way mode needs buyLeverage == sellLeverage, so sell_leverage must be None or
+    ///     equal to buy_leverage. In hedge mode each side can differ, None means the same as
+    ///     buy_leverage. Both values are checked against the instrument leverage filter
+    ///     before sending, and "leverage not modified" (110043) counts as applied.
+
+    ///     Required args:
+    ///         category (string): Product type. linear,inverse
+    ///         symbol (string): Symbol name
+    ///         buy_leverage (f64): leverage of the long side, or of the position in one-way mode
+    ///         sell_leverage (Option<f64>): leverage of the short side, hedge mode only
+
+    ///     Returns:
+    ///         The applied AppliedLeverage.
+    async fn set_leverage_typed(
+        &self,
+        category: &str,
+        symbol: &str,
+        buy_leverage: f64,
+        sell_leverage: Option<f64>,
+    ) -> Result<AppliedLeverage> {
+        let mode = self.get_position_mode(category, symbol).await?;
+        let sell_leverage = match (mode, sell_leverage) {
+            (PositionMode::OneWay, Some(sell)) if sell != buy_leverage => {
+                return Err(AppError::InvalidParameter(format!(
+                    "{} is in one-way mode, buy and sell leverage must be equal",
+                    symbol
+                )));
+            }
+            (_, sell) => sell.unwrap_or(buy_leverage),
+        };
+
+        let mut query = HashMap::new();
+        query.insert("category".to_string(), category.to_string());
+        query.insert("symbol".to_string(), symbol.to_string());
+        let instruments: Vec<Instrument> = response::into_list(
+            self.http_manager
+                .submit_request(
+                    Method::GET,
+                    &v5market::MarketEnum::GetInstrumentsInfo.to_string(),
+                    query.clone(),
+                    false,
+                )
+                .await?,
+        )?;
+        let instrument = instruments
+            .iter()
+            .find(|instrument| instrument.symbol == symbol)
+            .ok_or_else(|| AppError::InvalidParameter(format!("unknown symbol {}", symbol)))?;
+        for leverage in [buy_leverage, sell_leverage] {
+            if !instrument.valid_leverage(leverage) {
+                return Err(AppError::InvalidParameter(format!(
+                    "leverage {} is outside the leverage filter of {}",
+                    leverage, symbol
+                )));
+            }
+        }
+
+        query.insert(
+            "buyLeverage".to_string(),
+            utils::format_number(buy_leverage, 2),
+        );
+        query.insert(
+            "sellLeverage".to_string(),
+            utils::format_number(sell_leverage, 2),
+        );
+        response::accept_not_modified(self.set_leverage(query).await?, LEVERAGE_NOT_MODIFIED)?;
+        Ok(AppliedLeverage {
+            buy_leverage,
+            sell_leverage,
+        })
+    }
+
+    /// Set the leverage, first moving the position to a risk limit tier that allows it.
+    ///     Bybit rejects a leverage above the max leverage of the current tier. When that is
+    ///     the case this picks the largest tier whose max leverage covers the request, calls
+    ///     set_risk_limit and then set_leverage with the same buy and sell leverage.
+    ///     Classic accounts only, unified accounts have their risk limit managed automatically.
+
+    ///     Required args:
+    ///         category (string): Product type. linear,inverse
+    ///         symbol (string): Symbol name
+    ///         leverage (f64): leverage for both sides
+    ///         position_idx (PositionIdx): OneWay, or BuyHedge / SellHedge in hedge mode
+
+    ///     Returns:
+    ///         The set_leverage response.
+    async fn set_leverage_with_risk_limit(
+        &self,
+        category: &str,
+        symbol: &str,
+        leverage: f64,
+        position_idx: PositionIdx,
+    ) -> Result<Value> {
+        let mut query = HashMap::new();
+        query.insert("category".to_string(), category.to_string());
+        query.insert("symbol".to_string(), symbol.to_string());
+
+        let tiers = self.risk_limit_tiers(category, symbol).await?;
+        let positions: Vec<Value> = response::into_list(self.get_position(query.clone()).await?)?;
+        let current_risk_id = positions
+            .iter()
+            .find(|p| {
+                p.get("positionIdx").and_then(Value::as_u64) == Some(position_idx.as_u8() as u64)
+            })
+            .and_then(|p| p.get("riskId").and_then(Value::as_u64));
+
+        let current_tier = tiers.iter().find(|t| Some(t.id) == current_risk_id);
+        if current_tier.map_or(true, |tier| tier.max_leverage < leverage) {
+            let target = tiers
+                .iter()
+                .filter(|tier| tier.max_leverage >= leverage)
+                .max_by(|a, b| a.risk_limit_value.total_cmp(&b.risk_limit_value))
+                .ok_or_else(|| {
+                    AppError::InvalidParameter(format!(
+                        "no risk limit tier of {} allows leverage {}",
+                        symbol, leverage
+                    ))
+                })?;
+            if Some(target.id) != current_risk_id {
+                self.set_risk_limit_typed(category, symbol, target.id, position_idx)
+                    .await?;
+            }
+        }
+
+        let leverage = utils::format_number(leverage, 2);
+        query.insert("buyLeverage".to_string(), leverage.clone());
+        query.insert("sellLeverage".to_string(), leverage);
+        self.set_leverage(query).await
+    }
+
     /// Select cross margin mode or isolated margin mode
 
     ///     Required args:
@@ -120,6 +1134,35 @@ impl Position for PositionHTTP {
             .await
     }
 
+    ///
+    /// switch_margin_mode of a classic account symbol to cross or isolated margin, with
+    /// `leverage` for both sides. Already being in `trade_mode` counts as success
+    /// (110026), other non-zero retCodes are returned as `AppError::Api`.
+    ///
+    async fn switch_margin_mode_typed(
+        &self,
+        category: Category,
+        symbol: &str,
+        trade_mode: TradeMode,
+        leverage: f64,
+    ) -> Result<Value> {
+        let leverage = utils::format_number(leverage, 2);
+        let query = vec![
+            ("category", category.to_string()),
+            ("symbol", symbol.to_string()),
+            ("tradeMode", trade_mode.trade_mode_code().to_string()),
+            ("buyLeverage", leverage.clone()),
+            ("sellLeverage", leverage),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value))
+        .collect();
+        response::accept_not_modified(
+            self.switch_margin_mode(query).await?,
+            MARGIN_MODE_NOT_MODIFIED,
+        )
+    }
+
     /// Set TP/SL mode to Full or Partial
 
     ///     Required args:
@@ -158,9 +1201,41 @@ impl Position for PositionHTTP {
     ///         https://bybit-exchange.github.io/docs/v5/position/position-mode
     async fn switch_position_mode(&self, query: HashMap<String, String>) -> Result<Value> {
         let path = v5position::Position::SwitchPositionMode.to_string();
-        self.http_manager
+        let category = query.get("category").cloned().unwrap_or_default();
+        let result = self
+            .http_manager
             .submit_post_request(Method::POST, &path, true, query)
-            .await
+            .await;
+        // A coin wide switch changes every symbol settled in it, drop the whole category.
+        self.position_modes
+            .lock()
+            .unwrap()
+            .retain(|(cached_category, _), _| *cached_category != category);
+        result
+    }
+
+    ///
+    /// switch_position_mode of one symbol or of every symbol of a settle coin.
+    /// Already being in `mode` counts as success (110025), other non-zero retCodes are
+    /// returned as `AppError::Api`.
+    ///
+    async fn switch_position_mode_typed(
+        &self,
+        category: Category,
+        scope: ModeScope,
+        mode: PositionMode,
+    ) -> Result<Value> {
+        let mut query = HashMap::new();
+        query.insert("category".to_string(), category.to_string());
+        match scope {
+            ModeScope::Symbol(symbol) => query.insert("symbol".to_string(), symbol),
+            ModeScope::Coin(coin) => query.insert("coin".to_string(), coin),
+        };
+        query.insert("mode".to_string(), mode.mode_code().to_string());
+        response::accept_not_modified(
+            self.switch_position_mode(query).await?,
+            POSITION_MODE_NOT_MODIFIED,
+        )
     }
     /// The risk limit will limit the maximum position value you can hold under different margin requirements.
     ///     If you want to hold a bigger position size, you need more margin. This interface can set the risk limit of a single position.
@@ -186,6 +1261,44 @@ impl Position for PositionHTTP {
             .submit_post_request(Method::POST, &path, true, query)
             .await
     }
+
+    /// Same as set_risk_limit, with typed arguments and response.
+    ///     risk_id is checked against the symbol's tiers (cached, see risk_limit_tiers)
+    ///     before sending.
+
+    ///     Required args:
+    ///         category (string): Product type. linear,inverse
+    ///         symbol (string): Symbol name
+    ///         risk_id (u64): Risk limit ID
+    ///         position_idx (PositionIdx): OneWay, or BuyHedge / SellHedge in hedge mode
+
+    ///     Returns:
+    ///         SetRiskLimitResult with the applied risk id and limit.
+
+    ///     Additional information:
+    ///         https://bybit-exchange.github.io/docs/v5/position/set-risk-limit
+    async fn set_risk_limit_typed(
+        &self,
+        category: &str,
+        symbol: &str,
+        risk_id: u64,
+        position_idx: PositionIdx,
+    ) -> Result<SetRiskLimitResult> {
+        let tiers = self.risk_limit_tiers(category, symbol).await?;
+        if !tiers.iter().any(|tier| tier.id == risk_id) {
+            return Err(AppError::InvalidParameter(format!(
+                "risk id {} is not a risk limit tier of {}",
+                risk_id, symbol
+            )));
+        }
+
+        let mut query = HashMap::new();
+        query.insert("category".to_string(), category.to_string());
+        query.insert("symbol".to_string(), symbol.to_string());
+        query.insert("riskId".to_string(), risk_id.to_string());
+        query.insert("positionIdx".to_string(), position_idx.to_string());
+        response::into_result(self.set_risk_limit(query).await?)
+    }
     /// Set the trading stop condition
     ///    Required args:
     ///       category (string): Product type
@@ -205,6 +1318,17 @@ impl Position for PositionHTTP {
             .await
     }
 
+    ///
+    /// set_trading_stop from a TradingStopRequest, see StopPrice for the difference
+    /// between leaving a TP/SL unchanged and clearing it.
+    ///
+    async fn set_trading_stop_typed(&self, request: TradingStopRequest) -> Result<Value> {
+        let position_idx = request.position_idx;
+        self.set_trading_stop(request.into())
+            .await
+            .map_err(|err| position_idx_hint(err, Some(position_idx)))
+    }
+
     /// Turn on/off auto-add-margin for isolated margin position
 
     ///     Required args:
@@ -246,6 +1370,29 @@ impl Position for PositionHTTP {
             .await
     }
 
+    ///
+    /// Every execution matching `request`, newest first, following nextPageCursor
+    /// over all pages. Funding and settlement records are included, see
+    /// ExecType::is_fill. A window over 7 days fails with `AppError::InvalidParameter`.
+    ///
+    async fn get_executions_typed(&self, request: ExecutionRequest) -> Result<Vec<Execution>> {
+        let path = v5position::Position::GetExecutions.to_string();
+        check_time_window(&path, request.start_time, request.end_time)?;
+        let category = request.category.to_string();
+        let items = response::all_pages(request.into(), |query| self.get_executions(query)).await?;
+        let mut executions = items
+            .into_iter()
+            .map(serde_json::from_value::<Execution>)
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        for execution in executions
+            .iter_mut()
+            .filter(|execution| execution.category.is_empty())
+        {
+            execution.category = category.clone();
+        }
+        Ok(executions)
+    }
+
     /// Query user's closed profit and loss records. The results are sorted by createdTime in descending order.
 
     ///     Required args:
@@ -266,4 +1413,116 @@ impl Position for PositionHTTP {
             .submit_request(Method::GET, &path, query, true)
             .await
     }
+
+    ///
+    /// Every closed position matching `request`, newest first, following
+    /// nextPageCursor over all pages. A window over 7 days fails with
+    /// `AppError::InvalidParameter` before anything is sent.
+    ///
+    async fn get_closed_pnl_typed(&self, request: ClosedPnlRequest) -> Result<Vec<ClosedPnl>> {
+        let path = v5position::Position::GetClosedPnl.to_string();
+        check_time_window(&path, request.start_time, request.end_time)?;
+        let items = response::all_pages(request.into(), |query| self.get_closed_pnl(query)).await?;
+        Ok(items
+            .into_iter()
+            .map(serde_json::from_value)
+            .collect::<std::result::Result<_, _>>()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    /// A row of /v5/execution/list with the fields that matter here replaced.
+    fn execution(
+        category: &str,
+        symbol: &str,
+        side: &str,
+        qty: &str,
+        price: &str,
+        fee: &str,
+    ) -> Execution {
+        let is_maker = fee.starts_with('-');
+        serde_json::from_value(json!({
+            "category": category,
+            "symbol": symbol,
+            "execFee": fee,
+            "execId": "7e2ae69c-4edf-5800-a352-893d52b446aa",
+            "execPrice": price,
+            "execQty": qty,
+            "execType": "Trade",
+            "execValue": "0",
+            "feeRate": if is_maker { "-0.00025" } else { "0.00055" },
+            "isMaker": is_maker,
+            "orderId": "c0efdf3e-a4b6-4b8f-a02d-6c8e5a8db1f1",
+            "orderLinkId": "",
+            "orderType": "Limit",
+            "side": side,
+            "execTime": "1672364174443"
+        }))
+        .unwrap()
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn a_linear_maker_rebate_is_a_negative_quote_fee() {
+        let row = execution("linear", "BTCUSDT", "Sell", "0.5", "30000", "-3.75");
+        assert!(!row.is_taker());
+        assert_close(row.notional(1.0), 15000.0);
+        assert_close(row.fee_in_quote(), -3.75);
+    }
+
+    #[test]
+    fn inverse_fees_and_notional_are_converted_at_the_execution_price() {
+        // 1000 contracts of 1 USD at 25000: 0.04 BTC, rebate of 0.00001 BTC
+        let row = execution("inverse", "BTCUSD", "Buy", "1000", "25000", "-0.00001");
+        assert_close(row.notional(1.0), 0.04);
+        assert_close(row.fee_in_quote(), -0.25);
+    }
+
+    #[test]
+    fn spot_buy_fees_are_charged_in_the_base_coin() {
+        let buy = execution("spot", "BTCUSDT", "Buy", "0.01", "20000", "0.00001");
+        assert_close(buy.notional(1.0), 200.0);
+        assert_close(buy.fee_in_quote(), 0.2);
+        let sell = execution("spot", "BTCUSDT", "Sell", "0.01", "20000", "0.2");
+        assert_close(sell.fee_in_quote(), 0.2);
+    }
+
+    #[test]
+    fn trading_stop_omits_unset_fields_and_sends_clear_as_zero() {
+        let request = TradingStopRequest::new(Category::Linear, "BTCUSDT")
+            .take_profit("30000".parse().unwrap())
+            .clear_stop_loss();
+        let query = HashMap::from(request);
+        assert_eq!(query["takeProfit"], "30000");
+        assert_eq!(query["stopLoss"], "0");
+        for field in [
+            "trailingStop",
+            "tpTriggerBy",
+            "slTriggerBy",
+            "tpslMode",
+            "tpSize",
+            "slSize",
+        ] {
+            assert!(!query.contains_key(field), "{} was sent", field);
+        }
+    }
+
+    #[test]
+    fn stop_price_params() {
+        assert_eq!(StopPrice::Set("0.5".parse().unwrap()).to_param(), "0.5");
+        assert_eq!(StopPrice::Clear.to_param(), "0");
+    }
 }
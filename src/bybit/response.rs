@@ -0,0 +1,620 @@
+use std::collections::HashMap;
+
+use futures::Future;
+use serde::{de::DeserializeOwned, Deserialize, Deserializer};
+use serde_json::Value;
+
+use crate::errors::app_error::{self, AppError};
+
+use super::{http_manager::Response, Result};
+
+///
+/// A record deserialized into `T` for the fields the crate models, along with
+/// the raw JSON it came from for everything else.
+/// Derefs to `T`, so typed fields can be used directly.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartiallyTyped<T> {
+    pub typed: T,
+    pub raw: Value,
+}
+
+impl<T: DeserializeOwned> PartiallyTyped<T> {
+    pub fn from_value(raw: Value) -> serde_json::Result<Self> {
+        let typed = T::deserialize(&raw)?;
+        Ok(PartiallyTyped { typed, raw })
+    }
+
+    ///
+    /// Looks up a field the typed struct doesn't cover, e.g. `get("basisRate")`.
+    ///
+    pub fn get(&self, field: &str) -> Option<&Value> {
+        self.raw.get(field)
+    }
+}
+
+impl<T> std::ops::Deref for PartiallyTyped<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.typed
+    }
+}
+
+/// Serializes the raw JSON, which deserializes back into the same value.
+impl<T> serde::Serialize for PartiallyTyped<T> {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        self.raw.serialize(serializer)
+    }
+}
+
+impl<'de, T: DeserializeOwned> Deserialize<'de> for PartiallyTyped<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let raw = Value::deserialize(deserializer)?;
+        PartiallyTyped::from_value(raw).map_err(serde::de::Error::custom)
+    }
+}
+
+///
+/// The envelope of every v5 response, `{ retCode, retMsg, result, retExtInfo, time }`,
+/// with `result` deserialized into `T`.
+///
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiResponse<T> {
+    pub ret_code: i32,
+    #[serde(default)]
+    pub ret_msg: String,
+    pub result: T,
+    #[serde(default)]
+    pub ret_ext_info: Value,
+    /// Server time of the response, ms.
+    #[serde(default)]
+    pub time: u64,
+    /// Local time in ms the response was received at, set by from_response only.
+    #[serde(skip)]
+    pub received_at: Option<u64>,
+}
+
+impl<T> ApiResponse<T> {
+    ///
+    /// Server time in ms when the response was made. Comes with every call, unlike
+    /// get_server_time which costs a request of its own.
+    ///
+    pub fn server_time(&self) -> u64 {
+        self.time
+    }
+
+    ///
+    /// received_at minus server_time, in ms: the server to client delay plus the local
+    /// clock offset, see `Response::server_delay_ms`. None unless built by
+    /// from_response, or when the envelope has no `time`.
+    ///
+    pub fn server_delay_ms(&self) -> Option<i64> {
+        match (self.received_at, self.time) {
+            (Some(received_at), time) if time > 0 => Some(received_at as i64 - time as i64),
+            _ => None,
+        }
+    }
+}
+
+impl<T: DeserializeOwned> ApiResponse<T> {
+    ///
+    /// Checks the retCode before deserializing, so a failed call is reported as
+    /// `AppError::Api` rather than as a `result` that doesn't match `T`.
+    ///
+    pub fn from_body(mut body: Value) -> Result<Self> {
+        check_ret_code(&body)?;
+        if let Value::Object(fields) = &mut body {
+            fields.entry("result").or_insert(Value::Null);
+        }
+        Ok(serde_json::from_value(body)?)
+    }
+
+    ///
+    /// from_body keeping the receive time of a HttpManager::submit_request_full response.
+    ///
+    pub fn from_response(response: Response<Value>) -> Result<Self> {
+        let mut api_response = ApiResponse::from_body(response.body)?;
+        api_response.received_at = Some(response.received_at);
+        Ok(api_response)
+    }
+}
+
+const LIST_KEYS: [&str; 3] = ["list", "rows", "result"];
+
+///
+/// A `result` holding a list, whatever shape Bybit gives it, so that
+/// `ApiResponse<ListResult<T>>` fits every list endpoint:
+///
+/// ```text
+/// { "list": [...], "nextPageCursor": "..." }   tickers, instruments, positions, orders,
+///                                              executions, closed pnl, transaction log
+/// { "rows": [...] }                            asset coin info, deposit records
+/// { "result": [...] }                          sub api key list
+/// [...]                                        historical volatility, some account endpoints
+/// { ... }                                      single objects, a list of one
+/// ```
+///
+/// as the rules of `extract_list`. An empty nextPageCursor is None, as is a missing
+/// one. `category` is the category Bybit puts next to the list, when it does.
+/// Serializes in the first shape.
+///
+#[derive(serde_derive::Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ListResult<T> {
+    pub list: Vec<T>,
+    pub next_page_cursor: Option<String>,
+    pub category: Option<String>,
+}
+
+impl<T> ListResult<T> {
+    pub fn has_next_page(&self) -> bool {
+        self.next_page_cursor.is_some()
+    }
+}
+
+impl<'de, T: DeserializeOwned> Deserialize<'de> for ListResult<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let result = Value::deserialize(deserializer)?;
+        let field = |name: &str| {
+            result
+                .get(name)
+                .and_then(Value::as_str)
+                .filter(|value| !value.is_empty())
+                .map(str::to_string)
+        };
+        let next_page_cursor = field("nextPageCursor");
+        let category = field("category");
+        let list = extract_list(result)
+            .into_iter()
+            .map(serde_json::from_value)
+            .collect::<std::result::Result<_, _>>()
+            .map_err(serde::de::Error::custom)?;
+        Ok(ListResult {
+            list,
+            next_page_cursor,
+            category,
+        })
+    }
+}
+
+///
+/// Returns `AppError::Api` when the envelope carries a non-zero retCode,
+/// `AppError::RateLimited` for 10006 and `AppError::SystemMaintenance` for 10016.
+///
+pub(crate) fn check_ret_code(body: &Value) -> Result<()> {
+    let ret_code = body.get("retCode").and_then(Value::as_i64).unwrap_or(0) as i32;
+    if ret_code != 0 {
+        let ret_msg = body
+            .get("retMsg")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+        if ret_code == app_error::RATE_LIMITED {
+            return Err(AppError::RateLimited(ret_msg));
+        }
+        if ret_code == app_error::SYSTEM_ERROR {
+            return Err(AppError::SystemMaintenance(ret_msg));
+        }
+        return Err(AppError::Api { ret_code, ret_msg });
+    }
+    Ok(())
+}
+
+///
+/// check_ret_code for setters, treating `not_modified` (Bybit's answer when the value
+/// is already the requested one) as success.
+///
+pub(crate) fn accept_not_modified(body: Value, not_modified: i32) -> Result<Value> {
+    if body.get("retCode").and_then(Value::as_i64) != Some(not_modified as i64) {
+        check_ret_code(&body)?;
+    }
+    Ok(body)
+}
+
+///
+/// Takes `result` out of the envelope after checking the retCode.
+///
+pub(crate) fn take_result(mut body: Value) -> Result<Value> {
+    check_ret_code(&body)?;
+    Ok(body
+        .get_mut("result")
+        .map(Value::take)
+        .unwrap_or(Value::Null))
+}
+
+///
+/// Takes the list of items out of `result`.
+///
+/// Bybit is not consistent in where the payload sits inside `result`:
+///
+/// ```text
+/// { "result": { "list": [...] } }       most query endpoints
+/// { "result": { "rows": [...] } }       asset coin info, deposit records
+/// { "result": { "result": [...] } }     sub api key list
+/// { "result": [...] }                   a few account endpoints
+/// { "result": { ... } }                 single objects (api key info, order ack)
+/// ```
+///
+/// The rules below are applied in order:
+///
+/// 1. `result` is an array: its items.
+/// 2. `result` has an array under `list`, `rows` or `result`: those items.
+/// 3. `result` is null or an empty object: no items.
+/// 4. any other object: a single item.
+///
+pub(crate) fn extract_list(mut result: Value) -> Vec<Value> {
+    if let Value::Array(items) = result {
+        return items;
+    }
+    for key in LIST_KEYS {
+        if result.get(key).map_or(false, Value::is_array) {
+            if let Value::Array(items) = result[key].take() {
+                return items;
+            }
+        }
+    }
+    match result {
+        Value::Null => vec![],
+        Value::Object(ref fields) if fields.is_empty() => vec![],
+        other => vec![other],
+    }
+}
+
+///
+/// Follows nextPageCursor until the last page, returning every item. A page that is
+/// empty or hands back the cursor it was fetched with also counts as the last, so a
+/// misbehaving server can't keep the loop going.
+///
+pub(crate) async fn all_pages<F, Fut>(
+    query: HashMap<String, String>,
+    mut fetch: F,
+) -> Result<Vec<Value>>
+where
+    F: FnMut(HashMap<String, String>) -> Fut,
+    Fut: Future<Output = Result<Value>>,
+{
+    let mut items = vec![];
+    let mut cursor = String::new();
+    loop {
+        let mut page_query = query.clone();
+        if !cursor.is_empty() {
+            page_query.insert("cursor".to_string(), cursor.clone());
+        }
+        let result = take_result(fetch(page_query).await?)?;
+        let next_cursor = result
+            .get("nextPageCursor")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+        let page = extract_list(result);
+        let last = next_cursor.is_empty() || next_cursor == cursor || page.is_empty();
+        items.extend(page);
+        if last {
+            return Ok(items);
+        }
+        cursor = next_cursor;
+    }
+}
+
+///
+/// Deserializes `result` as a single object, used for endpoints returning one record.
+///
+pub(crate) fn into_result<T: DeserializeOwned>(body: Value) -> Result<T> {
+    Ok(ApiResponse::from_body(body)?.result)
+}
+
+///
+/// Deserializes the items of `result`, see `extract_list` for the rules.
+///
+pub(crate) fn into_list<T: DeserializeOwned>(body: Value) -> Result<Vec<T>> {
+    let items = extract_list(take_result(body)?);
+    Ok(items
+        .into_iter()
+        .map(serde_json::from_value)
+        .collect::<std::result::Result<_, _>>()?)
+}
+
+///
+/// Outcome of one order in a batch call, `request_index` being its position in the request.
+///
+#[derive(Debug)]
+pub struct BatchItem<T> {
+    pub request_index: usize,
+    pub result: std::result::Result<T, AppError>,
+}
+
+///
+/// Batch endpoints answer with two parallel arrays, `result.list` holding the order
+/// acks and `retExtInfo.list` holding a `{ code, msg }` per order, both in request order.
+/// BatchResult zips them so each order carries its own outcome. Failed orders still get
+/// an ack entry with empty fields, which is dropped in favour of the error.
+/// Typically built from `TradeHTTP::batch_place_order` output via `from_response`.
+///
+#[derive(Debug)]
+pub struct BatchResult<T> {
+    pub items: Vec<BatchItem<T>>,
+}
+
+impl<T: DeserializeOwned> BatchResult<T> {
+    ///
+    /// Builds the per-order results from a raw batch response.
+    ///
+    /// Additional information:
+    ///     The envelope retCode is checked first, a failed call as a whole is an error.
+    ///     Orders missing from `retExtInfo.list` are treated as successful.
+    ///
+    pub fn from_response(mut body: Value) -> Result<Self> {
+        check_ret_code(&body)?;
+        let errors = body
+            .get_mut("retExtInfo")
+            .and_then(|info| info.get_mut("list"))
+            .map(Value::take)
+            .and_then(|list| match list {
+                Value::Array(items) => Some(items),
+                _ => None,
+            })
+            .unwrap_or_default();
+        let acks = extract_list(
+            body.get_mut("result")
+                .map(Value::take)
+                .unwrap_or(Value::Null),
+        );
+
+        let len = acks.len().max(errors.len());
+        let mut acks = acks.into_iter();
+        let mut errors = errors.into_iter();
+        let items = (0..len)
+            .map(|request_index| {
+                let ack = acks.next().unwrap_or(Value::Null);
+                let error = errors.next().unwrap_or(Value::Null);
+                let ret_code = error.get("code").and_then(Value::as_i64).unwrap_or(0) as i32;
+                let result = if ret_code != 0 {
+                    let ret_msg = error
+                        .get("msg")
+                        .and_then(Value::as_str)
+                        .unwrap_or_default()
+                        .to_string();
+                    Err(AppError::Api { ret_code, ret_msg })
+                } else {
+                    serde_json::from_value(ack).map_err(AppError::Deserialize)
+                };
+                BatchItem {
+                    request_index,
+                    result,
+                }
+            })
+            .collect();
+        Ok(BatchResult { items })
+    }
+
+    pub fn successes(&self) -> impl Iterator<Item = (usize, &T)> {
+        self.items.iter().filter_map(|item| {
+            item.result
+                .as_ref()
+                .ok()
+                .map(|ack| (item.request_index, ack))
+        })
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = (usize, &AppError)> {
+        self.items.iter().filter_map(|item| {
+            item.result
+                .as_ref()
+                .err()
+                .map(|err| (item.request_index, err))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn extract_list_takes_the_items_of_every_shape() {
+        // /v5/market/tickers
+        let list = json!({
+            "category": "spot",
+            "list": [{ "symbol": "BTCUSDT", "lastPrice": "16597.00" }, { "symbol": "ETHUSDT", "lastPrice": "1197.56" }]
+        });
+        assert_eq!(extract_list(list).len(), 2);
+        // /v5/asset/coin/query-info
+        let rows = json!({ "rows": [{ "name": "BTC", "coin": "BTC", "remainAmount": "150" }] });
+        assert_eq!(extract_list(rows)[0]["coin"], "BTC");
+        // /v5/user/query-sub-members
+        let nested =
+            json!({ "result": [{ "id": "16651283", "apiKey": "XXXXXX" }], "nextPageCursor": "0" });
+        assert_eq!(extract_list(nested)[0]["apiKey"], "XXXXXX");
+        // /v5/market/historical-volatility
+        let bare = json!([{ "period": 7, "value": "0.27545620", "time": "1687525200000" }]);
+        assert_eq!(extract_list(bare)[0]["period"], 7);
+        // /v5/order/create
+        let single =
+            json!({ "orderId": "1321003749386327552", "orderLinkId": "spot-test-postonly" });
+        assert_eq!(extract_list(single.clone()), vec![single]);
+        assert!(extract_list(json!({})).is_empty());
+        assert!(extract_list(Value::Null).is_empty());
+    }
+
+    #[test]
+    fn extract_list_keeps_a_non_array_list_field_of_a_single_object() {
+        let single = json!({ "id": "16651283", "result": "ok", "rows": 3 });
+        assert_eq!(extract_list(single.clone()), vec![single]);
+    }
+
+    #[derive(serde_derive::Deserialize, Debug, PartialEq)]
+    #[serde(rename_all = "camelCase")]
+    struct Item {
+        symbol: String,
+    }
+
+    fn list_result(result: Value) -> ListResult<Item> {
+        serde_json::from_value(result).unwrap()
+    }
+
+    #[test]
+    fn list_result_reads_a_list_with_its_cursor_and_category() {
+        // /v5/position/list
+        let result = list_result(json!({
+            "list": [{ "positionIdx": 0, "symbol": "BTCUSD", "side": "Buy", "size": "1" }],
+            "nextPageCursor": "BTCUSD%3A1657711949945%2CBTCUSD%3A1657711949945",
+            "category": "inverse"
+        }));
+        assert_eq!(
+            result.list,
+            vec![Item {
+                symbol: "BTCUSD".to_string()
+            }]
+        );
+        assert!(result.has_next_page());
+        assert_eq!(result.category.as_deref(), Some("inverse"));
+    }
+
+    #[test]
+    fn list_result_treats_an_empty_cursor_as_the_last_page() {
+        let result = list_result(json!({ "list": [], "nextPageCursor": "", "category": "" }));
+        assert!(result.list.is_empty());
+        assert!(!result.has_next_page());
+        assert_eq!(result.category, None);
+    }
+
+    #[test]
+    fn list_result_reads_rows_a_bare_array_and_a_single_object() {
+        // /v5/asset/deposit/query-record
+        let rows = list_result(json!({
+            "rows": [{ "coin": "USDT", "chain": "ETH", "amount": "10000", "symbol": "USDT" }],
+            "nextPageCursor": "eyJtaW5JRCI6MTE2NDQ4NzQsIm1heElEIjoxMTY0NDg3NH0="
+        }));
+        assert_eq!(rows.list.len(), 1);
+        assert!(rows.has_next_page());
+        // /v5/market/historical-volatility style bare array
+        let bare = list_result(json!([{ "symbol": "BTCUSDT" }, { "symbol": "ETHUSDT" }]));
+        assert_eq!(bare.list.len(), 2);
+        assert_eq!(bare.next_page_cursor, None);
+        // /v5/position/set-leverage style single object
+        let single = list_result(json!({ "symbol": "BTCUSDT", "leverage": "10" }));
+        assert_eq!(
+            single.list,
+            vec![Item {
+                symbol: "BTCUSDT".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn list_result_of_an_empty_result_is_empty() {
+        assert!(list_result(json!({})).list.is_empty());
+        assert!(list_result(Value::Null).list.is_empty());
+    }
+
+    #[test]
+    fn list_result_reports_an_item_that_does_not_fit() {
+        let result = serde_json::from_value::<ListResult<Item>>(json!({ "list": [{ "coin": 1 }] }));
+        assert!(result.is_err());
+    }
+
+    fn envelope(ret_code: i32, ret_msg: &str) -> Value {
+        json!({ "retCode": ret_code, "retMsg": ret_msg, "result": {}, "retExtInfo": {}, "time": 1684765770483u64 })
+    }
+
+    #[test]
+    fn ret_codes_become_their_app_errors() {
+        assert!(check_ret_code(&envelope(0, "OK")).is_ok());
+        match check_ret_code(&envelope(10006, "Too many visits!")) {
+            Err(AppError::RateLimited(ret_msg)) => assert_eq!(ret_msg, "Too many visits!"),
+            other => panic!("{:?}", other),
+        }
+        match check_ret_code(&envelope(10016, "Server error.")) {
+            Err(AppError::SystemMaintenance(ret_msg)) => assert_eq!(ret_msg, "Server error."),
+            other => panic!("{:?}", other),
+        }
+        match check_ret_code(&envelope(110001, "Order does not exist.")) {
+            Err(AppError::Api { ret_code, ret_msg }) => {
+                assert_eq!(ret_code, 110001);
+                assert_eq!(ret_msg, "Order does not exist.");
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn not_modified_is_accepted_and_other_codes_are_not() {
+        assert!(
+            accept_not_modified(envelope(110025, "Position mode is not modified"), 110025).is_ok()
+        );
+        let err = accept_not_modified(
+            envelope(110026, "Cross/isolated margin mode is not modified"),
+            110025,
+        )
+        .unwrap_err();
+        assert_eq!(err.ret_code(), Some(110026));
+        assert!(matches!(
+            accept_not_modified(envelope(10006, "Too many visits!"), 110025),
+            Err(AppError::RateLimited(_))
+        ));
+        assert!(matches!(
+            accept_not_modified(envelope(10016, "Server error."), 110025),
+            Err(AppError::SystemMaintenance(_))
+        ));
+    }
+
+    /// A /v5/position/list style body with `symbols` and `next_page_cursor`.
+    fn page(symbols: &[&str], next_page_cursor: &str) -> Value {
+        let list: Vec<Value> = symbols
+            .iter()
+            .map(|symbol| json!({ "symbol": symbol }))
+            .collect();
+        json!({
+            "retCode": 0,
+            "retMsg": "OK",
+            "result": { "list": list, "nextPageCursor": next_page_cursor, "category": "linear" },
+            "retExtInfo": {},
+            "time": 1672280219169u64
+        })
+    }
+
+    /// Runs all_pages over `pages`, returning the items and the cursor of every request.
+    fn fetch_pages(pages: Vec<Value>) -> (Vec<Value>, Vec<Option<String>>) {
+        let mut pages = pages.into_iter();
+        let mut cursors = vec![];
+        let items = futures::executor::block_on(all_pages(HashMap::new(), |query| {
+            cursors.push(query.get("cursor").cloned());
+            let page = pages.next().expect("fetched past the last page");
+            async move { Ok(page) }
+        }))
+        .unwrap();
+        (items, cursors)
+    }
+
+    #[test]
+    fn all_pages_follows_the_cursor_to_the_last_page() {
+        let (items, cursors) = fetch_pages(vec![
+            page(&["BTCUSDT", "ETHUSDT"], "page-2"),
+            page(&["SOLUSDT"], ""),
+        ]);
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[2]["symbol"], "SOLUSDT");
+        assert_eq!(cursors, vec![None, Some("page-2".to_string())]);
+    }
+
+    #[test]
+    fn all_pages_stops_on_a_repeated_cursor_or_an_empty_page() {
+        let (items, cursors) = fetch_pages(vec![
+            page(&["BTCUSDT"], "page-2"),
+            page(&["ETHUSDT"], "page-2"),
+        ]);
+        assert_eq!(items.len(), 2);
+        assert_eq!(cursors.len(), 2);
+
+        let (items, cursors) = fetch_pages(vec![page(&["BTCUSDT"], "page-2"), page(&[], "page-3")]);
+        assert_eq!(items.len(), 1);
+        assert_eq!(cursors.len(), 2);
+    }
+}
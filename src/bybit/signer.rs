@@ -0,0 +1,63 @@
+use async_trait::async_trait;
+use ring::hmac;
+
+use super::Result;
+
+///
+/// Signs the requests of HttpManager (see `HttpManager::with_signer`), so the api
+/// secret can live outside the process, e.g. in a KMS or an HSM.
+///
+/// `sign` gets the exact string Bybit verifies,
+/// `timestamp + api_key + recv_window + payload` with the query string of a GET or
+/// the body of a POST as payload, and returns the lowercase hex HMAC-SHA256 of it
+/// (or the signature of whatever scheme the key was created with). Errors are passed
+/// on as they are, `AppError::Signature` fits most.
+///
+/// PrivateWebSocket still takes the secret itself.
+///
+#[async_trait]
+pub trait Signer: Send + Sync {
+    fn api_key(&self) -> &str;
+
+    async fn sign(&self, payload: &str) -> Result<String>;
+}
+
+///
+/// The Signer of HttpManager::new: HMAC-SHA256 with a secret held in memory.
+///
+#[derive(Clone)]
+pub struct HmacSigner {
+    api_key: String,
+    key: hmac::Key,
+}
+
+impl HmacSigner {
+    pub fn new(api_key: &str, api_secret: &str) -> Self {
+        HmacSigner {
+            api_key: api_key.to_string(),
+            key: hmac::Key::new(hmac::HMAC_SHA256, api_secret.as_bytes()),
+        }
+    }
+}
+
+/// Leaves the secret out.
+impl std::fmt::Debug for HmacSigner {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("HmacSigner")
+            .field("api_key", &self.api_key)
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl Signer for HmacSigner {
+    fn api_key(&self) -> &str {
+        &self.api_key
+    }
+
+    async fn sign(&self, payload: &str) -> Result<String> {
+        Ok(hex::encode(
+            hmac::sign(&self.key, payload.as_bytes()).as_ref(),
+        ))
+    }
+}
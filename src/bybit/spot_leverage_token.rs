@@ -10,24 +10,107 @@ use futures::Future;
 use reqwest::Method;
 use serde_json::Value;
 
-use crate::endpoints::v5spot_leverage_token;
+use crate::{endpoints::v5spot_leverage_token, helpers::de};
 
 use super::{
     http_manager::{HttpManager, Manager},
+    response,
+    types::{format_number, Number},
     Result,
 };
 
+/// A leveraged token as returned by get_leveraged_token_info_typed.
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LeveragedTokenInfo {
+    /// e.g. BTC3L
+    pub lt_coin: String,
+    #[serde(default)]
+    pub lt_name: String,
+    /// 1 purchasable and redeemable, 2 purchase only, 3 redeem only, 4 neither.
+    #[serde(default)]
+    pub lt_status: String,
+    /// Bounds of a single purchase, in the quote coin.
+    #[serde(default, deserialize_with = "de::string_as_option_number")]
+    pub min_purchase: Option<Number>,
+    #[serde(default, deserialize_with = "de::string_as_option_number")]
+    pub max_purchase: Option<Number>,
+    /// Bounds of a single redemption, in tokens.
+    #[serde(default, deserialize_with = "de::string_as_option_number")]
+    pub min_redeem: Option<Number>,
+    #[serde(default, deserialize_with = "de::string_as_option_number")]
+    pub max_redeem: Option<Number>,
+    #[serde(default, deserialize_with = "de::string_as_option_number")]
+    pub purchase_fee_rate: Option<Number>,
+    #[serde(default, deserialize_with = "de::string_as_option_number")]
+    pub redeem_fee_rate: Option<Number>,
+    #[serde(default, deserialize_with = "de::string_as_option_number")]
+    pub net_value: Option<Number>,
+}
+
+///
+/// Outcome of purchase or redeem. Both are settled asynchronously: until
+/// `lt_order_status` is "1" (completed) the executed fields may be empty; the fee
+/// is reported by the purchase and redemption records.
+///
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LeveragedTokenOrder {
+    pub lt_coin: String,
+    /// purchaseId or redeemId.
+    #[serde(alias = "purchaseId", alias = "redeemId")]
+    pub order_id: String,
+    /// 1 completed, 2 in progress, 3 failed.
+    pub lt_order_status: String,
+    /// Purchase amount in `value_coin`, or redeemed token quantity.
+    #[serde(
+        default,
+        alias = "quantity",
+        deserialize_with = "de::string_as_option_number"
+    )]
+    pub amount: Option<Number>,
+    /// Tokens received or redeemed.
+    #[serde(default, deserialize_with = "de::string_as_option_number")]
+    pub exec_qty: Option<Number>,
+    /// What it cost or returned, in `value_coin`.
+    #[serde(default, deserialize_with = "de::string_as_option_number")]
+    pub exec_amt: Option<Number>,
+    #[serde(default)]
+    pub serial_no: String,
+    #[serde(default)]
+    pub value_coin: String,
+}
+
 #[async_trait]
 pub trait SpotLeverageTokenTrade {
     fn new(http_manager: Arc<HttpManager>) -> Self;
     async fn get_leveraged_token_info(&self, query: HashMap<String, String>) -> Result<Value>;
 
+    async fn get_leveraged_token_info_typed(
+        &self,
+        lt_coin: Option<&str>,
+    ) -> Result<Vec<LeveragedTokenInfo>>;
+
     async fn get_leveraged_token_market(&self, query: HashMap<String, String>) -> Result<Value>;
 
     async fn purchase_leveraged_token(&self, query: HashMap<String, String>) -> Result<Value>;
 
     async fn redeem_leveraged_token(&self, query: HashMap<String, String>) -> Result<Value>;
 
+    async fn purchase(
+        &self,
+        lt_coin: &str,
+        amount: Number,
+        serial_no: Option<&str>,
+    ) -> Result<LeveragedTokenOrder>;
+
+    async fn redeem(
+        &self,
+        lt_coin: &str,
+        quantity: Number,
+        serial_no: Option<&str>,
+    ) -> Result<LeveragedTokenOrder>;
+
     async fn get_purchase_redemption_records(
         &self,
         query: HashMap<String, String>,
@@ -62,6 +145,20 @@ impl SpotLeverageTokenTrade for SpotLeverageTokenTradeHTTP {
             .await
     }
 
+    ///
+    /// Every leveraged token, or only `lt_coin`, with its purchase and redemption limits.
+    ///
+    async fn get_leveraged_token_info_typed(
+        &self,
+        lt_coin: Option<&str>,
+    ) -> Result<Vec<LeveragedTokenInfo>> {
+        let mut query = HashMap::new();
+        if let Some(lt_coin) = lt_coin {
+            query.insert("ltCoin".to_string(), lt_coin.to_string());
+        }
+        response::into_list(self.get_leveraged_token_info(query).await?)
+    }
+
     /// Get leverage token market information
 
     ///     Required args:
@@ -116,6 +213,43 @@ impl SpotLeverageTokenTrade for SpotLeverageTokenTradeHTTP {
             .await
     }
 
+    ///
+    /// Buys `amount` worth (in the quote coin) of `lt_coin`. Bybit rejects a reused
+    /// `serial_no`, so a retried purchase with the same one can't buy twice.
+    ///
+    async fn purchase(
+        &self,
+        lt_coin: &str,
+        amount: Number,
+        serial_no: Option<&str>,
+    ) -> Result<LeveragedTokenOrder> {
+        let mut query = HashMap::new();
+        query.insert("ltCoin".to_string(), lt_coin.to_string());
+        query.insert("ltAmount".to_string(), format_number(amount));
+        if let Some(serial_no) = serial_no {
+            query.insert("serialNo".to_string(), serial_no.to_string());
+        }
+        response::into_result(self.purchase_leveraged_token(query).await?)
+    }
+
+    ///
+    /// Redeems `quantity` tokens of `lt_coin`, `serial_no` works as for purchase.
+    ///
+    async fn redeem(
+        &self,
+        lt_coin: &str,
+        quantity: Number,
+        serial_no: Option<&str>,
+    ) -> Result<LeveragedTokenOrder> {
+        let mut query = HashMap::new();
+        query.insert("ltCoin".to_string(), lt_coin.to_string());
+        query.insert("quantity".to_string(), format_number(quantity));
+        if let Some(serial_no) = serial_no {
+            query.insert("serialNo".to_string(), serial_no.to_string());
+        }
+        response::into_result(self.redeem_leveraged_token(query).await?)
+    }
+
     /// Get purchase or redeem history
 
     ///     Required args:
@@ -2,19 +2,28 @@
 use async_trait::async_trait;
 use serde::Serialize;
 use std::{
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
     pin::Pin,
-    sync::Arc,
+    sync::{Arc, Mutex},
 };
 
 use futures::Future;
 use reqwest::Method;
 use serde_json::Value;
 
-use crate::endpoints::v5trade;
+use crate::{
+    endpoints::v5trade,
+    errors::app_error::AppError,
+    helpers::{de, ser, symbols, utils},
+};
 
 use super::{
     http_manager::{HttpManager, Manager},
+    response,
+    types::{
+        format_number, position_idx_hint, Category, Number, OrderType, PositionIdx, Side,
+        TimeInForce,
+    },
     Result,
 };
 
@@ -25,22 +34,912 @@ pub struct BatchOrderRequest {
     pub request: Vec<HashMap<String, String>>,
 }
 
+/// How `qty` of a spot market order is interpreted:
+/// BaseCoin buys/sells `qty` of the base coin (0.01 BTC),
+/// QuoteCoin spends/receives `qty` of the quote coin (100 USDT of BTC).
+#[derive(serde_derive::Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum MarketUnit {
+    BaseCoin,
+    QuoteCoin,
+}
+
+impl std::fmt::Display for MarketUnit {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            MarketUnit::BaseCoin => write!(f, "baseCoin"),
+            MarketUnit::QuoteCoin => write!(f, "quoteCoin"),
+        }
+    }
+}
+
+impl std::str::FromStr for MarketUnit {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "baseCoin" => Ok(MarketUnit::BaseCoin),
+            "quoteCoin" => Ok(MarketUnit::QuoteCoin),
+            _ => Err(AppError::InvalidParameter(format!(
+                "unknown marketUnit {}",
+                s
+            ))),
+        }
+    }
+}
+
+///
+/// Typed parameters of place_order, serialized into the create order body as is:
+/// numbers as decimal strings, flags as booleans, unset options left out.
+/// `PlaceOrderRequest::new(Category::Linear, "BTCUSDT", Side::Buy, OrderType::Limit, qty).price(p)`
+///
+#[derive(serde_derive::Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaceOrderRequest {
+    pub category: Category,
+    pub symbol: String,
+    pub side: Side,
+    pub order_type: OrderType,
+    #[serde(serialize_with = "ser::number_as_string")]
+    pub qty: Number,
+    /// Required for limit orders, ignored for market orders.
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "ser::option_number_as_string"
+    )]
+    pub price: Option<Number>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_in_force: Option<TimeInForce>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reduce_only: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub close_on_trigger: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub order_link_id: Option<String>,
+    /// Required in hedge mode, see PositionHTTP::apply_position_idx.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position_idx: Option<PositionIdx>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub market_unit: Option<MarketUnit>,
+    /// Turns the order into a conditional order triggered at this price.
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "ser::option_number_as_string"
+    )]
+    pub trigger_price: Option<Number>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "ser::option_number_as_string"
+    )]
+    pub take_profit: Option<Number>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "ser::option_number_as_string"
+    )]
+    pub stop_loss: Option<Number>,
+    /// MarkPrice, IndexPrice, LastPrice
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tp_trigger_by: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sl_trigger_by: Option<String>,
+    /// Full, Partial
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tpsl_mode: Option<String>,
+    /// Limit TP/SL need tpsl_mode Partial and a limit price.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tp_order_type: Option<OrderType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sl_order_type: Option<OrderType>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "ser::option_number_as_string"
+    )]
+    pub tp_limit_price: Option<Number>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "ser::option_number_as_string"
+    )]
+    pub sl_limit_price: Option<Number>,
+}
+
+impl PlaceOrderRequest {
+    pub fn new(
+        category: Category,
+        symbol: &str,
+        side: Side,
+        order_type: OrderType,
+        qty: Number,
+    ) -> Self {
+        PlaceOrderRequest {
+            category,
+            symbol: symbol.to_string(),
+            side,
+            order_type,
+            qty,
+            price: None,
+            time_in_force: None,
+            reduce_only: None,
+            close_on_trigger: None,
+            order_link_id: None,
+            position_idx: None,
+            market_unit: None,
+            trigger_price: None,
+            take_profit: None,
+            stop_loss: None,
+            tp_trigger_by: None,
+            sl_trigger_by: None,
+            tpsl_mode: None,
+            tp_order_type: None,
+            sl_order_type: None,
+            tp_limit_price: None,
+            sl_limit_price: None,
+        }
+    }
+
+    pub fn price(mut self, price: Number) -> Self {
+        self.price = Some(price);
+        self
+    }
+
+    pub fn time_in_force(mut self, time_in_force: TimeInForce) -> Self {
+        self.time_in_force = Some(time_in_force);
+        self
+    }
+
+    pub fn reduce_only(mut self, reduce_only: bool) -> Self {
+        self.reduce_only = Some(reduce_only);
+        self
+    }
+
+    pub fn close_on_trigger(mut self, close_on_trigger: bool) -> Self {
+        self.close_on_trigger = Some(close_on_trigger);
+        self
+    }
+
+    pub fn order_link_id(mut self, order_link_id: &str) -> Self {
+        self.order_link_id = Some(order_link_id.to_string());
+        self
+    }
+
+    ///
+    /// Sets a random UUID as orderLinkId unless one is set already. The id is fixed
+    /// from then on, so sending the same request again can't create a second order.
+    ///
+    pub fn generate_order_link_id(mut self) -> Self {
+        if self.order_link_id.is_none() {
+            self.order_link_id = Some(utils::generate_uuid());
+        }
+        self
+    }
+
+    pub fn position_idx(mut self, position_idx: PositionIdx) -> Self {
+        self.position_idx = Some(position_idx);
+        self
+    }
+
+    pub fn market_unit(mut self, market_unit: MarketUnit) -> Self {
+        self.market_unit = Some(market_unit);
+        self
+    }
+
+    pub fn trigger_price(mut self, trigger_price: Number) -> Self {
+        self.trigger_price = Some(trigger_price);
+        self
+    }
+
+    pub fn take_profit(mut self, take_profit: Number) -> Self {
+        self.take_profit = Some(take_profit);
+        self
+    }
+
+    pub fn stop_loss(mut self, stop_loss: Number) -> Self {
+        self.stop_loss = Some(stop_loss);
+        self
+    }
+
+    /// Trigger price type of both take profit and stop loss.
+    pub fn tpsl_trigger_by(mut self, trigger_by: &str) -> Self {
+        self.tp_trigger_by = Some(trigger_by.to_string());
+        self.sl_trigger_by = Some(trigger_by.to_string());
+        self
+    }
+
+    pub fn tpsl_mode(mut self, tpsl_mode: &str) -> Self {
+        self.tpsl_mode = Some(tpsl_mode.to_string());
+        self
+    }
+
+    /// Limit take profit at `price`, switches tpsl_mode to Partial as Bybit requires.
+    pub fn tp_limit_price(mut self, price: Number) -> Self {
+        self.tp_order_type = Some(OrderType::Limit);
+        self.tp_limit_price = Some(price);
+        self.tpsl_mode = Some("Partial".to_string());
+        self
+    }
+
+    /// Limit stop loss at `price`, switches tpsl_mode to Partial as Bybit requires.
+    pub fn sl_limit_price(mut self, price: Number) -> Self {
+        self.sl_order_type = Some(OrderType::Limit);
+        self.sl_limit_price = Some(price);
+        self.tpsl_mode = Some("Partial".to_string());
+        self
+    }
+}
+
+///
+/// The query map form of the request, as taken by place_order, validate_order and
+/// PositionHTTP::apply_position_idx.
+///
+impl From<&PlaceOrderRequest> for HashMap<String, String> {
+    fn from(request: &PlaceOrderRequest) -> Self {
+        let body = serde_json::to_value(request).unwrap_or_default();
+        match body {
+            Value::Object(fields) => fields
+                .into_iter()
+                .map(|(key, value)| match value {
+                    Value::String(value) => (key, value),
+                    value => (key, value.to_string()),
+                })
+                .collect(),
+            _ => HashMap::new(),
+        }
+    }
+}
+
+/// retCode of an orderLinkId already taken by another order.
+pub const DUPLICATE_ORDER_LINK_ID: i32 = 110072;
+
+///
+/// The last `capacity` orderLinkIds sent, to catch one being reused within the
+/// process before Bybit rejects it. See TradeHTTP::with_order_link_id_guard.
+///
+#[derive(Debug)]
+pub struct OrderLinkIdGuard {
+    capacity: usize,
+    ids: Mutex<(VecDeque<String>, HashSet<String>)>,
+}
+
+impl OrderLinkIdGuard {
+    pub fn new(capacity: usize) -> Self {
+        OrderLinkIdGuard {
+            capacity,
+            ids: Mutex::new((VecDeque::new(), HashSet::new())),
+        }
+    }
+
+    ///
+    /// Records `id` as used, forgetting the oldest id past capacity.
+    /// Fails with `AppError::DuplicateOrderLinkId` if it is still remembered.
+    ///
+    pub fn claim(&self, id: &str) -> std::result::Result<(), AppError> {
+        self.claim_all(&[id])
+    }
+
+    ///
+    /// claim for a batch: records all of `ids` or, failing with
+    /// `AppError::DuplicateOrderLinkId` when one is still remembered or appears twice
+    /// in `ids`, none of them.
+    ///
+    pub fn claim_all(&self, ids: &[&str]) -> std::result::Result<(), AppError> {
+        let mut guard = self.ids.lock().unwrap();
+        let (order, seen) = &mut *guard;
+        let mut batch = HashSet::new();
+        for id in ids {
+            if seen.contains(*id) || !batch.insert(*id) {
+                return Err(AppError::DuplicateOrderLinkId(id.to_string()));
+            }
+        }
+        for id in ids {
+            order.push_back(id.to_string());
+            seen.insert(id.to_string());
+        }
+        while order.len() > self.capacity {
+            if let Some(oldest) = order.pop_front() {
+                seen.remove(&oldest);
+            }
+        }
+        Ok(())
+    }
+
+    ///
+    /// Forgets `id` again, for an order Bybit never placed.
+    ///
+    pub fn release(&self, id: &str) {
+        let mut guard = self.ids.lock().unwrap();
+        let (order, seen) = &mut *guard;
+        if seen.remove(id) {
+            order.retain(|other| other != id);
+        }
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.ids.lock().unwrap().1.contains(id)
+    }
+}
+
+///
+/// Indices of the entries of a `len` order batch that `body` says weren't placed:
+/// all of them when the batch was rejected as a whole, else those with a non-zero
+/// code in retExtInfo.
+///
+fn rejected_batch_entries(body: &Value, len: usize) -> Vec<usize> {
+    if body.get("retCode").and_then(Value::as_i64).unwrap_or(0) != 0 {
+        return (0..len).collect();
+    }
+    let errors = body
+        .pointer("/retExtInfo/list")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    errors
+        .iter()
+        .take(len)
+        .enumerate()
+        .filter(|(_, error)| error.get("code").and_then(Value::as_i64).unwrap_or(0) != 0)
+        .map(|(i, _)| i)
+        .collect()
+}
+
+///
+/// Bybit rejects a reused orderLinkId with 110072, or with 10001 and a "duplicate"
+/// message on some products; both become `AppError::DuplicateOrderLinkId`.
+///
+fn duplicate_order_link_id(err: AppError, order_link_id: Option<&String>) -> AppError {
+    match (err, order_link_id) {
+        (AppError::Api { ret_code, ret_msg }, Some(id))
+            if is_duplicate_order_link_id(ret_code, &ret_msg) =>
+        {
+            AppError::DuplicateOrderLinkId(id.clone())
+        }
+        (err, _) => err,
+    }
+}
+
+fn is_duplicate_order_link_id(ret_code: i32, ret_msg: &str) -> bool {
+    ret_code == DUPLICATE_ORDER_LINK_ID
+        || (ret_code == 10001 && ret_msg.to_lowercase().contains("duplicate"))
+}
+
+///
+/// Whether the orderLinkId of a place_order call is taken on Bybit's side after
+/// `outcome`: the order was placed, or Bybit refused the id as already used.
+///
+fn order_link_id_taken(outcome: std::result::Result<&Value, &AppError>) -> bool {
+    let rejected;
+    let err = match outcome {
+        Ok(body) => match response::check_ret_code(body) {
+            Ok(()) => return true,
+            Err(err) => {
+                rejected = err;
+                &rejected
+            }
+        },
+        Err(err) => err,
+    };
+    match err {
+        AppError::DuplicateOrderLinkId(_) => true,
+        AppError::Api { ret_code, ret_msg } => is_duplicate_order_link_id(*ret_code, ret_msg),
+        _ => false,
+    }
+}
+
+/// Most orders a single batch call (create, amend or cancel) accepts, see max_batch_orders.
+pub const MAX_BATCH_ORDERS: usize = 20;
+
+///
+/// Most orders a batch call accepts in `category`: 10 for spot, MAX_BATCH_ORDERS for
+/// linear and option. None for inverse, which the batch endpoints don't support.
+///
+pub fn max_batch_orders(category: Category) -> Option<usize> {
+    match category {
+        Category::Spot => Some(10),
+        Category::Linear | Category::Option => Some(MAX_BATCH_ORDERS),
+        Category::Inverse => None,
+    }
+}
+
+/// Rejects a batch Bybit would refuse as a whole, before sending it.
+fn check_batch(category: Category, len: usize) -> std::result::Result<(), AppError> {
+    let max = max_batch_orders(category).ok_or_else(|| {
+        AppError::InvalidParameter(format!("batch orders are not supported for {}", category))
+    })?;
+    if len == 0 || len > max {
+        return Err(AppError::InvalidParameter(format!(
+            "a {} batch takes 1 to {} orders, got {}",
+            category, max, len
+        )));
+    }
+    Ok(())
+}
+
+/// The orders of a batch, without the category the batch itself carries.
+fn batch_entries(orders: Vec<HashMap<String, String>>) -> Vec<HashMap<String, String>> {
+    orders
+        .into_iter()
+        .map(|mut order| {
+            order.remove("category");
+            order
+        })
+        .collect()
+}
+
+/// Per-order outcome of place_batch_orders, a failed order carries its own
+/// `AppError::Api { ret_code, ret_msg }` while the others succeed.
+pub type BatchOrderResponse = response::BatchResult<OrderResponse>;
+
+/// Ids of a created order as returned by place_order. Creation is asynchronous,
+/// query the order or follow the `order` topic for its state.
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderResponse {
+    pub order_id: String,
+    #[serde(default)]
+    pub order_link_id: String,
+}
+
+/// Identifies one order, by Bybit's id or by the orderLinkId it was placed with.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum OrderRef {
+    OrderId(String),
+    OrderLinkId(String),
+}
+
+impl OrderRef {
+    fn insert_into(&self, query: &mut HashMap<String, String>) {
+        match self {
+            OrderRef::OrderId(id) => query.insert("orderId".to_string(), id.clone()),
+            OrderRef::OrderLinkId(id) => query.insert("orderLinkId".to_string(), id.clone()),
+        };
+    }
+}
+
+/// Parameters of cancel_order_typed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CancelOrderRequest {
+    pub category: Category,
+    pub symbol: String,
+    pub order: OrderRef,
+    /// Spot only: Order, tpslOrder, StopOrder. Defaults to Order.
+    pub order_filter: Option<String>,
+}
+
+impl CancelOrderRequest {
+    pub fn new(category: Category, symbol: &str, order: OrderRef) -> Self {
+        CancelOrderRequest {
+            category,
+            symbol: symbol.to_string(),
+            order,
+            order_filter: None,
+        }
+    }
+
+    pub fn order_filter(mut self, order_filter: &str) -> Self {
+        self.order_filter = Some(order_filter.to_string());
+        self
+    }
+}
+
+impl From<CancelOrderRequest> for HashMap<String, String> {
+    fn from(request: CancelOrderRequest) -> Self {
+        let mut query = HashMap::new();
+        query.insert("category".to_string(), request.category.to_string());
+        query.insert("symbol".to_string(), request.symbol);
+        request.order.insert_into(&mut query);
+        if let Some(order_filter) = request.order_filter {
+            query.insert("orderFilter".to_string(), order_filter);
+        }
+        query
+    }
+}
+
+///
+/// Parameters of amend_order_typed. Only the fields that are set are sent, an unset
+/// field keeps its current value on the order: amending the price leaves qty alone.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct AmendOrderRequest {
+    pub category: Category,
+    pub symbol: String,
+    pub order: OrderRef,
+    pub qty: Option<Number>,
+    pub price: Option<Number>,
+    pub trigger_price: Option<Number>,
+    /// 0 cancels the take profit.
+    pub take_profit: Option<Number>,
+    /// 0 cancels the stop loss.
+    pub stop_loss: Option<Number>,
+}
+
+impl AmendOrderRequest {
+    pub fn new(category: Category, symbol: &str, order: OrderRef) -> Self {
+        AmendOrderRequest {
+            category,
+            symbol: symbol.to_string(),
+            order,
+            qty: None,
+            price: None,
+            trigger_price: None,
+            take_profit: None,
+            stop_loss: None,
+        }
+    }
+
+    pub fn qty(mut self, qty: Number) -> Self {
+        self.qty = Some(qty);
+        self
+    }
+
+    pub fn price(mut self, price: Number) -> Self {
+        self.price = Some(price);
+        self
+    }
+
+    pub fn trigger_price(mut self, trigger_price: Number) -> Self {
+        self.trigger_price = Some(trigger_price);
+        self
+    }
+
+    pub fn take_profit(mut self, take_profit: Number) -> Self {
+        self.take_profit = Some(take_profit);
+        self
+    }
+
+    pub fn stop_loss(mut self, stop_loss: Number) -> Self {
+        self.stop_loss = Some(stop_loss);
+        self
+    }
+}
+
+impl From<AmendOrderRequest> for HashMap<String, String> {
+    fn from(request: AmendOrderRequest) -> Self {
+        let mut query: HashMap<String, String> = vec![
+            ("category", Some(request.category.to_string())),
+            ("symbol", Some(request.symbol)),
+            ("qty", request.qty.map(format_number)),
+            ("price", request.price.map(format_number)),
+            ("triggerPrice", request.trigger_price.map(format_number)),
+            ("takeProfit", request.take_profit.map(format_number)),
+            ("stopLoss", request.stop_loss.map(format_number)),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| value.map(|value| (key.to_string(), value)))
+        .collect();
+        request.order.insert_into(&mut query);
+        query
+    }
+}
+
+///
+/// Parameters of cancel_all_orders_typed. Without filters a linear cancel falls back
+/// to the default settle coin, see TradeHTTP::with_default_settle_coin.
+///
+/// Filters allowed, checked by `validate` before sending:
+///
+/// ```text
+/// spot                      symbol, or nothing (every spot order)
+/// linear, inverse, option   one of symbol, baseCoin, settleCoin, or baseCoin with settleCoin
+/// ```
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct CancelAllRequest {
+    pub category: Category,
+    pub symbol: Option<String>,
+    pub base_coin: Option<String>,
+    pub settle_coin: Option<String>,
+    pub order_filter: Option<String>,
+}
+
+impl CancelAllRequest {
+    pub fn new(category: Category) -> Self {
+        CancelAllRequest {
+            category,
+            symbol: None,
+            base_coin: None,
+            settle_coin: None,
+            order_filter: None,
+        }
+    }
+
+    pub fn symbol(mut self, symbol: &str) -> Self {
+        self.symbol = Some(symbol.to_string());
+        self
+    }
+
+    pub fn base_coin(mut self, base_coin: &str) -> Self {
+        self.base_coin = Some(base_coin.to_string());
+        self
+    }
+
+    pub fn settle_coin(mut self, settle_coin: &str) -> Self {
+        self.settle_coin = Some(settle_coin.to_string());
+        self
+    }
+
+    pub fn order_filter(mut self, order_filter: &str) -> Self {
+        self.order_filter = Some(order_filter.to_string());
+        self
+    }
+
+    /// See the type docs, fails with `AppError::InvalidParameter`.
+    pub fn validate(&self) -> Result<()> {
+        symbols::check_coin_filters(
+            self.category,
+            self.symbol.as_deref(),
+            self.base_coin.as_deref(),
+            self.settle_coin.as_deref(),
+            &[Category::Linear, Category::Inverse, Category::Option],
+        )
+    }
+}
+
+impl From<CancelAllRequest> for HashMap<String, String> {
+    fn from(request: CancelAllRequest) -> Self {
+        let fields = vec![
+            ("category", Some(request.category.to_string())),
+            ("symbol", request.symbol),
+            ("baseCoin", request.base_coin),
+            ("settleCoin", request.settle_coin),
+            ("orderFilter", request.order_filter),
+        ];
+        fields
+            .into_iter()
+            .filter_map(|(key, value)| Some((key.to_string(), value?)))
+            .collect()
+    }
+}
+
+///
+/// An order as returned by get_open_orders, get_order_history and the private `order` topic.
+/// The REST endpoints carry `category` once next to the list rather than on every order,
+/// the typed methods copy it onto each order so both sources end up with the same shape.
+///
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Order {
+    #[serde(default)]
+    pub category: String,
+    pub order_id: String,
+    #[serde(default)]
+    pub order_link_id: String,
+    pub symbol: String,
+    pub side: Side,
+    pub order_type: OrderType,
+    /// None where Bybit leaves it empty, as for some market orders.
+    #[serde(default, deserialize_with = "de::empty_string_as_none")]
+    pub time_in_force: Option<TimeInForce>,
+    #[serde(deserialize_with = "de::string_as_number")]
+    pub price: Number,
+    #[serde(deserialize_with = "de::string_as_number")]
+    pub qty: Number,
+    #[serde(deserialize_with = "de::string_as_number")]
+    pub cum_exec_qty: Number,
+    /// None until the order has fills.
+    #[serde(default, deserialize_with = "de::string_as_option_number")]
+    pub avg_price: Option<Number>,
+    pub order_status: String,
+    #[serde(deserialize_with = "de::string_as_u64")]
+    pub created_time: u64,
+    #[serde(deserialize_with = "de::string_as_u64")]
+    pub updated_time: u64,
+    #[serde(default, deserialize_with = "de::string_as_option_number")]
+    pub take_profit: Option<Number>,
+    #[serde(default, deserialize_with = "de::string_as_option_number")]
+    pub stop_loss: Option<Number>,
+    #[serde(default)]
+    pub reduce_only: bool,
+}
+
+impl Order {
+    ///
+    /// Whether the order can still fill: New, PartiallyFilled or a conditional order not yet triggered.
+    ///
+    pub fn is_open(&self) -> bool {
+        matches!(
+            self.order_status.as_str(),
+            "New" | "PartiallyFilled" | "Untriggered"
+        )
+    }
+}
+
+///
+/// Query of get_open_orders_typed and get_order_history_typed, which share it since
+/// both endpoints filter the same way. Linear open order queries without a symbol or
+/// coin use the default settle coin, see TradeHTTP::with_default_settle_coin.
+///
+/// Filters allowed, checked by `validate` before sending:
+///
+/// ```text
+/// spot                      symbol, or nothing
+/// linear, inverse, option   one of symbol, baseCoin, settleCoin, or baseCoin with settleCoin
+/// ```
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderQuery {
+    pub category: Category,
+    pub symbol: Option<String>,
+    pub base_coin: Option<String>,
+    pub settle_coin: Option<String>,
+    pub order: Option<OrderRef>,
+    /// Open orders only. 0 (default) returns active orders only, 1 also returns
+    /// orders closed recently for unified accounts, 2 for classic accounts.
+    pub open_only: Option<u8>,
+    /// Order (active orders), StopOrder (conditional), tpslOrder (TP/SL), OcoOrder,
+    /// BidirectionalTpslOrder. Returns every kind when unset.
+    pub order_filter: Option<String>,
+    /// Page size, at most 50.
+    pub limit: Option<u32>,
+}
+
+impl OrderQuery {
+    pub fn new(category: Category) -> Self {
+        OrderQuery {
+            category,
+            symbol: None,
+            base_coin: None,
+            settle_coin: None,
+            order: None,
+            open_only: None,
+            order_filter: None,
+            limit: None,
+        }
+    }
+
+    pub fn symbol(mut self, symbol: &str) -> Self {
+        self.symbol = Some(symbol.to_string());
+        self
+    }
+
+    pub fn base_coin(mut self, base_coin: &str) -> Self {
+        self.base_coin = Some(base_coin.to_string());
+        self
+    }
+
+    pub fn settle_coin(mut self, settle_coin: &str) -> Self {
+        self.settle_coin = Some(settle_coin.to_string());
+        self
+    }
+
+    pub fn order(mut self, order: OrderRef) -> Self {
+        self.order = Some(order);
+        self
+    }
+
+    pub fn open_only(mut self, open_only: u8) -> Self {
+        self.open_only = Some(open_only);
+        self
+    }
+
+    pub fn order_filter(mut self, order_filter: &str) -> Self {
+        self.order_filter = Some(order_filter.to_string());
+        self
+    }
+
+    pub fn limit(mut self, limit: u32) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// See the type docs, fails with `AppError::InvalidParameter`.
+    pub fn validate(&self) -> Result<()> {
+        symbols::check_coin_filters(
+            self.category,
+            self.symbol.as_deref(),
+            self.base_coin.as_deref(),
+            self.settle_coin.as_deref(),
+            &[Category::Linear, Category::Inverse, Category::Option],
+        )
+    }
+}
+
+impl From<OrderQuery> for HashMap<String, String> {
+    fn from(request: OrderQuery) -> Self {
+        let mut query: HashMap<String, String> = vec![
+            ("category", Some(request.category.to_string())),
+            ("symbol", request.symbol),
+            ("baseCoin", request.base_coin),
+            ("settleCoin", request.settle_coin),
+            (
+                "openOnly",
+                request.open_only.map(|open_only| open_only.to_string()),
+            ),
+            ("orderFilter", request.order_filter),
+            ("limit", request.limit.map(|limit| limit.to_string())),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| value.map(|value| (key.to_string(), value)))
+        .collect();
+        if let Some(order) = request.order {
+            order.insert_into(&mut query);
+        }
+        query
+    }
+}
+
+///
+/// Deserializes the orders of a REST order list, filling in `category` from the result.
+///
+fn orders_from_response(body: Value) -> Result<Vec<Order>> {
+    let result = response::take_result(body)?;
+    let category = result
+        .get("category")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string();
+    let mut orders = response::extract_list(result)
+        .into_iter()
+        .map(serde_json::from_value::<Order>)
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    for order in orders.iter_mut().filter(|order| order.category.is_empty()) {
+        order.category = category.clone();
+    }
+    Ok(orders)
+}
+
+///
+/// `marketUnit` is only understood by spot market orders, reject it anywhere else
+/// instead of letting Bybit silently ignore it.
+///
+fn validate_market_unit(
+    category: Option<&String>,
+    order: &HashMap<String, String>,
+) -> std::result::Result<(), AppError> {
+    let unit = match order.get("marketUnit") {
+        Some(unit) => unit,
+        None => return Ok(()),
+    };
+    unit.parse::<MarketUnit>()?;
+    if category.map(String::as_str) != Some("spot")
+        || order.get("orderType").map(String::as_str) != Some("Market")
+    {
+        return Err(AppError::InvalidParameter(
+            "marketUnit is only supported for spot market orders".to_string(),
+        ));
+    }
+    Ok(())
+}
+
 #[async_trait]
 pub trait Trade {
     fn new(http_manager: Arc<HttpManager>) -> Self;
     async fn place_order(&self, query: HashMap<String, String>) -> Result<Value>;
+    async fn place_order_typed(&self, request: PlaceOrderRequest) -> Result<OrderResponse>;
     async fn batch_place_order(&self, query: BatchOrderRequest) -> Result<Value>;
+    async fn place_batch_orders(
+        &self,
+        category: Category,
+        orders: Vec<PlaceOrderRequest>,
+    ) -> Result<BatchOrderResponse>;
     async fn amend_order(&self, query: HashMap<String, String>) -> Result<Value>;
+    async fn amend_order_typed(&self, request: AmendOrderRequest) -> Result<OrderResponse>;
     async fn batch_amend_order(&self, query: BatchOrderRequest) -> Result<Value>;
+    async fn amend_batch_orders(
+        &self,
+        category: Category,
+        orders: Vec<AmendOrderRequest>,
+    ) -> Result<BatchOrderResponse>;
+    async fn cancel_batch_orders(
+        &self,
+        category: Category,
+        orders: Vec<CancelOrderRequest>,
+    ) -> Result<BatchOrderResponse>;
 
     async fn cancel_order(&self, query: HashMap<String, String>) -> Result<Value>;
+    async fn cancel_order_typed(&self, request: CancelOrderRequest) -> Result<OrderResponse>;
 
     async fn get_open_orders(&self, query: HashMap<String, String>) -> Result<Value>;
 
     async fn cancel_all_orders(&self, query: HashMap<String, String>) -> Result<Value>;
+    async fn cancel_all_orders_typed(
+        &self,
+        request: CancelAllRequest,
+    ) -> Result<Vec<OrderResponse>>;
 
     async fn get_order_history(&self, query: HashMap<String, String>) -> Result<Value>;
 
+    async fn get_open_orders_typed(&self, query: OrderQuery) -> Result<Vec<Order>>;
+
+    async fn get_order_history_typed(&self, query: OrderQuery) -> Result<Vec<Order>>;
+
     async fn amend_batch_order(&self, query: HashMap<String, String>) -> Result<Value>;
 
     async fn cancel_batch_order(&self, query: HashMap<String, String>) -> Result<Value>;
@@ -50,6 +949,115 @@ pub trait Trade {
 }
 pub struct TradeHTTP {
     http_manager: Arc<HttpManager>,
+    default_settle_coin: Option<String>,
+    symbol_allowlist: Option<HashSet<String>>,
+    symbol_denylist: HashSet<String>,
+    order_link_ids: Option<Arc<OrderLinkIdGuard>>,
+}
+
+impl TradeHTTP {
+    ///
+    /// Restricts place_order, amend_order and the batch variants to these symbols,
+    /// anything else fails locally with `AppError::SymbolNotAllowed`.
+    ///
+    pub fn with_symbol_allowlist(mut self, symbols: &[&str]) -> Self {
+        self.symbol_allowlist = Some(symbols.iter().map(|s| s.to_string()).collect());
+        self
+    }
+
+    ///
+    /// Rejects these symbols in place_order, amend_order and the batch variants with
+    /// `AppError::SymbolNotAllowed`. Checked after the allowlist, so a symbol on both is rejected.
+    ///
+    pub fn with_symbol_denylist(mut self, symbols: &[&str]) -> Self {
+        self.symbol_denylist = symbols.iter().map(|s| s.to_string()).collect();
+        self
+    }
+
+    fn check_symbol(&self, order: &HashMap<String, String>) -> std::result::Result<(), AppError> {
+        let symbol = order.get("symbol").map(String::as_str).unwrap_or_default();
+        let allowed = self
+            .symbol_allowlist
+            .as_ref()
+            .map_or(true, |allowlist| allowlist.contains(symbol))
+            && !self.symbol_denylist.contains(symbol);
+        if !allowed {
+            return Err(AppError::SymbolNotAllowed(symbol.to_string()));
+        }
+        Ok(())
+    }
+
+    ///
+    /// Checks the orderLinkId of every order placed through this client against
+    /// `guard`, a reused one fails locally with `AppError::DuplicateOrderLinkId`.
+    /// A batch is checked as a whole, repeats within it included, before any id is
+    /// claimed. The id of an order that wasn't placed, because the request failed or
+    /// Bybit rejected it, is released again so the order can be retried with it.
+    /// The guard can be shared by several clients.
+    ///
+    pub fn with_order_link_id_guard(mut self, guard: Arc<OrderLinkIdGuard>) -> Self {
+        self.order_link_ids = Some(guard);
+        self
+    }
+
+    fn claim_order_link_id(
+        &self,
+        order: &HashMap<String, String>,
+    ) -> std::result::Result<(), AppError> {
+        match (&self.order_link_ids, order.get("orderLinkId")) {
+            (Some(guard), Some(id)) => guard.claim(id),
+            _ => Ok(()),
+        }
+    }
+
+    ///
+    /// Releases the claimed `order_link_id` of a single order the request failed to
+    /// place, so the order can be retried with the same id.
+    ///
+    fn release_unplaced_order_link_id(
+        &self,
+        order_link_id: Option<&String>,
+        outcome: std::result::Result<&Value, &AppError>,
+    ) {
+        if let (Some(guard), Some(id)) = (&self.order_link_ids, order_link_id) {
+            if !order_link_id_taken(outcome) {
+                guard.release(id);
+            }
+        }
+    }
+
+    ///
+    /// Releases the orderLinkIds of the `orders` of a batch that weren't placed, all of
+    /// them when the request failed.
+    ///
+    fn release_unplaced_order_link_ids(
+        &self,
+        orders: &[HashMap<String, String>],
+        outcome: &Result<Value>,
+    ) {
+        let guard = match &self.order_link_ids {
+            Some(guard) => guard,
+            None => return,
+        };
+        let rejected = match outcome {
+            Ok(body) => rejected_batch_entries(body, orders.len()),
+            Err(_) => (0..orders.len()).collect(),
+        };
+        for i in rejected {
+            if let Some(id) = orders[i].get("orderLinkId") {
+                guard.release(id);
+            }
+        }
+    }
+
+    ///
+    /// Settle coin sent by get_open_orders and cancel_all_orders for linear queries
+    /// without symbol, baseCoin or settleCoin. Defaults to USDT, None turns it off.
+    ///
+    pub fn with_default_settle_coin(mut self, settle_coin: Option<&str>) -> Self {
+        self.default_settle_coin = settle_coin.map(str::to_string);
+        self
+    }
 }
 #[async_trait]
 impl Trade for TradeHTTP {
@@ -59,7 +1067,13 @@ impl Trade for TradeHTTP {
     ///
     ///
     fn new(http_manager: Arc<HttpManager>) -> Self {
-        TradeHTTP { http_manager }
+        TradeHTTP {
+            http_manager,
+            default_settle_coin: Some("USDT".to_string()),
+            symbol_allowlist: None,
+            symbol_denylist: HashSet::new(),
+            order_link_ids: None,
+        }
     }
     ////
     /// This method supports to create the order for spot, spot margin, linear perpetual, inverse futures and options.
@@ -69,15 +1083,49 @@ impl Trade for TradeHTTP {
     ///     side (string): Buy, Sell
     ///     orderType (string): Market, Limit
     ///     qty (string): Order quantity
+    /// Optional args:
+    ///     marketUnit (string): baseCoin, quoteCoin. Spot market orders only, see MarketUnit
     /// Returns:
     ///     Request results as JSON data.
     /// Additional information:
     ///     https://bybit-exchange.github.io/docs/v5/order/create-order
     async fn place_order(&self, query: HashMap<String, String>) -> Result<Value> {
+        self.check_symbol(&query)?;
+        validate_market_unit(query.get("category"), &query)?;
+        self.claim_order_link_id(&query)?;
         let endpoint = v5trade::Trade::PlaceOrder.to_string();
-        self.http_manager
+        let order_link_id = query.get("orderLinkId").cloned();
+        let outcome = self
+            .http_manager
             .submit_post_request(Method::POST, &endpoint, true, query)
-            .await
+            .await;
+        self.release_unplaced_order_link_id(order_link_id.as_ref(), outcome.as_ref());
+        outcome
+    }
+
+    ///
+    /// place_order from a PlaceOrderRequest, returning the ids of the new order.
+    /// The request goes through the same symbol filter, marketUnit and orderLinkId
+    /// checks; Bybit refusing a reused orderLinkId fails with
+    /// `AppError::DuplicateOrderLinkId`.
+    ///
+    async fn place_order_typed(&self, request: PlaceOrderRequest) -> Result<OrderResponse> {
+        let query = HashMap::from(&request);
+        self.check_symbol(&query)?;
+        validate_market_unit(query.get("category"), &query)?;
+        self.claim_order_link_id(&query)?;
+        let endpoint = v5trade::Trade::PlaceOrder.to_string();
+        let position_idx = request.position_idx;
+        let order_link_id = request.order_link_id.clone();
+        let outcome = self
+            .http_manager
+            .submit_post_request(Method::POST, &endpoint, true, request)
+            .await;
+        self.release_unplaced_order_link_id(order_link_id.as_ref(), outcome.as_ref());
+        outcome
+            .and_then(response::into_result)
+            .map_err(|err| duplicate_order_link_id(err, order_link_id.as_ref()))
+            .map_err(|err| position_idx_hint(err, position_idx))
     }
 
     ////
@@ -90,10 +1138,52 @@ impl Trade for TradeHTTP {
     /// Additional information:
     ///     https://bybit-exchange.github.io/docs/v5/order/batch-place
     async fn batch_place_order(&self, query: BatchOrderRequest) -> Result<Value> {
+        for order in &query.request {
+            self.check_symbol(order)?;
+            validate_market_unit(Some(&query.category), order)?;
+        }
+        if let Some(guard) = &self.order_link_ids {
+            let ids: Vec<&str> = query
+                .request
+                .iter()
+                .filter_map(|order| order.get("orderLinkId").map(String::as_str))
+                .collect();
+            guard.claim_all(&ids)?;
+        }
+        let orders = query.request.clone();
         let endpoint = v5trade::Trade::BatchPlaceOrder.to_string();
-        self.http_manager
+        let outcome = self
+            .http_manager
             .submit_post_request(Method::POST, &endpoint, true, query)
-            .await
+            .await;
+        self.release_unplaced_order_link_ids(&orders, &outcome);
+        outcome
+    }
+
+    ///
+    /// batch_place_order from PlaceOrderRequests, all placed in `category` whatever
+    /// their own category field says.
+    /// Returns:
+    ///     The outcome of each order in request order, see BatchResult::failures
+    ///     for the orders Bybit rejected while the rest of the batch went through.
+    /// Additional information:
+    ///     More orders than max_batch_orders allows, none, or an inverse batch fail
+    ///     with `AppError::InvalidParameter` before anything is sent.
+    ///
+    async fn place_batch_orders(
+        &self,
+        category: Category,
+        orders: Vec<PlaceOrderRequest>,
+    ) -> Result<BatchOrderResponse> {
+        check_batch(category, orders.len())?;
+        let request = batch_entries(orders.iter().map(HashMap::from).collect());
+        let body = self
+            .batch_place_order(BatchOrderRequest {
+                category: category.to_string(),
+                request,
+            })
+            .await?;
+        response::BatchResult::from_response(body)
     }
 
     /// Unified account covers: Linear contract / Options
@@ -108,6 +1198,7 @@ impl Trade for TradeHTTP {
 
     ///     Additional information:
     async fn amend_order(&self, query: HashMap<String, String>) -> Result<Value> {
+        self.check_symbol(&query)?;
         self.http_manager
             .submit_post_request(
                 Method::POST,
@@ -118,6 +1209,13 @@ impl Trade for TradeHTTP {
             .await
     }
 
+    ///
+    /// amend_order from an AmendOrderRequest, returning the ids of the amended order.
+    ///
+    async fn amend_order_typed(&self, request: AmendOrderRequest) -> Result<OrderResponse> {
+        response::into_result(self.amend_order(request.into()).await?)
+    }
+
     /// Unified account covers: Linear contract / Options
     /// Normal account covers: USDT perpetual / Inverse perpetual / Inverse futures
     /// Required args:
@@ -128,6 +1226,9 @@ impl Trade for TradeHTTP {
     /// Additional information:
     ///     https://bybit-exchange.github.io/docs/v5/order/batch-amend
     async fn batch_amend_order(&self, query: BatchOrderRequest) -> Result<Value> {
+        for order in &query.request {
+            self.check_symbol(order)?;
+        }
         self.http_manager
             .submit_post_request(
                 Method::POST,
@@ -138,6 +1239,65 @@ impl Trade for TradeHTTP {
             .await
     }
 
+    ///
+    /// batch_amend_order from AmendOrderRequests, all in `category` whatever their
+    /// own category field says.
+    /// Returns:
+    ///     The outcome of each amendment in request order, an order Bybit rejected
+    ///     (unknown symbol, already filled) doesn't fail the others.
+    /// Additional information:
+    ///     Same limits as place_batch_orders: more orders than max_batch_orders
+    ///     allows, none, or an inverse batch fail with `AppError::InvalidParameter`.
+    ///
+    async fn amend_batch_orders(
+        &self,
+        category: Category,
+        orders: Vec<AmendOrderRequest>,
+    ) -> Result<BatchOrderResponse> {
+        check_batch(category, orders.len())?;
+        let request = batch_entries(orders.into_iter().map(HashMap::from).collect());
+        let body = self
+            .batch_amend_order(BatchOrderRequest {
+                category: category.to_string(),
+                request,
+            })
+            .await?;
+        response::BatchResult::from_response(body)
+    }
+
+    ///
+    /// Cancels several orders in one call, all in `category` whatever their own
+    /// category field says.
+    /// Returns:
+    ///     The outcome of each cancellation in request order, an order Bybit rejected
+    ///     (unknown symbol, no longer open) doesn't fail the others.
+    /// Additional information:
+    ///     Same limits as place_batch_orders: more orders than max_batch_orders
+    ///     allows, none, or an inverse batch fail with `AppError::InvalidParameter`.
+    ///     https://bybit-exchange.github.io/docs/v5/order/batch-cancel
+    ///
+    async fn cancel_batch_orders(
+        &self,
+        category: Category,
+        orders: Vec<CancelOrderRequest>,
+    ) -> Result<BatchOrderResponse> {
+        check_batch(category, orders.len())?;
+        let request = batch_entries(orders.into_iter().map(HashMap::from).collect());
+        let body = self
+            .http_manager
+            .submit_post_request(
+                Method::POST,
+                &v5trade::Trade::BatchCancelOrder.to_string(),
+                true,
+                BatchOrderRequest {
+                    category: category.to_string(),
+                    request,
+                },
+            )
+            .await?;
+        response::BatchResult::from_response(body)
+    }
+
     /// Unified account covers: Spot / Linear contract / Options
     ///     Normal account covers: USDT perpetual / Inverse perpetual / Inverse futures
 
@@ -163,6 +1323,13 @@ impl Trade for TradeHTTP {
             .await
     }
 
+    ///
+    /// cancel_order from a CancelOrderRequest, returning the ids of the cancelled order.
+    ///
+    async fn cancel_order_typed(&self, request: CancelOrderRequest) -> Result<OrderResponse> {
+        response::into_result(self.cancel_order(request.into()).await?)
+    }
+
     /// Query unfilled or partially filled orders in real-time. To query older order records, please use the order history interface.
 
     ///     Required args:
@@ -173,7 +1340,8 @@ impl Trade for TradeHTTP {
 
     ///     Additional information:
     ///         https://bybit-exchange.github.io/docs/v5/order/open-order
-    async fn get_open_orders(&self, query: HashMap<String, String>) -> Result<Value> {
+    async fn get_open_orders(&self, mut query: HashMap<String, String>) -> Result<Value> {
+        symbols::apply_default_settle_coin(&mut query, self.default_settle_coin.as_deref());
         self.http_manager
             .submit_request(
                 Method::GET,
@@ -198,7 +1366,8 @@ impl Trade for TradeHTTP {
 
     ///     Additional information:
     ///         https://bybit-exchange.github.io/docs/v5/order/cancel-all
-    async fn cancel_all_orders(&self, query: HashMap<String, String>) -> Result<Value> {
+    async fn cancel_all_orders(&self, mut query: HashMap<String, String>) -> Result<Value> {
+        symbols::apply_default_settle_coin(&mut query, self.default_settle_coin.as_deref());
         self.http_manager
             .submit_post_request(
                 Method::POST,
@@ -209,6 +1378,18 @@ impl Trade for TradeHTTP {
             .await
     }
 
+    ///
+    /// cancel_all_orders from a CancelAllRequest, returning the ids of every cancelled
+    /// order so they can be reconciled against local state.
+    ///
+    async fn cancel_all_orders_typed(
+        &self,
+        request: CancelAllRequest,
+    ) -> Result<Vec<OrderResponse>> {
+        request.validate()?;
+        response::into_list(self.cancel_all_orders(request.into()).await?)
+    }
+
     /// Query order history. As order creation/cancellation is asynchronous, the data returned from this endpoint may delay.
     ///     If you want to get real-time order information, you could query this endpoint or rely on the websocket stream (recommended).
 
@@ -234,6 +1415,24 @@ impl Trade for TradeHTTP {
             )
             .await
     }
+
+    ///
+    /// Active orders from /v5/order/realtime, one page of `Order`s. Use
+    /// `order_filter` to pick active, conditional or TP/SL orders.
+    ///
+    async fn get_open_orders_typed(&self, query: OrderQuery) -> Result<Vec<Order>> {
+        query.validate()?;
+        orders_from_response(self.get_open_orders(query.into()).await?)
+    }
+
+    ///
+    /// Closed and cancelled orders from /v5/order/history, one page of `Order`s.
+    /// `open_only` is ignored by this endpoint.
+    ///
+    async fn get_order_history_typed(&self, query: OrderQuery) -> Result<Vec<Order>> {
+        query.validate()?;
+        orders_from_response(self.get_order_history(query.into()).await?)
+    }
     /// Covers: Option (Unified Account)
 
     ///     Required args:
@@ -275,7 +1474,7 @@ impl Trade for TradeHTTP {
         self.http_manager
             .submit_post_request(
                 Method::POST,
-                &v5trade::Trade::CancelAllOrders.to_string(),
+                &v5trade::Trade::BatchCancelOrder.to_string(),
                 true,
                 query,
             )
@@ -324,3 +1523,118 @@ impl Trade for TradeHTTP {
             .await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn amending_the_price_sends_only_the_price() {
+        let request = AmendOrderRequest::new(
+            Category::Linear,
+            "BTCUSDT",
+            OrderRef::OrderLinkId("grid-7".to_string()),
+        )
+        .price("27000.5".parse().unwrap());
+        let query = HashMap::from(request);
+        let mut keys: Vec<&str> = query.keys().map(String::as_str).collect();
+        keys.sort_unstable();
+        assert_eq!(keys, ["category", "orderLinkId", "price", "symbol"]);
+        assert_eq!(query["price"], "27000.5");
+        for field in ["qty", "triggerPrice", "takeProfit", "stopLoss"] {
+            assert!(!query.contains_key(field), "{} was sent", field);
+        }
+    }
+
+    #[test]
+    fn a_batch_with_a_taken_or_repeated_id_claims_nothing() {
+        let guard = OrderLinkIdGuard::new(100);
+        guard.claim("taken").unwrap();
+        assert!(guard.claim_all(&["a", "taken"]).is_err());
+        assert!(guard.claim_all(&["b", "b"]).is_err());
+        assert!(!guard.contains("a") && !guard.contains("b"));
+        guard.claim_all(&["a", "b"]).unwrap();
+        assert!(guard.contains("a") && guard.contains("b"));
+    }
+
+    #[test]
+    fn released_ids_can_be_claimed_again() {
+        let guard = OrderLinkIdGuard::new(100);
+        guard.claim("a").unwrap();
+        guard.release("a");
+        assert!(!guard.contains("a"));
+        guard.claim("a").unwrap();
+    }
+
+    #[test]
+    fn a_rejected_order_can_be_retried_with_its_id() {
+        let manager = Arc::new(HttpManager::new(String::new(), String::new(), false));
+        let guard = Arc::new(OrderLinkIdGuard::new(100));
+        let trade = TradeHTTP::new(manager).with_order_link_id_guard(guard.clone());
+        let id = "tp-1".to_string();
+
+        guard.claim(&id).unwrap();
+        let rejected = json!({ "retCode": 110007, "retMsg": "ab not enough for new order", "result": {}, "retExtInfo": {} });
+        trade.release_unplaced_order_link_id(Some(&id), Ok(&rejected));
+        assert!(!guard.contains(&id));
+
+        guard.claim(&id).unwrap();
+        trade.release_unplaced_order_link_id(Some(&id), Err(&AppError::Timeout));
+        assert!(!guard.contains(&id));
+
+        guard.claim(&id).unwrap();
+        let placed = json!({ "retCode": 0, "retMsg": "OK", "result": { "orderId": "1321003749386327552", "orderLinkId": "tp-1" }, "retExtInfo": {} });
+        trade.release_unplaced_order_link_id(Some(&id), Ok(&placed));
+        assert!(guard.contains(&id));
+
+        let duplicate = json!({ "retCode": 110072, "retMsg": "OrderLinkedID is duplicate", "result": {}, "retExtInfo": {} });
+        trade.release_unplaced_order_link_id(Some(&id), Ok(&duplicate));
+        assert!(guard.contains(&id));
+    }
+
+    #[test]
+    fn rejected_batch_entries_are_the_ones_bybit_did_not_place() {
+        let partial = json!({
+            "retCode": 0,
+            "retMsg": "OK",
+            "result": { "list": [
+                { "category": "linear", "symbol": "BTCUSDT", "orderId": "1666800494330512128", "orderLinkId": "batch-1" },
+                { "category": "linear", "symbol": "BTCUSDX", "orderId": "", "orderLinkId": "batch-2" }
+            ] },
+            "retExtInfo": { "list": [{ "code": 0, "msg": "OK" }, { "code": 10001, "msg": "symbol invalid" }] }
+        });
+        assert_eq!(rejected_batch_entries(&partial, 2), vec![1]);
+        let rejected =
+            json!({ "retCode": 10001, "retMsg": "params error", "result": {}, "retExtInfo": {} });
+        assert_eq!(rejected_batch_entries(&rejected, 2), vec![0, 1]);
+    }
+
+    #[test]
+    fn a_batch_with_an_invalid_symbol_fails_only_that_order() {
+        let body = json!({
+            "retCode": 0,
+            "retMsg": "OK",
+            "result": { "list": [
+                { "category": "linear", "symbol": "BTCUSDX", "orderId": "", "orderLinkId": "", "createAt": "" },
+                { "category": "linear", "symbol": "ETHUSDT", "orderId": "2b2dc5a2-4f5f-4dc8-a8c8-6b7b4b41a8d6", "orderLinkId": "batch-2", "createAt": "1713434102753" }
+            ] },
+            "retExtInfo": { "list": [
+                { "code": 10001, "msg": "params error: symbol invalid" },
+                { "code": 0, "msg": "OK" }
+            ] },
+            "time": 1713434102753u64
+        });
+        let batch = BatchOrderResponse::from_response(body).unwrap();
+        assert_eq!(batch.items.len(), 2);
+        let successes: Vec<_> = batch.successes().collect();
+        assert_eq!(successes.len(), 1);
+        assert_eq!(successes[0].0, 1);
+        assert_eq!(successes[0].1.order_link_id, "batch-2");
+        let failures: Vec<_> = batch.failures().collect();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].0, 0);
+        assert_eq!(failures[0].1.ret_code(), Some(10001));
+    }
+}
@@ -0,0 +1,631 @@
+use serde_derive::{Deserialize, Serialize};
+
+use crate::errors::app_error::AppError;
+
+///
+/// Type of the price, size and quantity fields of the typed responses: f64 by default,
+/// `rust_decimal::Decimal` with the `decimal` feature, which keeps values such as
+/// `0.00000001` exact.
+///
+#[cfg(not(feature = "decimal"))]
+pub type Number = f64;
+#[cfg(feature = "decimal")]
+pub type Number = rust_decimal::Decimal;
+
+/// Number as f64, for arithmetic where float precision is good enough.
+#[cfg(not(feature = "decimal"))]
+pub fn number_to_f64(number: Number) -> f64 {
+    number
+}
+
+/// Number as f64, for arithmetic where float precision is good enough.
+#[cfg(feature = "decimal")]
+pub fn number_to_f64(number: Number) -> f64 {
+    rust_decimal::prelude::ToPrimitive::to_f64(&number).unwrap_or_default()
+}
+
+///
+/// Formats a Number for a query string or request body. Neither f64 nor Decimal
+/// use scientific notation, and trailing zeros of a Decimal are dropped.
+///
+pub fn format_number(number: Number) -> String {
+    #[cfg(feature = "decimal")]
+    let number = number.normalize();
+    number.to_string()
+}
+
+///
+/// `value` snapped onto a multiple of `step`: rounded down when `down` is set, to the
+/// nearest multiple otherwise. A step of zero or less returns `value` unchanged.
+///
+#[cfg(not(feature = "decimal"))]
+pub fn round_to_step(value: Number, step: Number, down: bool) -> Number {
+    if step <= 0.0 {
+        return value;
+    }
+    // 0.3 / 0.1 is 2.9999999999999996, nudge it back before flooring.
+    let steps = value / step;
+    let steps = if down {
+        (steps + 1e-9).floor()
+    } else {
+        steps.round()
+    };
+    let factor = 10f64.powi(crate::helpers::utils::step_decimals(&step.to_string()) as i32);
+    (steps * step * factor).round() / factor
+}
+
+///
+/// `value` snapped onto a multiple of `step`: rounded down when `down` is set, to the
+/// nearest multiple otherwise. A step of zero or less returns `value` unchanged.
+///
+#[cfg(feature = "decimal")]
+pub fn round_to_step(value: Number, step: Number, down: bool) -> Number {
+    if step <= Number::ZERO {
+        return value;
+    }
+    let steps = value / step;
+    let steps = if down { steps.floor() } else { steps.round() };
+    (steps * step).normalize()
+}
+
+///
+/// Bybit deployment a client talks to. Demo trading runs on mainnet accounts with
+/// simulated funds and only has its own REST host and private stream; its public
+/// data is mainnet's. `false` / `true` convert to Mainnet / Testnet.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Environment {
+    #[default]
+    Mainnet,
+    Testnet,
+    Demo,
+}
+
+impl Environment {
+    pub fn rest_url(&self) -> &'static str {
+        match self {
+            Environment::Mainnet => "https://api.bybit.com",
+            Environment::Testnet => "https://api-testnet.bybit.com",
+            Environment::Demo => "https://api-demo.bybit.com",
+        }
+    }
+
+    /// Public stream of `category`, e.g. `wss://stream.bybit.com/v5/public/linear`.
+    pub fn public_ws_url(&self, category: Category) -> String {
+        let host = match self {
+            Environment::Testnet => "stream-testnet.bybit.com",
+            Environment::Mainnet | Environment::Demo => "stream.bybit.com",
+        };
+        format!("wss://{}/v5/public/{}", host, category)
+    }
+
+    pub fn private_ws_url(&self) -> &'static str {
+        match self {
+            Environment::Mainnet => "wss://stream.bybit.com/v5/private",
+            Environment::Testnet => "wss://stream-testnet.bybit.com/v5/private",
+            Environment::Demo => "wss://stream-demo.bybit.com/v5/private",
+        }
+    }
+}
+
+impl From<bool> for Environment {
+    fn from(testnet: bool) -> Self {
+        if testnet {
+            Environment::Testnet
+        } else {
+            Environment::Mainnet
+        }
+    }
+}
+
+/// Product type, the `category` of every v5 request.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Category {
+    Spot,
+    Linear,
+    Inverse,
+    Option,
+}
+
+impl std::fmt::Display for Category {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Category::Spot => write!(f, "spot"),
+            Category::Linear => write!(f, "linear"),
+            Category::Inverse => write!(f, "inverse"),
+            Category::Option => write!(f, "option"),
+        }
+    }
+}
+
+impl std::str::FromStr for Category {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "spot" => Ok(Category::Spot),
+            "linear" => Ok(Category::Linear),
+            "inverse" => Ok(Category::Inverse),
+            "option" => Ok(Category::Option),
+            _ => Err(AppError::InvalidParameter(format!(
+                "unknown category {}",
+                s
+            ))),
+        }
+    }
+}
+
+/// Kline interval, shared by every kline endpoint and the kline topic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Interval {
+    Min1,
+    Min3,
+    Min5,
+    Min15,
+    Min30,
+    Min60,
+    Min120,
+    Min240,
+    Min360,
+    Min720,
+    Day,
+    Week,
+    Month,
+}
+
+impl Interval {
+    pub const ALL: [Interval; 13] = [
+        Interval::Min1,
+        Interval::Min3,
+        Interval::Min5,
+        Interval::Min15,
+        Interval::Min30,
+        Interval::Min60,
+        Interval::Min120,
+        Interval::Min240,
+        Interval::Min360,
+        Interval::Min720,
+        Interval::Day,
+        Interval::Week,
+        Interval::Month,
+    ];
+
+    ///
+    /// Token Bybit expects: the number of minutes, or D, W, M.
+    ///
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Interval::Min1 => "1",
+            Interval::Min3 => "3",
+            Interval::Min5 => "5",
+            Interval::Min15 => "15",
+            Interval::Min30 => "30",
+            Interval::Min60 => "60",
+            Interval::Min120 => "120",
+            Interval::Min240 => "240",
+            Interval::Min360 => "360",
+            Interval::Min720 => "720",
+            Interval::Day => "D",
+            Interval::Week => "W",
+            Interval::Month => "M",
+        }
+    }
+
+    ///
+    /// Length of one candle in ms, None for months which vary in length.
+    ///
+    pub fn duration_ms(&self) -> Option<u64> {
+        let minutes = match self {
+            Interval::Day => 24 * 60,
+            Interval::Week => 7 * 24 * 60,
+            Interval::Month => return None,
+            minutes => minutes.as_str().parse::<u64>().ok()?,
+        };
+        Some(minutes * 60_000)
+    }
+}
+
+impl std::fmt::Display for Interval {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl std::str::FromStr for Interval {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Interval::ALL
+            .iter()
+            .find(|interval| interval.as_str() == s)
+            .copied()
+            .ok_or_else(|| AppError::InvalidParameter(format!("unknown kline interval {}", s)))
+    }
+}
+
+///
+/// Bucket size of the derivatives statistics endpoints: `period` of the long/short
+/// ratio, `intervalTime` of open interest.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Period {
+    Min5,
+    Min15,
+    Min30,
+    Hour1,
+    Hour4,
+    Day1,
+}
+
+impl Period {
+    pub const ALL: [Period; 6] = [
+        Period::Min5,
+        Period::Min15,
+        Period::Min30,
+        Period::Hour1,
+        Period::Hour4,
+        Period::Day1,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Period::Min5 => "5min",
+            Period::Min15 => "15min",
+            Period::Min30 => "30min",
+            Period::Hour1 => "1h",
+            Period::Hour4 => "4h",
+            Period::Day1 => "1d",
+        }
+    }
+}
+
+impl std::fmt::Display for Period {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl std::str::FromStr for Period {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Period::ALL
+            .iter()
+            .find(|period| period.as_str() == s)
+            .copied()
+            .ok_or_else(|| AppError::InvalidParameter(format!("unknown period {}", s)))
+    }
+}
+
+/// Order side.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+impl std::fmt::Display for Side {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Side::Buy => write!(f, "Buy"),
+            Side::Sell => write!(f, "Sell"),
+        }
+    }
+}
+
+/// Order type, UNKNOWN on executions that don't come from an order such as funding.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OrderType {
+    Market,
+    Limit,
+    #[serde(rename = "UNKNOWN", other)]
+    Unknown,
+}
+
+impl std::fmt::Display for OrderType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            OrderType::Market => write!(f, "Market"),
+            OrderType::Limit => write!(f, "Limit"),
+            OrderType::Unknown => write!(f, "UNKNOWN"),
+        }
+    }
+}
+
+///
+/// How long an order stays open. Market orders are IOC whatever is sent, and come
+/// back with an empty timeInForce on some products: deserialize it with
+/// `de::empty_string_as_none` into `Option<TimeInForce>`.
+///
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TimeInForce {
+    #[serde(rename = "GTC")]
+    Gtc,
+    #[serde(rename = "IOC")]
+    Ioc,
+    #[serde(rename = "FOK")]
+    Fok,
+    /// Cancelled instead of taking liquidity.
+    PostOnly,
+}
+
+impl std::fmt::Display for TimeInForce {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TimeInForce::Gtc => write!(f, "GTC"),
+            TimeInForce::Ioc => write!(f, "IOC"),
+            TimeInForce::Fok => write!(f, "FOK"),
+            TimeInForce::PostOnly => write!(f, "PostOnly"),
+        }
+    }
+}
+
+/// What produced an execution record. Only some of them are actual fills.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ExecType {
+    Trade,
+    AdlTrade,
+    Funding,
+    BustTrade,
+    Delivery,
+    Settle,
+    BlockTrade,
+    MovePosition,
+    #[serde(other)]
+    Unknown,
+}
+
+impl ExecType {
+    ///
+    /// Whether the execution is a fill (the position changed by trading),
+    /// as opposed to funding, settlement or delivery.
+    ///
+    pub fn is_fill(&self) -> bool {
+        matches!(
+            self,
+            ExecType::Trade | ExecType::AdlTrade | ExecType::BustTrade | ExecType::BlockTrade
+        )
+    }
+}
+
+/// Account type of the wallet and transfer endpoints.
+/// get_wallet_balance takes Unified, Contract (classic derivatives) and Spot.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum AccountType {
+    Unified,
+    Contract,
+    Spot,
+    Fund,
+    Option,
+}
+
+impl std::fmt::Display for AccountType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            AccountType::Unified => write!(f, "UNIFIED"),
+            AccountType::Contract => write!(f, "CONTRACT"),
+            AccountType::Spot => write!(f, "SPOT"),
+            AccountType::Fund => write!(f, "FUND"),
+            AccountType::Option => write!(f, "OPTION"),
+        }
+    }
+}
+
+impl std::str::FromStr for AccountType {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "UNIFIED" => Ok(AccountType::Unified),
+            "CONTRACT" => Ok(AccountType::Contract),
+            "SPOT" => Ok(AccountType::Spot),
+            "FUND" => Ok(AccountType::Fund),
+            "OPTION" => Ok(AccountType::Option),
+            _ => Err(AppError::InvalidParameter(format!(
+                "unknown account type {}",
+                s
+            ))),
+        }
+    }
+}
+
+///
+/// positionIdx of an order or position: OneWay (0) is the only valid value in one-way
+/// mode, hedge mode has a BuyHedge (1, long) and a SellHedge (2, short) leg.
+/// Serialized as the number Bybit uses.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum PositionIdx {
+    #[default]
+    OneWay,
+    BuyHedge,
+    SellHedge,
+}
+
+impl PositionIdx {
+    pub fn as_u8(&self) -> u8 {
+        match self {
+            PositionIdx::OneWay => 0,
+            PositionIdx::BuyHedge => 1,
+            PositionIdx::SellHedge => 2,
+        }
+    }
+
+    pub fn from_u8(position_idx: u8) -> Option<Self> {
+        match position_idx {
+            0 => Some(PositionIdx::OneWay),
+            1 => Some(PositionIdx::BuyHedge),
+            2 => Some(PositionIdx::SellHedge),
+            _ => None,
+        }
+    }
+
+    /// Position mode this index is valid in.
+    pub fn mode(&self) -> PositionMode {
+        match self {
+            PositionIdx::OneWay => PositionMode::OneWay,
+            PositionIdx::BuyHedge | PositionIdx::SellHedge => PositionMode::Hedge,
+        }
+    }
+}
+
+impl std::fmt::Display for PositionIdx {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.as_u8())
+    }
+}
+
+impl serde::Serialize for PositionIdx {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8(self.as_u8())
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for PositionIdx {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let position_idx = <u8 as serde::Deserialize>::deserialize(deserializer)?;
+        PositionIdx::from_u8(position_idx).ok_or_else(|| {
+            serde::de::Error::custom(format!("unknown positionIdx {}", position_idx))
+        })
+    }
+}
+
+/// retCode of a request with an invalid parameter.
+const PARAMS_ERROR: i32 = 10001;
+
+///
+/// Adds a position mode hint to the 10001 Bybit answers a request whose positionIdx
+/// doesn't fit the symbol's position mode with, e.g. BuyHedge in one-way mode.
+///
+pub(crate) fn position_idx_hint(err: AppError, position_idx: Option<PositionIdx>) -> AppError {
+    match (err, position_idx) {
+        (AppError::Api { ret_code, ret_msg }, Some(position_idx)) if ret_code == PARAMS_ERROR => {
+            AppError::Api {
+                ret_code,
+                ret_msg: format!(
+                    "{} (positionIdx {} needs {:?} position mode, check the symbol's mode)",
+                    ret_msg,
+                    position_idx,
+                    position_idx.mode()
+                ),
+            }
+        }
+        (err, _) => err,
+    }
+}
+
+/// Margin mode of a classic account position, see switch_margin_mode_typed.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TradeMode {
+    Cross,
+    Isolated,
+}
+
+impl TradeMode {
+    /// Value of the `tradeMode` parameter: 0 cross, 1 isolated.
+    pub fn trade_mode_code(&self) -> u8 {
+        match self {
+            TradeMode::Cross => 0,
+            TradeMode::Isolated => 1,
+        }
+    }
+}
+
+/// Position mode of a linear / inverse symbol.
+/// One-way keeps a single position (positionIdx 0), hedge keeps a long (1) and a short (2).
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PositionMode {
+    OneWay,
+    Hedge,
+}
+
+impl PositionMode {
+    ///
+    /// Mode a position list entry belongs to, from its positionIdx.
+    ///
+    pub fn from_position_idx(position_idx: u64) -> Option<Self> {
+        match position_idx {
+            0 => Some(PositionMode::OneWay),
+            1 | 2 => Some(PositionMode::Hedge),
+            _ => None,
+        }
+    }
+
+    ///
+    /// Value of the `mode` parameter of switch_position_mode: 0 one-way, 3 hedge.
+    ///
+    pub fn mode_code(&self) -> u8 {
+        match self {
+            PositionMode::OneWay => 0,
+            PositionMode::Hedge => 3,
+        }
+    }
+
+    ///
+    /// positionIdx an order must carry: 0 in one-way mode, in hedge mode the leg it
+    /// opens (Buy -> 1, Sell -> 2) or, for reduce-only orders, the leg it closes.
+    ///
+    pub fn position_idx(&self, side: Side, reduce_only: bool) -> PositionIdx {
+        match (self, side, reduce_only) {
+            (PositionMode::OneWay, _, _) => PositionIdx::OneWay,
+            (PositionMode::Hedge, Side::Buy, false) | (PositionMode::Hedge, Side::Sell, true) => {
+                PositionIdx::BuyHedge
+            }
+            (PositionMode::Hedge, Side::Sell, false) | (PositionMode::Hedge, Side::Buy, true) => {
+                PositionIdx::SellHedge
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn round_trip<T>(value: T, wire: serde_json::Value)
+    where
+        T: serde::Serialize + serde::de::DeserializeOwned + PartialEq + std::fmt::Debug,
+    {
+        assert_eq!(serde_json::to_value(&value).unwrap(), wire);
+        assert_eq!(serde_json::from_value::<T>(wire).unwrap(), value);
+    }
+
+    #[test]
+    fn position_idx_is_a_number_on_the_wire() {
+        round_trip(PositionIdx::OneWay, json!(0));
+        round_trip(PositionIdx::BuyHedge, json!(1));
+        round_trip(PositionIdx::SellHedge, json!(2));
+        assert!(serde_json::from_value::<PositionIdx>(json!(3)).is_err());
+        assert!(serde_json::from_value::<PositionIdx>(json!("1")).is_err());
+    }
+
+    #[test]
+    fn order_fields_use_bybit_names() {
+        round_trip(Side::Buy, json!("Buy"));
+        round_trip(OrderType::Limit, json!("Limit"));
+        round_trip(OrderType::Unknown, json!("UNKNOWN"));
+        round_trip(TimeInForce::Gtc, json!("GTC"));
+        round_trip(TimeInForce::PostOnly, json!("PostOnly"));
+        round_trip(Category::Inverse, json!("inverse"));
+        round_trip(ExecType::Funding, json!("Funding"));
+        round_trip(TradeMode::Isolated, json!("Isolated"));
+        round_trip(PositionMode::Hedge, json!("Hedge"));
+    }
+
+    #[test]
+    fn unknown_values_fall_back_where_there_is_an_unknown_variant() {
+        assert_eq!(
+            serde_json::from_value::<OrderType>(json!("StopLimit")).unwrap(),
+            OrderType::Unknown
+        );
+        assert_eq!(
+            serde_json::from_value::<ExecType>(json!("FutureType")).unwrap(),
+            ExecType::Unknown
+        );
+        assert!(serde_json::from_value::<TimeInForce>(json!("GTD")).is_err());
+    }
+}
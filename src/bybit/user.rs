@@ -10,12 +10,217 @@ use futures::Future;
 use reqwest::Method;
 use serde_json::Value;
 
-use crate::endpoints::v5user;
+use crate::{endpoints::v5user, errors::app_error::AppError};
 
 use super::{
     http_manager::{HttpManager, Manager},
-    Result,
+    response, Result,
 };
+
+/// Permissions granted to an API key, grouped the way Bybit reports them.
+/// Empty groups are left out when sent in a create/modify request.
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct ApiKeyPermissions {
+    #[serde(
+        rename = "ContractTrade",
+        default,
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub contract_trade: Vec<String>,
+    #[serde(rename = "Spot", default, skip_serializing_if = "Vec::is_empty")]
+    pub spot: Vec<String>,
+    #[serde(rename = "Wallet", default, skip_serializing_if = "Vec::is_empty")]
+    pub wallet: Vec<String>,
+    #[serde(rename = "Options", default, skip_serializing_if = "Vec::is_empty")]
+    pub options: Vec<String>,
+    #[serde(rename = "Derivatives", default, skip_serializing_if = "Vec::is_empty")]
+    pub derivatives: Vec<String>,
+    #[serde(rename = "CopyTrading", default, skip_serializing_if = "Vec::is_empty")]
+    pub copy_trading: Vec<String>,
+    #[serde(rename = "BlockTrade", default, skip_serializing_if = "Vec::is_empty")]
+    pub block_trade: Vec<String>,
+    #[serde(rename = "Exchange", default, skip_serializing_if = "Vec::is_empty")]
+    pub exchange: Vec<String>,
+    #[serde(rename = "NFT", default, skip_serializing_if = "Vec::is_empty")]
+    pub nft: Vec<String>,
+}
+
+impl ApiKeyPermissions {
+    ///
+    /// Returns the permissions of a group by its Bybit name, e.g. "Spot" or "ContractTrade".
+    ///
+    pub fn group(&self, group: &str) -> &[String] {
+        match group {
+            "ContractTrade" => &self.contract_trade,
+            "Spot" => &self.spot,
+            "Wallet" => &self.wallet,
+            "Options" => &self.options,
+            "Derivatives" => &self.derivatives,
+            "CopyTrading" => &self.copy_trading,
+            "BlockTrade" => &self.block_trade,
+            "Exchange" => &self.exchange,
+            "NFT" => &self.nft,
+            _ => &[],
+        }
+    }
+}
+
+/// Information about the API key used to sign the request.
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiKeyInfo {
+    pub id: String,
+    #[serde(default)]
+    pub note: String,
+    pub api_key: String,
+    /// 0: read and write, 1: read only
+    pub read_only: u8,
+    #[serde(default)]
+    pub permissions: ApiKeyPermissions,
+    /// IP whitelist, `["*"]` when the key is not bound to any IP.
+    #[serde(default)]
+    pub ips: Vec<String>,
+    /// 1: personal, 2: third-party application
+    #[serde(rename = "type")]
+    pub key_type: u8,
+    /// Remaining valid days, 0 for keys that never expire.
+    #[serde(default)]
+    pub deadline_day: i64,
+    /// Expiry time as an ISO 8601 timestamp, empty for keys that never expire.
+    #[serde(default)]
+    pub expired_at: String,
+    #[serde(default)]
+    pub created_at: String,
+    #[serde(default)]
+    pub uta: u8,
+    #[serde(rename = "userID", default)]
+    pub user_id: u64,
+    #[serde(default)]
+    pub is_master: bool,
+}
+
+/// Structure used to create a sub account api key
+#[derive(serde_derive::Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateSubApiKeyRequest {
+    pub subuid: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// 0: read and write, 1: read only
+    pub read_only: u8,
+    /// Comma separated IP whitelist, e.g. "192.168.0.1,192.168.0.2"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ips: Option<String>,
+    pub permissions: ApiKeyPermissions,
+}
+
+/// Structure used to modify a sub account api key.
+/// `apikey` is only needed when calling with the master account's key.
+#[derive(serde_derive::Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ModifySubApiKeyRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub apikey: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_only: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ips: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub permissions: Option<ApiKeyPermissions>,
+}
+
+/// A sub account api key.
+/// `secret` holds the real secret only in the response of the create call,
+/// Bybit masks it everywhere else.
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SubApiKey {
+    pub id: String,
+    #[serde(default)]
+    pub note: String,
+    pub api_key: String,
+    pub read_only: u8,
+    #[serde(default)]
+    pub secret: String,
+    #[serde(default)]
+    pub permissions: ApiKeyPermissions,
+    #[serde(default, deserialize_with = "ips_from_list_or_string")]
+    pub ips: Vec<String>,
+    #[serde(default)]
+    pub expired_at: String,
+    #[serde(default)]
+    pub created_at: String,
+}
+
+/// One page of the api keys of a sub account.
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SubApiKeyList {
+    #[serde(rename = "result", default)]
+    pub keys: Vec<SubApiKey>,
+    #[serde(default)]
+    pub next_page_cursor: String,
+}
+
+///
+/// The create/modify responses report `ips` as a comma separated string while
+/// the list endpoint uses an array, accept both.
+///
+fn ips_from_list_or_string<'de, D>(deserializer: D) -> std::result::Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(serde_derive::Deserialize)]
+    #[serde(untagged)]
+    enum Ips {
+        List(Vec<String>),
+        Joined(String),
+    }
+    Ok(match serde::Deserialize::deserialize(deserializer)? {
+        Ips::List(ips) => ips,
+        Ips::Joined(ips) => ips
+            .split(',')
+            .filter(|ip| !ip.is_empty())
+            .map(str::to_string)
+            .collect(),
+    })
+}
+
+impl ApiKeyInfo {
+    pub fn is_read_only(&self) -> bool {
+        self.read_only == 1
+    }
+
+    pub fn has_permission(&self, group: &str, permission: &str) -> bool {
+        self.permissions
+            .group(group)
+            .iter()
+            .any(|granted| granted == permission)
+    }
+
+    ///
+    /// Fails with `AppError::MissingPermission` unless the key can write and
+    /// holds every `(group, permission)` pair, e.g. `("Spot", "SpotTrade")`.
+    /// Meant to be called once at startup before trading.
+    ///
+    pub fn require_permissions(
+        &self,
+        required: &[(&str, &str)],
+    ) -> std::result::Result<(), AppError> {
+        if self.is_read_only() {
+            return Err(AppError::MissingPermission("write".to_string()));
+        }
+        for (group, permission) in required {
+            if !self.has_permission(group, permission) {
+                return Err(AppError::MissingPermission(format!(
+                    "{}.{}",
+                    group, permission
+                )));
+            }
+        }
+        Ok(())
+    }
+}
 #[async_trait]
 pub trait User {
     fn new(http_manager: Arc<HttpManager>) -> Self;
@@ -23,15 +228,26 @@ pub trait User {
 
     async fn create_sub_api_key(&self, query: HashMap<String, String>) -> Result<Value>;
 
+    async fn create_sub_api_key_typed(&self, query: CreateSubApiKeyRequest) -> Result<SubApiKey>;
+
+    async fn get_sub_account_all_api_keys(
+        &self,
+        query: HashMap<String, String>,
+    ) -> Result<SubApiKeyList>;
+
     async fn get_sub_uid_list(&self, query: HashMap<String, String>) -> Result<Value>;
     async fn freeze_sub_uid(&self, query: HashMap<String, String>) -> Result<Value>;
 
     async fn get_api_key_information(&self, query: HashMap<String, String>) -> Result<Value>;
 
+    async fn get_api_key_info(&self) -> Result<ApiKeyInfo>;
+
     async fn modify_master_api_key(&self, query: HashMap<String, String>) -> Result<Value>;
 
     async fn modify_sub_api_key(&self, query: HashMap<String, String>) -> Result<Value>;
 
+    async fn modify_sub_api_key_typed(&self, query: ModifySubApiKeyRequest) -> Result<SubApiKey>;
+
     async fn delete_master_api_key(&self, query: HashMap<String, String>) -> Result<Value>;
 
     async fn delete_sub_api_key(&self, query: HashMap<String, String>) -> Result<Value>;
@@ -88,10 +304,49 @@ impl User for UserHTTP {
         let endpoint = v5user::User::CreateSubApiKey.to_string();
         let result = self
             .http_manager
-            .submit_request(Method::GET, &endpoint, query, true)
+            .submit_post_request(Method::POST, &endpoint, true, query)
             .await?;
         Ok(result)
     }
+
+    /// Same as create_sub_api_key, with a typed request and response.
+    ///     The returned secret is only shown once, store it right away.
+
+    ///     Returns:
+    ///         SubApiKey
+
+    ///     Additional information:
+    ///         https://bybit-exchange.github.io/docs/v5/user/create-subuid-apikey
+    async fn create_sub_api_key_typed(&self, query: CreateSubApiKeyRequest) -> Result<SubApiKey> {
+        let endpoint = v5user::User::CreateSubApiKey.to_string();
+        let result = self
+            .http_manager
+            .submit_post_request(Method::POST, &endpoint, true, query)
+            .await?;
+        response::into_result(result)
+    }
+
+    /// Query all api keys of a sub account. Use master user's api key only.
+
+    ///     Required args:
+    ///         subMemberId (string): Sub user Id
+
+    ///     Returns:
+    ///         SubApiKeyList, follow next_page_cursor for the next page.
+
+    ///     Additional information:
+    ///         https://bybit-exchange.github.io/docs/v5/user/list-sub-apikeys
+    async fn get_sub_account_all_api_keys(
+        &self,
+        query: HashMap<String, String>,
+    ) -> Result<SubApiKeyList> {
+        let endpoint = v5user::User::GetSubAccountAllApiKeys.to_string();
+        let result = self
+            .http_manager
+            .submit_request(Method::GET, &endpoint, query, true)
+            .await?;
+        response::into_result(result)
+    }
     /// Get all sub uid of master account. Use master user's api key only.
 
     ///     Returns:
@@ -143,6 +398,22 @@ impl User for UserHTTP {
             .await?;
         Ok(result)
     }
+
+    /// Get the permissions, IP whitelist and expiry of the api key used to call the endpoint.
+
+    ///     Returns:
+    ///         ApiKeyInfo
+
+    ///     Additional information:
+    ///         https://bybit-exchange.github.io/docs/v5/user/apikey-info
+    async fn get_api_key_info(&self) -> Result<ApiKeyInfo> {
+        let endpoint = v5user::User::GetApiKeyInformation.to_string();
+        let result = self
+            .http_manager
+            .submit_request(Method::GET, &endpoint, HashMap::new(), true)
+            .await?;
+        response::into_result(result)
+    }
     /// Modify the settings of master api key. Use the api key pending to be modified to call the endpoint. Use master user's api key only.
 
     /// Required args:
@@ -183,6 +454,22 @@ impl User for UserHTTP {
         Ok(result)
     }
 
+    /// Same as modify_sub_api_key, with a typed request and response.
+
+    ///     Returns:
+    ///         SubApiKey
+
+    ///     Additional information:
+    ///         https://bybit-exchange.github.io/docs/v5/user/modify-sub-apikey
+    async fn modify_sub_api_key_typed(&self, query: ModifySubApiKeyRequest) -> Result<SubApiKey> {
+        let endpoint = v5user::User::ModifySubApiKey.to_string();
+        let result = self
+            .http_manager
+            .submit_post_request(Method::POST, &endpoint, true, query)
+            .await?;
+        response::into_result(result)
+    }
+
     /// Delete the api key of master account. Use the api key pending to be delete to call the endpoint. Use master user's api key only.
 
     ///     Returns:
@@ -0,0 +1,287 @@
+use std::collections::HashMap;
+
+use super::{
+    market::{Instrument, RiskLimit},
+    types::number_to_f64,
+};
+
+/// Tolerance for float error when checking a value sits on a tick / step grid.
+const EPSILON: f64 = 1e-9;
+
+///
+/// A reason an order would be rejected, found without sending it.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationError {
+    MissingField(&'static str),
+    InvalidNumber { field: &'static str, value: String },
+    SymbolMismatch { order: String, instrument: String },
+    NotTrading { status: String },
+    PriceRequired,
+    PriceOutOfRange { price: f64, min: f64, max: f64 },
+    PriceOffTick { price: f64, tick_size: f64 },
+    QtyOutOfRange { qty: f64, min: f64, max: f64 },
+    QtyOffStep { qty: f64, step: f64 },
+    BelowMinNotional { notional: f64, min: f64 },
+    LeverageNotAllowed { leverage: f64 },
+    ExceedsRiskLimit { value: f64, max: f64 },
+    InsufficientBalance { required: f64, available: f64 },
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ValidationError::MissingField(field) => write!(f, "{} is required", field),
+            ValidationError::InvalidNumber { field, value } => {
+                write!(f, "{} is not a number: {}", field, value)
+            }
+            ValidationError::SymbolMismatch { order, instrument } => write!(
+                f,
+                "order is for {} but the instrument is {}",
+                order, instrument
+            ),
+            ValidationError::NotTrading { status } => {
+                write!(f, "symbol is not trading (status {})", status)
+            }
+            ValidationError::PriceRequired => write!(f, "limit orders need a price"),
+            ValidationError::PriceOutOfRange { price, min, max } => {
+                write!(f, "price {} outside [{}, {}]", price, min, max)
+            }
+            ValidationError::PriceOffTick { price, tick_size } => {
+                write!(
+                    f,
+                    "price {} is not a multiple of the tick size {}",
+                    price, tick_size
+                )
+            }
+            ValidationError::QtyOutOfRange { qty, min, max } => {
+                write!(f, "qty {} outside [{}, {}]", qty, min, max)
+            }
+            ValidationError::QtyOffStep { qty, step } => {
+                write!(f, "qty {} is not a multiple of the qty step {}", qty, step)
+            }
+            ValidationError::BelowMinNotional { notional, min } => {
+                write!(f, "order value {} below the minimum of {}", notional, min)
+            }
+            ValidationError::LeverageNotAllowed { leverage } => {
+                write!(f, "leverage {} is not allowed for this symbol", leverage)
+            }
+            ValidationError::ExceedsRiskLimit { value, max } => write!(
+                f,
+                "order value {} above the largest risk limit {} at this leverage",
+                value, max
+            ),
+            ValidationError::InsufficientBalance {
+                required,
+                available,
+            } => write!(
+                f,
+                "order needs {} but only {} is available",
+                required, available
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+///
+/// What validate_order checks the order against, all fetched beforehand by the caller.
+///
+/// Required args:
+///     instrument: from get_instruments_info_typed or MarketHTTP::instrument
+/// Optional args:
+///     risk_limits: tiers from PositionHTTP::risk_limit_tiers, empty skips the risk limit check
+///     available_balance: spendable amount in the coin the order draws on (the settle coin
+///         for derivatives, the quote coin for spot buys, the base coin for spot sells)
+///     reference_price: price used to value market orders, e.g. the ticker's last price
+///     leverage: leverage the position runs at, 1 when not set
+///
+#[derive(Debug, Clone)]
+pub struct OrderContext<'a> {
+    pub instrument: &'a Instrument,
+    pub risk_limits: &'a [RiskLimit],
+    pub available_balance: Option<f64>,
+    pub reference_price: Option<f64>,
+    pub leverage: Option<f64>,
+}
+
+impl<'a> OrderContext<'a> {
+    pub fn new(instrument: &'a Instrument) -> Self {
+        OrderContext {
+            instrument,
+            risk_limits: &[],
+            available_balance: None,
+            reference_price: None,
+            leverage: None,
+        }
+    }
+}
+
+fn on_grid(value: f64, step: f64) -> bool {
+    if step <= 0.0 {
+        return true;
+    }
+    let steps = value / step;
+    (steps - steps.round()).abs() < EPSILON * steps.abs().max(1.0)
+}
+
+fn number(
+    order: &HashMap<String, String>,
+    field: &'static str,
+    errors: &mut Vec<ValidationError>,
+) -> Option<f64> {
+    let value = order.get(field).filter(|value| !value.is_empty())?;
+    match value.parse::<f64>() {
+        Ok(number) => Some(number),
+        Err(_) => {
+            errors.push(ValidationError::InvalidNumber {
+                field,
+                value: value.clone(),
+            });
+            None
+        }
+    }
+}
+
+///
+/// Checks a place_order query locally and returns every problem found, empty when
+/// the order looks acceptable. Nothing is sent: Bybit has no validate-only flag on
+/// order creation, so all checks run against the data in `context`.
+///
+/// Additional information:
+///     Checked locally: required fields, symbol status, price range and tick size, qty
+///     range and step, minimum order value, leverage filter, risk limit tier capacity
+///     and available balance when provided.
+///     Not checked, server state only: current position size and mode, open orders
+///     reserving margin, reduceOnly against the actual position, price bands around
+///     the mark price, account level restrictions.
+///
+pub fn validate_order(
+    order: &HashMap<String, String>,
+    context: &OrderContext,
+) -> Vec<ValidationError> {
+    let mut errors = vec![];
+    let instrument = context.instrument;
+
+    for field in ["category", "symbol", "side", "orderType", "qty"] {
+        if order.get(field).map_or(true, String::is_empty) {
+            errors.push(ValidationError::MissingField(field));
+        }
+    }
+    if let Some(symbol) = order.get("symbol").filter(|symbol| !symbol.is_empty()) {
+        if *symbol != instrument.symbol {
+            errors.push(ValidationError::SymbolMismatch {
+                order: symbol.clone(),
+                instrument: instrument.symbol.clone(),
+            });
+        }
+    }
+    if instrument.status != "Trading" {
+        errors.push(ValidationError::NotTrading {
+            status: instrument.status.clone(),
+        });
+    }
+
+    let is_limit = order.get("orderType").map(String::as_str) == Some("Limit");
+    let price = number(order, "price", &mut errors);
+    if is_limit && order.get("price").map_or(true, String::is_empty) {
+        errors.push(ValidationError::PriceRequired);
+    }
+    if let (Some(price), Some(filter)) = (price.filter(|_| is_limit), &instrument.price_filter) {
+        let min = filter.min_price.map_or(0.0, number_to_f64);
+        let max = filter.max_price.map_or(f64::MAX, number_to_f64);
+        let tick_size = number_to_f64(filter.tick_size);
+        if price < min || price > max {
+            errors.push(ValidationError::PriceOutOfRange { price, min, max });
+        }
+        if !on_grid(price, tick_size) {
+            errors.push(ValidationError::PriceOffTick { price, tick_size });
+        }
+    }
+
+    let qty = number(order, "qty", &mut errors);
+    let is_spot = order.get("category").map(String::as_str) == Some("spot");
+    let quote_qty = is_spot
+        && !is_limit
+        && order.get("side").map(String::as_str) == Some("Buy")
+        && order.get("marketUnit").map(String::as_str) != Some("baseCoin");
+    if let (Some(qty), Some(filter)) = (qty.filter(|_| !quote_qty), &instrument.lot_size_filter) {
+        let min = number_to_f64(filter.min_order_qty);
+        let max = number_to_f64(match filter.max_mkt_order_qty {
+            Some(max_mkt) if !is_limit => max_mkt,
+            _ => filter.max_order_qty,
+        });
+        if qty < min || qty > max {
+            errors.push(ValidationError::QtyOutOfRange { qty, min, max });
+        }
+        if let Some(step) = filter.step().map(number_to_f64) {
+            if !on_grid(qty, step) {
+                errors.push(ValidationError::QtyOffStep { qty, step });
+            }
+        }
+    }
+
+    let leverage = context.leverage.unwrap_or(1.0);
+    if context.leverage.is_some()
+        && instrument.leverage_filter.is_some()
+        && !instrument.valid_leverage(leverage)
+    {
+        errors.push(ValidationError::LeverageNotAllowed { leverage });
+    }
+
+    // Spot market buys are sent as a quote amount, which already is the notional.
+    let notional = match (qty, price.filter(|_| is_limit).or(context.reference_price)) {
+        (Some(qty), _) if quote_qty => Some(qty),
+        (Some(qty), Some(price)) => Some(qty * price),
+        _ => None,
+    };
+    let notional = match notional {
+        Some(notional) => notional,
+        None => return errors,
+    };
+
+    if let Some(min) = instrument
+        .lot_size_filter
+        .as_ref()
+        .and_then(|filter| filter.min_notional())
+        .map(number_to_f64)
+    {
+        if notional < min {
+            errors.push(ValidationError::BelowMinNotional { notional, min });
+        }
+    }
+
+    if !context.risk_limits.is_empty() {
+        let max = context
+            .risk_limits
+            .iter()
+            .filter(|tier| tier.max_leverage + EPSILON >= leverage)
+            .map(|tier| tier.risk_limit_value)
+            .fold(0.0, f64::max);
+        if notional > max {
+            errors.push(ValidationError::ExceedsRiskLimit {
+                value: notional,
+                max,
+            });
+        }
+    }
+
+    if let Some(available) = context.available_balance {
+        let required = if !is_spot {
+            notional / leverage.max(1.0)
+        } else if order.get("side").map(String::as_str) == Some("Sell") {
+            qty.unwrap_or(0.0)
+        } else {
+            notional
+        };
+        if required > available {
+            errors.push(ValidationError::InsufficientBalance {
+                required,
+                available,
+            });
+        }
+    }
+
+    errors
+}
@@ -0,0 +1,1363 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use futures::{
+    stream::{self, FuturesUnordered},
+    Future, SinkExt, Stream, StreamExt,
+};
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::{
+    errors::app_error::AppError,
+    helpers::{de, utils},
+};
+
+use super::{
+    http_manager::HttpManager,
+    market::{Kline, PublicTrade},
+    orderbook::OrderBookData,
+    position::{Execution, Position, PositionInfo},
+    proxy::Proxy,
+    response,
+    trade::{Order, Trade},
+    types::{Category, Environment, Interval, Number, Side},
+    Result,
+};
+
+///
+/// A push of the private `execution` topic:
+/// `{ "topic": "execution", "id": "...", "creationTime": 1672364174455, "data": [...] }`
+///
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecutionMessage {
+    pub topic: String,
+    #[serde(default)]
+    pub id: String,
+    pub creation_time: u64,
+    pub data: Vec<Execution>,
+}
+
+impl ExecutionMessage {
+    ///
+    /// Executions that are actual fills, leaving out funding, settlement and delivery.
+    ///
+    pub fn fills(&self) -> impl Iterator<Item = &Execution> {
+        self.data.iter().filter(|exec| exec.exec_type.is_fill())
+    }
+}
+
+///
+/// A push of the private `order` topic, same envelope as `ExecutionMessage`.
+/// Each order already carries its category.
+///
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderMessage {
+    pub topic: String,
+    #[serde(default)]
+    pub id: String,
+    pub creation_time: u64,
+    pub data: Vec<Order>,
+}
+
+///
+/// Why a subscribe request was rejected.
+/// Transient failures are worth retrying, permanent ones (unknown topic,
+/// invalid symbol, malformed args) will fail the same way every time.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum SubscribeFailure {
+    Transient(String),
+    Permanent(String),
+}
+
+impl std::fmt::Display for SubscribeFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SubscribeFailure::Transient(msg) => write!(f, "Subscribe failed (transient): {}", msg),
+            SubscribeFailure::Permanent(msg) => write!(f, "Subscribe failed: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for SubscribeFailure {}
+
+/// `ret_msg` fragments Bybit uses when the topic itself is rejected.
+const PERMANENT_SUBSCRIBE_ERRORS: [&str; 5] = [
+    "handler not found",
+    "invalid topic",
+    "invalid symbol",
+    "args size",
+    "already subscribed",
+];
+
+///
+/// Classifies the `ret_msg` of a NAK'd subscribe frame, e.g.
+/// `error:handler not found,topic:orderbook.50.BTCUSDTX` is permanent.
+///
+pub fn classify_subscribe_failure(ret_msg: &str) -> SubscribeFailure {
+    let msg = ret_msg.to_lowercase();
+    if PERMANENT_SUBSCRIBE_ERRORS
+        .iter()
+        .any(|fragment| msg.contains(fragment))
+    {
+        SubscribeFailure::Permanent(ret_msg.to_string())
+    } else {
+        SubscribeFailure::Transient(ret_msg.to_string())
+    }
+}
+
+///
+/// Exponential backoff used when re-sending a subscribe frame that failed transiently.
+/// PublicWebSocket and PrivateWebSocket resend a frame NAK'd with a transient
+/// `ret_msg` until it succeeds or `max_attempts` frames were sent; the attempts start
+/// over with every connection.
+///
+#[derive(Debug, Clone)]
+pub struct SubscribeRetry {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for SubscribeRetry {
+    fn default() -> Self {
+        SubscribeRetry {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(10),
+        }
+    }
+}
+
+impl SubscribeRetry {
+    ///
+    /// Delay before retry number `attempt` (starting at 0), or None once
+    /// `max_attempts` have been used.
+    ///
+    pub fn delay(&self, attempt: u32) -> Option<Duration> {
+        if attempt + 1 >= self.max_attempts {
+            return None;
+        }
+        let delay = self.base_delay.saturating_mul(2u32.saturating_pow(attempt));
+        Some(delay.min(self.max_delay))
+    }
+}
+
+///
+/// Backoff between the reconnects of PublicWebSocket and PrivateWebSocket.
+/// With `max_attempts` None, the default, the stream keeps reconnecting until it is
+/// dropped; the delay doubles from `base_delay` up to `max_delay`.
+///
+#[derive(Debug, Clone)]
+pub struct ReconnectPolicy {
+    pub max_attempts: Option<u32>,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        ReconnectPolicy {
+            max_attempts: None,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    ///
+    /// Delay before reconnect number `attempt` (starting at 0), or None once
+    /// `max_attempts` have been used.
+    ///
+    pub fn delay(&self, attempt: u32) -> Option<Duration> {
+        if let Some(max_attempts) = self.max_attempts {
+            if attempt >= max_attempts {
+                return None;
+            }
+        }
+        let delay = self.base_delay.saturating_mul(2u32.saturating_pow(attempt));
+        Some(delay.min(self.max_delay))
+    }
+}
+
+///
+/// Paces resubscription after reconnects so a flapping connection doesn't turn
+/// into a subscribe storm.
+///
+/// Limits respected by the defaults:
+/// - spot accepts at most 10 args per subscribe frame, so topics are sent in batches of 10,
+/// - an args array must stay under 21,000 characters,
+/// - Bybit allows 500 new connections per 5 minutes per IP, one resubscription per
+///   second on top of the reconnect backoff keeps well under it.
+///
+#[derive(Debug, Clone)]
+pub struct ResubscribeThrottle {
+    pub min_interval: Duration,
+    pub max_args_per_frame: usize,
+    pub max_args_len: usize,
+    last_resubscribe: Option<Instant>,
+}
+
+impl Default for ResubscribeThrottle {
+    fn default() -> Self {
+        ResubscribeThrottle {
+            min_interval: Duration::from_secs(1),
+            max_args_per_frame: 10,
+            max_args_len: 21_000,
+            last_resubscribe: None,
+        }
+    }
+}
+
+impl ResubscribeThrottle {
+    ///
+    /// Waits until `min_interval` has passed since the previous resubscription.
+    /// `backoff` is the delay the reconnect loop already waited, it counts towards the interval.
+    ///
+    pub async fn wait(&mut self, backoff: Duration) {
+        if let Some(last) = self.last_resubscribe {
+            let elapsed = last.elapsed() + backoff;
+            if elapsed < self.min_interval {
+                tokio::time::sleep(self.min_interval - elapsed).await;
+            }
+        }
+        self.last_resubscribe = Some(Instant::now());
+    }
+
+    ///
+    /// Splits topics into as few subscribe frames as the limits allow.
+    ///
+    pub fn batches(&self, topics: &[String]) -> Vec<Vec<String>> {
+        let mut batches: Vec<Vec<String>> = vec![];
+        let mut current: Vec<String> = vec![];
+        let mut current_len = 0;
+        for topic in topics {
+            // quotes and separating comma of the serialized args array
+            let topic_len = topic.len() + 3;
+            if !current.is_empty()
+                && (current.len() >= self.max_args_per_frame
+                    || current_len + topic_len > self.max_args_len)
+            {
+                batches.push(std::mem::take(&mut current));
+                current_len = 0;
+            }
+            current.push(topic.clone());
+            current_len += topic_len;
+        }
+        if !current.is_empty() {
+            batches.push(current);
+        }
+        batches
+    }
+
+    ///
+    /// Subscribe frames for the batches, ready to be sent as text messages.
+    /// Frame `i` carries `req_id` `subscribe-{i}`, which Bybit echoes in its response.
+    ///
+    pub fn frames(&self, topics: &[String]) -> Vec<String> {
+        self.batches(topics)
+            .into_iter()
+            .enumerate()
+            .map(|(i, args)| {
+                serde_json::json!({
+                    "op": "subscribe",
+                    "req_id": format!("subscribe-{}", i),
+                    "args": args,
+                })
+                .to_string()
+            })
+            .collect()
+    }
+}
+
+///
+/// Turns a live kline feed that may drop candles (e.g. while reconnecting) into a
+/// gapless stream of candles in chronological order.
+///
+/// `live` should yield confirmed candles. Whenever a candle starts more than one
+/// `interval_ms` after the previous one, `backfill(start, end)` is awaited for the
+/// missing range, typically `MarketHTTP::get_kline_range`, and the missed candles
+/// are emitted first. Candles at or before the last emitted one are dropped.
+/// A failed backfill is yielded as an error and the stream carries on with the
+/// live candle, leaving that gap unfilled.
+///
+pub fn gapless_klines<S, F, Fut>(
+    live: S,
+    interval_ms: u64,
+    backfill: F,
+) -> impl Stream<Item = Result<Kline>>
+where
+    S: Stream<Item = Result<Kline>> + Unpin,
+    F: FnMut(u64, u64) -> Fut,
+    Fut: Future<Output = Result<Vec<Kline>>>,
+{
+    struct State<S, F> {
+        live: S,
+        backfill: F,
+        pending: VecDeque<Kline>,
+        last_start: Option<u64>,
+    }
+
+    let state = State {
+        live,
+        backfill,
+        pending: VecDeque::new(),
+        last_start: None,
+    };
+
+    stream::unfold(state, move |mut state| async move {
+        loop {
+            if let Some(kline) = state.pending.pop_front() {
+                state.last_start = Some(kline.start_time);
+                return Some((Ok(kline), state));
+            }
+
+            let kline = match state.live.next().await? {
+                Ok(kline) => kline,
+                Err(e) => return Some((Err(e), state)),
+            };
+            let last = match state.last_start {
+                Some(last) if kline.start_time <= last => continue,
+                Some(last) => last,
+                None => {
+                    state.pending.push_back(kline);
+                    continue;
+                }
+            };
+
+            if kline.start_time > last + interval_ms {
+                let missed =
+                    (state.backfill)(last + interval_ms, kline.start_time - interval_ms).await;
+                match missed {
+                    Ok(mut missed) => {
+                        missed.sort_by_key(|k| k.start_time);
+                        missed.dedup_by_key(|k| k.start_time);
+                        state.pending.extend(
+                            missed
+                                .into_iter()
+                                .filter(|k| k.start_time > last && k.start_time < kline.start_time),
+                        );
+                    }
+                    Err(e) => {
+                        state.pending.push_back(kline);
+                        return Some((Err(e), state));
+                    }
+                }
+            }
+            state.pending.push_back(kline);
+        }
+    })
+}
+
+///
+/// An order or execution update, from the private topics or from a REST backfill.
+///
+#[derive(Debug, Clone)]
+pub enum PrivateEvent {
+    Order(Order),
+    Execution(Execution),
+}
+
+///
+/// Tracks the last order and execution seen on the private stream so that, after a
+/// reconnect, what happened during the disconnect can be fetched over REST and
+/// replayed as catch-up events.
+///
+/// Feed every pushed order / execution to `observe_order` / `observe_execution`,
+/// call `backfill` once the connection is back and process its events before the
+/// live ones. Executions already seen are dropped by exec id. Orders are replayed
+/// when their updatedTime is newer than the last seen one, from get_open_orders
+/// (still open) and get_order_history (filled or cancelled during the gap).
+///
+/// `window` caps how far back a backfill reaches, and is also the lookback used
+/// when nothing was observed yet. Defaults to one hour; Bybit serves at most seven
+/// days per query.
+///
+#[derive(Debug, Clone)]
+pub struct PrivateBackfill {
+    pub categories: Vec<String>,
+    pub window: Duration,
+    last_execution_time: Option<u64>,
+    last_order_time: Option<u64>,
+    seen_exec_ids: HashSet<String>,
+    seen_exec_order: VecDeque<String>,
+}
+
+impl PrivateBackfill {
+    /// Number of exec ids remembered for deduplication.
+    const SEEN_EXEC_IDS: usize = 1000;
+
+    pub fn new(categories: &[&str]) -> Self {
+        PrivateBackfill {
+            categories: categories.iter().map(|c| c.to_string()).collect(),
+            window: Duration::from_secs(3600),
+            last_execution_time: None,
+            last_order_time: None,
+            seen_exec_ids: HashSet::new(),
+            seen_exec_order: VecDeque::new(),
+        }
+    }
+
+    pub fn with_window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    pub fn observe_execution(&mut self, execution: &Execution) {
+        self.last_execution_time = self.last_execution_time.max(Some(execution.exec_time));
+        if self.seen_exec_ids.insert(execution.exec_id.clone()) {
+            self.seen_exec_order.push_back(execution.exec_id.clone());
+            if self.seen_exec_order.len() > Self::SEEN_EXEC_IDS {
+                if let Some(oldest) = self.seen_exec_order.pop_front() {
+                    self.seen_exec_ids.remove(&oldest);
+                }
+            }
+        }
+    }
+
+    pub fn observe_order(&mut self, order: &Order) {
+        self.last_order_time = self.last_order_time.max(Some(order.updated_time));
+    }
+
+    fn since(&self, last_seen: Option<u64>, now_ms: u64) -> u64 {
+        let oldest = now_ms.saturating_sub(self.window.as_millis() as u64);
+        last_seen.map_or(oldest, |last| last.max(oldest))
+    }
+
+    ///
+    /// Fetches the orders and executions missed since the last observed ones, oldest
+    /// first with executions before orders, and marks them as observed.
+    ///
+    /// Required args:
+    ///     trade: used for get_open_orders and get_order_history
+    ///     position: used for get_executions
+    ///     now_ms: current time in milliseconds, the end of the backfill range
+    ///
+    pub async fn backfill<T, P>(
+        &mut self,
+        trade: &T,
+        position: &P,
+        now_ms: u64,
+    ) -> Result<Vec<PrivateEvent>>
+    where
+        T: Trade + Sync,
+        P: Position + Sync,
+    {
+        let exec_since = self.since(self.last_execution_time, now_ms);
+        let order_since = self.since(self.last_order_time, now_ms);
+        let mut executions = vec![];
+        let mut orders: HashMap<String, Order> = HashMap::new();
+
+        for category in &self.categories {
+            let mut query = HashMap::new();
+            query.insert("category".to_string(), category.clone());
+
+            let mut range = query.clone();
+            range.insert("startTime".to_string(), exec_since.to_string());
+            range.insert("endTime".to_string(), now_ms.to_string());
+            range.insert("limit".to_string(), "100".to_string());
+            for item in response::all_pages(range, |q| position.get_executions(q)).await? {
+                executions.push(serde_json::from_value::<Execution>(item)?);
+            }
+
+            let mut open = query.clone();
+            open.insert("limit".to_string(), "50".to_string());
+            let mut history = open.clone();
+            history.insert("startTime".to_string(), order_since.to_string());
+            history.insert("endTime".to_string(), now_ms.to_string());
+            let fetched = response::all_pages(open, |q| trade.get_open_orders(q))
+                .await?
+                .into_iter()
+                .chain(response::all_pages(history, |q| trade.get_order_history(q)).await?);
+            for item in fetched {
+                let mut order = serde_json::from_value::<Order>(item)?;
+                if order.category.is_empty() {
+                    order.category = category.clone();
+                }
+                let newer = orders
+                    .get(&order.order_id)
+                    .map_or(true, |known| order.updated_time > known.updated_time);
+                if newer {
+                    orders.insert(order.order_id.clone(), order);
+                }
+            }
+        }
+
+        executions.retain(|exec| {
+            exec.exec_time >= exec_since && !self.seen_exec_ids.contains(&exec.exec_id)
+        });
+        executions.sort_by_key(|exec| exec.exec_time);
+        let last_order_time = self.last_order_time;
+        let mut orders: Vec<Order> = orders
+            .into_values()
+            .filter(|order| last_order_time.map_or(true, |last| order.updated_time > last))
+            .collect();
+        orders.sort_by_key(|order| order.updated_time);
+
+        for exec in &executions {
+            self.observe_execution(exec);
+        }
+        for order in &orders {
+            self.observe_order(order);
+        }
+        Ok(executions
+            .into_iter()
+            .map(PrivateEvent::Execution)
+            .chain(orders.into_iter().map(PrivateEvent::Order))
+            .collect())
+    }
+}
+
+/// Whether an order book push replaces the book or updates some levels of it.
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdateType {
+    Snapshot,
+    Delta,
+}
+
+///
+/// A push of the `orderbook.{depth}.{symbol}` topic.
+///
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, PartialEq)]
+pub struct OrderBookMessage {
+    pub topic: String,
+    #[serde(rename = "type")]
+    pub update_type: UpdateType,
+    pub ts: u64,
+    pub data: OrderBookData,
+    /// Matching engine time of the update.
+    #[serde(default)]
+    pub cts: u64,
+}
+
+/// A trade of the `publicTrade.{symbol}` topic, which uses one letter keys.
+#[derive(serde_derive::Serialize, serde_derive::Deserialize)]
+struct WsTrade {
+    #[serde(rename = "T")]
+    time: u64,
+    #[serde(rename = "s")]
+    symbol: String,
+    #[serde(rename = "S")]
+    side: Side,
+    #[serde(rename = "v", deserialize_with = "de::string_as_number")]
+    size: Number,
+    #[serde(rename = "p", deserialize_with = "de::string_as_number")]
+    price: Number,
+    #[serde(rename = "i")]
+    exec_id: String,
+    #[serde(rename = "BT", default)]
+    is_block_trade: bool,
+}
+
+impl From<WsTrade> for PublicTrade {
+    fn from(trade: WsTrade) -> Self {
+        PublicTrade {
+            exec_id: trade.exec_id,
+            symbol: trade.symbol,
+            price: trade.price,
+            size: trade.size,
+            side: trade.side,
+            time: trade.time,
+            is_block_trade: trade.is_block_trade,
+        }
+    }
+}
+
+impl From<PublicTrade> for WsTrade {
+    fn from(trade: PublicTrade) -> Self {
+        WsTrade {
+            time: trade.time,
+            symbol: trade.symbol,
+            side: trade.side,
+            size: trade.size,
+            price: trade.price,
+            exec_id: trade.exec_id,
+            is_block_trade: trade.is_block_trade,
+        }
+    }
+}
+
+#[derive(serde_derive::Serialize, serde_derive::Deserialize)]
+struct RawTradeMessage {
+    topic: String,
+    ts: u64,
+    data: Vec<WsTrade>,
+}
+
+///
+/// A push of the `publicTrade.{symbol}` topic, trades mapped onto the REST `PublicTrade`.
+///
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, PartialEq)]
+#[serde(from = "RawTradeMessage", into = "RawTradeMessage")]
+pub struct TradeMessage {
+    pub topic: String,
+    pub ts: u64,
+    pub data: Vec<PublicTrade>,
+}
+
+impl From<RawTradeMessage> for TradeMessage {
+    fn from(raw: RawTradeMessage) -> Self {
+        TradeMessage {
+            topic: raw.topic,
+            ts: raw.ts,
+            data: raw.data.into_iter().map(PublicTrade::from).collect(),
+        }
+    }
+}
+
+impl From<TradeMessage> for RawTradeMessage {
+    fn from(message: TradeMessage) -> Self {
+        RawTradeMessage {
+            topic: message.topic,
+            ts: message.ts,
+            data: message.data.into_iter().map(WsTrade::from).collect(),
+        }
+    }
+}
+
+///
+/// A candle of the `kline.{interval}.{symbol}` topic. Unlike REST klines it's an
+/// object, and it's pushed on every trade until `confirm` marks it closed.
+///
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WsKline {
+    pub start: u64,
+    pub end: u64,
+    pub interval: String,
+    #[serde(deserialize_with = "de::string_as_number")]
+    pub open: Number,
+    #[serde(deserialize_with = "de::string_as_number")]
+    pub close: Number,
+    #[serde(deserialize_with = "de::string_as_number")]
+    pub high: Number,
+    #[serde(deserialize_with = "de::string_as_number")]
+    pub low: Number,
+    #[serde(deserialize_with = "de::string_as_number")]
+    pub volume: Number,
+    #[serde(deserialize_with = "de::string_as_number")]
+    pub turnover: Number,
+    pub confirm: bool,
+    /// Time of the last update of the candle.
+    pub timestamp: u64,
+}
+
+impl From<WsKline> for Kline {
+    fn from(kline: WsKline) -> Self {
+        Kline {
+            start_time: kline.start,
+            open: kline.open,
+            high: kline.high,
+            low: kline.low,
+            close: kline.close,
+            volume: kline.volume,
+            turnover: kline.turnover,
+        }
+    }
+}
+
+///
+/// A push of the `kline.{interval}.{symbol}` topic.
+///
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, PartialEq)]
+pub struct KlineMessage {
+    pub topic: String,
+    #[serde(rename = "type")]
+    pub update_type: UpdateType,
+    pub ts: u64,
+    pub data: Vec<WsKline>,
+}
+
+///
+/// A decoded message of the public stream. Topics without a typed message yet are
+/// passed on as `Other`.
+///
+#[derive(Debug, Clone)]
+pub enum PublicMessage {
+    OrderBook(OrderBookMessage),
+    Trade(TradeMessage),
+    Kline(KlineMessage),
+    Other(Value),
+}
+
+///
+/// Decodes a text frame of the public stream. Control frames (subscribe acks, pongs)
+/// give None, a rejected subscription an error.
+///
+fn parse_public(text: &str) -> Option<Result<PublicMessage>> {
+    let value: Value = match serde_json::from_str(text) {
+        Ok(value) => value,
+        Err(e) => return Some(Err(e.into())),
+    };
+    if let Some(failure) = control_failure(&value) {
+        return failure.map(Err);
+    }
+    let topic = value
+        .get("topic")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    let message = if topic.starts_with("orderbook.") {
+        serde_json::from_value(value).map(PublicMessage::OrderBook)
+    } else if topic.starts_with("publicTrade.") {
+        serde_json::from_value(value).map(PublicMessage::Trade)
+    } else if topic.starts_with("kline.") {
+        serde_json::from_value(value).map(PublicMessage::Kline)
+    } else {
+        Ok(PublicMessage::Other(value))
+    };
+    Some(message.map_err(Into::into))
+}
+
+///
+/// Client of the public v5 stream of one category, e.g.
+/// `PublicWebSocket::new(Category::Linear, false).subscribe(&["orderbook.50.BTCUSDT"])`.
+///
+/// `stream()` connects in a background task and yields decoded messages. The task
+/// sends Bybit's `{"op":"ping"}` heartbeat every 20 seconds (the server drops silent
+/// connections), and when the socket drops it yields the error, reconnects with the
+/// `reconnect` backoff and resubscribes every topic, paced by `ResubscribeThrottle`.
+/// By default it reconnects indefinitely; the backoff restarts after each successful
+/// connection, and with `ReconnectPolicy::max_attempts` set the stream ends once that
+/// many reconnects in a row failed. A subscribe frame Bybit rejects with a transient
+/// error is resent with the `subscribe_retry` backoff; every rejection is also yielded
+/// as `AppError::Subscribe`. Dropping the stream closes the connection.
+///
+#[derive(Debug, Clone)]
+pub struct PublicWebSocket {
+    pub url: String,
+    pub topics: Vec<String>,
+    pub ping_interval: Duration,
+    pub reconnect: ReconnectPolicy,
+    pub subscribe_retry: SubscribeRetry,
+    pub throttle: ResubscribeThrottle,
+    pub proxy: Option<Proxy>,
+}
+
+impl PublicWebSocket {
+    /// `environment` is an Environment or a testnet flag.
+    pub fn new(category: Category, environment: impl Into<Environment>) -> Self {
+        PublicWebSocket::from_url(&environment.into().public_ws_url(category))
+    }
+
+    pub fn from_url(url: &str) -> Self {
+        PublicWebSocket {
+            url: url.to_string(),
+            topics: vec![],
+            ping_interval: Duration::from_secs(20),
+            reconnect: ReconnectPolicy::default(),
+            subscribe_retry: SubscribeRetry::default(),
+            throttle: ResubscribeThrottle::default(),
+            proxy: None,
+        }
+    }
+
+    pub fn subscribe(mut self, topics: &[&str]) -> Self {
+        self.topics
+            .extend(topics.iter().map(|topic| topic.to_string()));
+        self
+    }
+
+    pub fn with_ping_interval(mut self, ping_interval: Duration) -> Self {
+        self.ping_interval = ping_interval;
+        self
+    }
+
+    pub fn with_reconnect(mut self, reconnect: ReconnectPolicy) -> Self {
+        self.reconnect = reconnect;
+        self
+    }
+
+    pub fn with_subscribe_retry(mut self, subscribe_retry: SubscribeRetry) -> Self {
+        self.subscribe_retry = subscribe_retry;
+        self
+    }
+
+    /// Connects through `proxy`, HTTP CONNECT or SOCKS5.
+    pub fn with_proxy(mut self, proxy: Proxy) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    ///
+    /// Connects and yields the messages of the subscribed topics, see the type docs.
+    /// Must be called within a tokio runtime.
+    ///
+    pub fn stream(self) -> impl Stream<Item = Result<PublicMessage>> {
+        SocketTask {
+            url: self.url,
+            topics: self.topics,
+            ping_interval: self.ping_interval,
+            reconnect: self.reconnect,
+            subscribe_retry: self.subscribe_retry,
+            throttle: self.throttle,
+            proxy: self.proxy,
+            credentials: None,
+            clock_offset_ms: None,
+            parse: parse_public,
+        }
+        .spawn()
+    }
+
+    ///
+    /// Opens a connection of its own, with this client's settings but only the
+    /// `kline.{interval}.{symbol}` topic, and sends its candles as Kline to the
+    /// returned receiver. With `confirmed_only` the candles are sent once, when they
+    /// close, otherwise on every update. Connection errors are passed on like
+    /// `stream` does. Dropping the receiver closes the connection.
+    /// Must be called within a tokio runtime.
+    ///
+    pub fn subscribe_klines(
+        &self,
+        symbol: &str,
+        interval: Interval,
+        confirmed_only: bool,
+    ) -> mpsc::Receiver<Result<Kline>> {
+        let mut socket = self.clone();
+        socket.topics = vec![format!("kline.{}.{}", interval, symbol)];
+        let (tx, rx) = mpsc::channel(1024);
+        let mut messages = Box::pin(socket.stream());
+        tokio::spawn(async move {
+            while let Some(message) = messages.next().await {
+                let klines = match message {
+                    Ok(PublicMessage::Kline(msg)) => msg.data,
+                    Ok(_) => continue,
+                    Err(e) => {
+                        if tx.send(Err(e)).await.is_err() {
+                            return;
+                        }
+                        continue;
+                    }
+                };
+                for kline in klines {
+                    if confirmed_only && !kline.confirm {
+                        continue;
+                    }
+                    if tx.send(Ok(kline.into())).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+        rx
+    }
+}
+
+/// Topics PrivateWebSocket subscribes to unless told otherwise.
+pub const PRIVATE_TOPICS: [&str; 4] = ["order", "position", "execution", "wallet"];
+
+///
+/// A push of the private `position` topic, same envelope as `ExecutionMessage`.
+///
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PositionMessage {
+    pub topic: String,
+    #[serde(default)]
+    pub id: String,
+    pub creation_time: u64,
+    pub data: Vec<PositionInfo>,
+}
+
+///
+/// A push of the private `wallet` topic, one entry per account type.
+///
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WalletMessage {
+    pub topic: String,
+    #[serde(default)]
+    pub id: String,
+    pub creation_time: u64,
+    pub data: Vec<Value>,
+}
+
+///
+/// A decoded message of the private stream, see PublicMessage.
+///
+#[derive(Debug, Clone)]
+pub enum PrivateMessage {
+    Order(OrderMessage),
+    Execution(ExecutionMessage),
+    Position(PositionMessage),
+    Wallet(WalletMessage),
+    Other(Value),
+}
+
+fn parse_private(text: &str) -> Option<Result<PrivateMessage>> {
+    let value: Value = match serde_json::from_str(text) {
+        Ok(value) => value,
+        Err(e) => return Some(Err(e.into())),
+    };
+    if let Some(failure) = control_failure(&value) {
+        return failure.map(Err);
+    }
+    // Topics may carry a category suffix, e.g. `order.linear`.
+    let topic = value
+        .get("topic")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    let message = match topic.split('.').next().unwrap_or_default() {
+        "order" => serde_json::from_value(value).map(PrivateMessage::Order),
+        "execution" => serde_json::from_value(value).map(PrivateMessage::Execution),
+        "position" => serde_json::from_value(value).map(PrivateMessage::Position),
+        "wallet" => serde_json::from_value(value).map(PrivateMessage::Wallet),
+        _ => Ok(PrivateMessage::Other(value)),
+    };
+    Some(message.map_err(Into::into))
+}
+
+///
+/// The private stream split per topic, fed by a single connection. Connection errors
+/// go to `errors`; a topic whose receiver is dropped is skipped, the connection closes
+/// once all receivers are gone.
+///
+pub struct PrivateStreams {
+    pub orders: mpsc::Receiver<OrderMessage>,
+    pub executions: mpsc::Receiver<ExecutionMessage>,
+    pub positions: mpsc::Receiver<PositionMessage>,
+    pub wallet: mpsc::Receiver<WalletMessage>,
+    pub errors: mpsc::Receiver<super::Error>,
+}
+
+///
+/// Client of the private v5 stream. Logs in with `{"op":"auth"}`, signing
+/// `GET/realtime{expires}`, then subscribes to `PRIVATE_TOPICS`:
+/// `PrivateWebSocket::new(key, secret, false).stream()`.
+///
+/// Keepalive, reconnects and resubscription work as in PublicWebSocket; every
+/// reconnect logs in again with a fresh `expires`, so a session the server expired is
+/// simply re-established. A rejected login (wrong key or secret, clock drift, see
+/// with_clock_of) is yielded as `AppError::WebSocketAuth` and ends the stream, as
+/// retrying it can't succeed. Messages pushed while disconnected are lost, feed
+/// `PrivateBackfill` from the stream and call its `backfill` after a reconnect to
+/// recover them.
+///
+#[derive(Debug, Clone)]
+pub struct PrivateWebSocket {
+    pub url: String,
+    pub topics: Vec<String>,
+    pub ping_interval: Duration,
+    pub reconnect: ReconnectPolicy,
+    pub subscribe_retry: SubscribeRetry,
+    pub throttle: ResubscribeThrottle,
+    pub proxy: Option<Proxy>,
+    api_key: String,
+    api_secret: String,
+    clock_offset_ms: Option<Arc<AtomicI64>>,
+}
+
+impl PrivateWebSocket {
+    /// `environment` is an Environment or a testnet flag.
+    pub fn new(api_key: &str, api_secret: &str, environment: impl Into<Environment>) -> Self {
+        PrivateWebSocket::from_url(environment.into().private_ws_url(), api_key, api_secret)
+    }
+
+    pub fn from_url(url: &str, api_key: &str, api_secret: &str) -> Self {
+        PrivateWebSocket {
+            url: url.to_string(),
+            topics: PRIVATE_TOPICS
+                .iter()
+                .map(|topic| topic.to_string())
+                .collect(),
+            ping_interval: Duration::from_secs(20),
+            reconnect: ReconnectPolicy::default(),
+            subscribe_retry: SubscribeRetry::default(),
+            throttle: ResubscribeThrottle::default(),
+            proxy: None,
+            api_key: api_key.to_string(),
+            api_secret: api_secret.to_string(),
+            clock_offset_ms: None,
+        }
+    }
+
+    ///
+    /// Signs the login with the clock offset `http_manager.sync_clock` measures, as
+    /// REST requests already are, so a drifting local clock doesn't get it rejected.
+    /// A later sync_clock applies from the next login on.
+    ///
+    pub fn with_clock_of(mut self, http_manager: &HttpManager) -> Self {
+        self.clock_offset_ms = Some(http_manager.shared_clock_offset());
+        self
+    }
+
+    /// Replaces the default topics, e.g. `&["order.linear", "execution.linear"]`.
+    pub fn with_topics(mut self, topics: &[&str]) -> Self {
+        self.topics = topics.iter().map(|topic| topic.to_string()).collect();
+        self
+    }
+
+    pub fn with_ping_interval(mut self, ping_interval: Duration) -> Self {
+        self.ping_interval = ping_interval;
+        self
+    }
+
+    pub fn with_reconnect(mut self, reconnect: ReconnectPolicy) -> Self {
+        self.reconnect = reconnect;
+        self
+    }
+
+    pub fn with_subscribe_retry(mut self, subscribe_retry: SubscribeRetry) -> Self {
+        self.subscribe_retry = subscribe_retry;
+        self
+    }
+
+    /// Connects through `proxy`, HTTP CONNECT or SOCKS5.
+    pub fn with_proxy(mut self, proxy: Proxy) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    ///
+    /// Connects and yields the messages of all subscribed topics.
+    /// Must be called within a tokio runtime.
+    ///
+    pub fn stream(self) -> impl Stream<Item = Result<PrivateMessage>> {
+        SocketTask {
+            url: self.url,
+            topics: self.topics,
+            ping_interval: self.ping_interval,
+            reconnect: self.reconnect,
+            subscribe_retry: self.subscribe_retry,
+            throttle: self.throttle,
+            proxy: self.proxy,
+            credentials: Some((self.api_key, self.api_secret)),
+            clock_offset_ms: self.clock_offset_ms,
+            parse: parse_private,
+        }
+        .spawn()
+    }
+
+    ///
+    /// Like `stream`, but with one typed receiver per topic.
+    /// Must be called within a tokio runtime.
+    ///
+    pub fn split(self) -> PrivateStreams {
+        let (orders_tx, orders) = mpsc::channel(1024);
+        let (executions_tx, executions) = mpsc::channel(1024);
+        let (positions_tx, positions) = mpsc::channel(1024);
+        let (wallet_tx, wallet) = mpsc::channel(1024);
+        let (errors_tx, errors) = mpsc::channel(64);
+        let mut messages = Box::pin(self.stream());
+        tokio::spawn(async move {
+            loop {
+                let all_closed = async {
+                    tokio::join!(
+                        orders_tx.closed(),
+                        executions_tx.closed(),
+                        positions_tx.closed(),
+                        wallet_tx.closed(),
+                        errors_tx.closed(),
+                    )
+                };
+                // Ends the task, and so the connection, even while nothing is pushed.
+                let message = tokio::select! {
+                    message = messages.next() => match message {
+                        Some(message) => message,
+                        None => return,
+                    },
+                    _ = all_closed => return,
+                };
+                // A failed send only means that topic's receiver was dropped.
+                let _ = match message {
+                    Ok(PrivateMessage::Order(msg)) => orders_tx.send(msg).await.ok(),
+                    Ok(PrivateMessage::Execution(msg)) => executions_tx.send(msg).await.ok(),
+                    Ok(PrivateMessage::Position(msg)) => positions_tx.send(msg).await.ok(),
+                    Ok(PrivateMessage::Wallet(msg)) => wallet_tx.send(msg).await.ok(),
+                    Ok(PrivateMessage::Other(_)) => None,
+                    Err(e) => errors_tx.send(e).await.ok(),
+                };
+            }
+        });
+        PrivateStreams {
+            orders,
+            executions,
+            positions,
+            wallet,
+            errors,
+        }
+    }
+}
+
+///
+/// Index of the subscribe frame a transient NAK refers to, from the `req_id` set by
+/// `ResubscribeThrottle::frames`.
+///
+fn transient_nak(text: &str) -> Option<usize> {
+    if !text.contains("\"subscribe\"") {
+        return None;
+    }
+    let value: Value = serde_json::from_str(text).ok()?;
+    match control_failure(&value)? {
+        Some(AppError::Subscribe(SubscribeFailure::Transient(_))) => value
+            .get("req_id")?
+            .as_str()?
+            .strip_prefix("subscribe-")?
+            .parse()
+            .ok(),
+        _ => None,
+    }
+}
+
+/// Resolves to `frame` after `delay`.
+async fn resend_after(delay: Duration, frame: usize) -> usize {
+    tokio::time::sleep(delay).await;
+    frame
+}
+
+///
+/// Handles the `op` frames (acks, pongs) shared by both streams: None when `value` is a
+/// regular push, Some(None) for an ack to skip, Some(Some(..)) for a rejected subscribe.
+///
+fn control_failure(value: &Value) -> Option<Option<super::Error>> {
+    let op = value.get("op")?.as_str().unwrap_or_default();
+    if op == "subscribe" && value.get("success").and_then(Value::as_bool) == Some(false) {
+        let ret_msg = value
+            .get("ret_msg")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        return Some(Some(classify_subscribe_failure(ret_msg).into()));
+    }
+    Some(None)
+}
+
+///
+/// The connection loop behind PublicWebSocket and PrivateWebSocket.
+///
+struct SocketTask<T> {
+    url: String,
+    topics: Vec<String>,
+    ping_interval: Duration,
+    reconnect: ReconnectPolicy,
+    subscribe_retry: SubscribeRetry,
+    throttle: ResubscribeThrottle,
+    proxy: Option<Proxy>,
+    /// Api key and secret to log in with, private stream only.
+    credentials: Option<(String, String)>,
+    /// Applied to the login's `expires`, see PrivateWebSocket::with_clock_of.
+    clock_offset_ms: Option<Arc<AtomicI64>>,
+    parse: fn(&str) -> Option<Result<T>>,
+}
+
+impl<T: Send + 'static> SocketTask<T> {
+    fn spawn(self) -> impl Stream<Item = Result<T>> {
+        let (tx, rx) = mpsc::channel(1024);
+        tokio::spawn(self.run(tx));
+        stream::unfold(rx, |mut rx| async move {
+            let message = rx.recv().await?;
+            Some((message, rx))
+        })
+    }
+
+    async fn run(mut self, tx: mpsc::Sender<Result<T>>) {
+        let mut attempt = 0;
+        let mut backoff = Duration::ZERO;
+        loop {
+            self.throttle.wait(backoff).await;
+            let mut connected = false;
+            let error = match self.session(&tx, &mut connected).await {
+                Ok(()) => return,
+                Err(e) => e,
+            };
+            let fatal = matches!(error, AppError::WebSocketAuth(_));
+            if tx.send(Err(error)).await.is_err() || fatal {
+                return;
+            }
+            if connected {
+                attempt = 0;
+            }
+            backoff = match self.reconnect.delay(attempt) {
+                Some(delay) => delay,
+                None => return,
+            };
+            tokio::time::sleep(backoff).await;
+            attempt = attempt.saturating_add(1);
+        }
+    }
+
+    ///
+    /// One connection: log in if needed, subscribe, then forward messages until the
+    /// socket fails. Returns Ok once the receiving side is gone.
+    ///
+    async fn session(&self, tx: &mpsc::Sender<Result<T>>, connected: &mut bool) -> Result<()> {
+        let (socket, _) = match &self.proxy {
+            Some(proxy) => {
+                let tunnel = proxy.connect(&self.url).await?;
+                tokio_tungstenite::client_async_tls(self.url.as_str(), tunnel).await?
+            }
+            None => tokio_tungstenite::connect_async(self.url.as_str()).await?,
+        };
+        let (mut write, mut read) = socket.split();
+
+        if let Some((api_key, api_secret)) = &self.credentials {
+            let offset = self
+                .clock_offset_ms
+                .as_ref()
+                .map_or(0, |offset| offset.load(Ordering::Relaxed));
+            let expires = utils::generate_timestamp_with_offset(offset)? + 10_000;
+            let signature =
+                utils::sign_query_string(&format!("GET/realtime{}", expires), api_secret)?;
+            let frame = json!({ "op": "auth", "args": [api_key, expires, signature] });
+            write.send(Message::Text(frame.to_string().into())).await?;
+            let ack = tokio::time::timeout(Duration::from_secs(10), async {
+                while let Some(message) = read.next().await {
+                    if let Message::Text(text) = message? {
+                        let value: Value = serde_json::from_str(text.as_str())?;
+                        if value.get("op").and_then(Value::as_str) == Some("auth") {
+                            return Ok(value);
+                        }
+                    }
+                }
+                Err::<Value, super::Error>(AppError::WebSocketClosed(
+                    "before the auth response".to_string(),
+                ))
+            })
+            .await
+            .map_err(|_| AppError::WebSocketAuth("no auth response within 10s".to_string()))??;
+            if ack.get("success").and_then(Value::as_bool) != Some(true) {
+                let ret_msg = ack
+                    .get("ret_msg")
+                    .and_then(Value::as_str)
+                    .unwrap_or("rejected");
+                return Err(AppError::WebSocketAuth(ret_msg.to_string()));
+            }
+        }
+
+        let frames = self.throttle.frames(&self.topics);
+        for frame in &frames {
+            write.send(Message::Text(frame.clone().into())).await?;
+        }
+        *connected = true;
+        let mut sent = vec![1; frames.len()];
+        let mut resends = FuturesUnordered::new();
+
+        let mut ping = tokio::time::interval(self.ping_interval);
+        ping.tick().await;
+        loop {
+            tokio::select! {
+                _ = tx.closed() => return Ok(()),
+                _ = ping.tick() => {
+                    let frame = json!({ "op": "ping" }).to_string();
+                    write.send(Message::Text(frame.into())).await?;
+                }
+                Some(i) = resends.next(), if !resends.is_empty() => {
+                    let i: usize = i; // the futures are only pushed further down
+                    sent[i] += 1;
+                    write.send(Message::Text(frames[i].clone().into())).await?;
+                }
+                message = read.next() => match message {
+                    Some(Ok(Message::Text(text))) => {
+                        if let Some(i) = transient_nak(text.as_str()).filter(|i| *i < frames.len()) {
+                            if let Some(delay) = self.subscribe_retry.delay(sent[i] - 1) {
+                                resends.push(resend_after(delay, i));
+                            }
+                        }
+                        if let Some(message) = (self.parse)(text.as_str()) {
+                            if tx.send(message).await.is_err() {
+                                return Ok(());
+                            }
+                        }
+                    }
+                    Some(Ok(Message::Ping(payload))) => write.send(Message::Pong(payload)).await?,
+                    Some(Ok(Message::Close(_))) | None => {
+                        return Err(AppError::WebSocketClosed("by the server".to_string()));
+                    }
+                    Some(Ok(_)) => {}
+                    Some(Err(e)) => return Err(e.into()),
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transient_subscribe_naks_point_at_their_frame() {
+        let throttle = ResubscribeThrottle::default();
+        let frames = throttle.frames(&["orderbook.50.BTCUSDT".to_string()]);
+        let frame: Value = serde_json::from_str(&frames[0]).unwrap();
+        assert_eq!(frame["req_id"], "subscribe-0");
+
+        let transient = r#"{"success":false,"ret_msg":"system busy","conn_id":"c1","req_id":"subscribe-0","op":"subscribe"}"#;
+        assert_eq!(transient_nak(transient), Some(0));
+        let permanent = r#"{"success":false,"ret_msg":"error:handler not found,topic:orderbook.50.BTCUSDTX","conn_id":"c1","req_id":"subscribe-1","op":"subscribe"}"#;
+        assert_eq!(transient_nak(permanent), None);
+        let ack = r#"{"success":true,"ret_msg":"","conn_id":"c1","req_id":"subscribe-0","op":"subscribe"}"#;
+        assert_eq!(transient_nak(ack), None);
+    }
+
+    #[test]
+    fn the_login_follows_the_clock_offset_of_the_http_manager() {
+        let manager = HttpManager::new("key".to_string(), "secret".to_string(), false);
+        let socket = PrivateWebSocket::new("key", "secret", false).with_clock_of(&manager);
+        manager
+            .shared_clock_offset()
+            .store(-2_500, Ordering::Relaxed);
+        let offset = socket.clock_offset_ms.unwrap().load(Ordering::Relaxed);
+        assert_eq!(offset, manager.clock_offset_ms());
+        assert_eq!(offset, -2_500);
+    }
+
+    #[tokio::test]
+    async fn split_closes_the_connection_once_every_receiver_is_dropped() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}/v5/private", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut socket = tokio_tungstenite::accept_async(socket).await.unwrap();
+            // auth request, then the subscribe frame once logged in
+            socket.next().await.unwrap().unwrap();
+            let ack = json!({ "success": true, "ret_msg": "", "op": "auth", "conn_id": "c1" });
+            socket
+                .send(Message::Text(ack.to_string().into()))
+                .await
+                .unwrap();
+            socket.next().await.unwrap().unwrap();
+            // quiet connection: what comes next is the client closing it
+            loop {
+                match socket.next().await {
+                    Some(Ok(Message::Text(_))) | Some(Ok(Message::Ping(_))) => {}
+                    _ => return,
+                }
+            }
+        });
+
+        let streams = PrivateWebSocket::from_url(&url, "key", "secret").split();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        drop(streams);
+        tokio::time::timeout(Duration::from_secs(2), server)
+            .await
+            .expect("connection still open")
+            .unwrap();
+    }
+
+    #[test]
+    fn reconnect_backoff_is_capped_and_unbounded_by_default() {
+        let policy = ReconnectPolicy::default();
+        assert_eq!(policy.delay(0), Some(Duration::from_millis(500)));
+        assert_eq!(policy.delay(3), Some(Duration::from_secs(4)));
+        assert_eq!(policy.delay(10), Some(Duration::from_secs(30)));
+        assert_eq!(policy.delay(u32::MAX), Some(Duration::from_secs(30)));
+
+        let limited = ReconnectPolicy {
+            max_attempts: Some(2),
+            ..ReconnectPolicy::default()
+        };
+        assert!(limited.delay(1).is_some());
+        assert_eq!(limited.delay(2), None);
+    }
+}
@@ -0,0 +1,95 @@
+///
+/// Documented maximum of the `limit` parameter per endpoint path.
+/// Some endpoints cap differently per category, hence the category argument.
+/// Returns None for endpoints without a `limit` parameter or without a documented cap.
+///
+pub fn max_limit(path: &str, category: Option<&str>) -> Option<u64> {
+    let max = match path {
+        "/v5/market/kline"
+        | "/v5/market/mark-price-kline"
+        | "/v5/market/index-price-kline"
+        | "/v5/market/premium-index-price-kline"
+        | "/v5/market/instruments-info" => 1000,
+        "/v5/market/orderbook" => match category {
+            Some("spot") => 200,
+            Some("option") => 25,
+            _ => 500,
+        },
+        "/v5/market/recent-trade" => match category {
+            Some("spot") => 60,
+            _ => 1000,
+        },
+        "/v5/market/funding/history"
+        | "/v5/market/open-interest"
+        | "/v5/market/delivery-price"
+        | "/v5/position/list" => 200,
+        "/v5/market/account-ratio" => 500,
+        "/v5/order/realtime"
+        | "/v5/order/history"
+        | "/v5/account/transaction-log"
+        | "/v5/account/borrow-history"
+        | "/v5/asset/delivery-record"
+        | "/v5/asset/settlement-record"
+        | "/v5/asset/exchange/order-record"
+        | "/v5/asset/transfer/query-inter-transfer-list"
+        | "/v5/asset/transfer/query-universal-transfer-list"
+        | "/v5/asset/deposit/query-record"
+        | "/v5/asset/deposit/query-sub-member-record"
+        | "/v5/asset/deposit/query-internal-record"
+        | "/v5/asset/withdraw/query-record" => 50,
+        "/v5/execution/list" | "/v5/position/closed-pnl" | "/v5/user/submembers" => 100,
+        _ => return None,
+    };
+    Some(max)
+}
+
+///
+/// Rate limit group of an endpoint path, the key of RateLimiterConfig. Endpoints of a
+/// group share one per-UID budget on Bybit's side.
+///
+pub fn rate_limit_group(path: &str) -> &'static str {
+    match path {
+        "/v5/order/create" | "/v5/order/amend" | "/v5/order/cancel" | "/v5/order/cancel-all" => {
+            "order"
+        }
+        "/v5/order/create-batch" | "/v5/order/amend-batch" | "/v5/order/cancel-batch" => {
+            "order_batch"
+        }
+        "/v5/order/realtime" | "/v5/order/history" | "/v5/execution/list" => "order_query",
+        "/v5/position/list" | "/v5/position/closed-pnl" => "position_query",
+        path if path.starts_with("/v5/position/") => "position",
+        path if path.starts_with("/v5/account/") => "account",
+        path if path.starts_with("/v5/asset/") => "asset",
+        path if path.starts_with("/v5/market/") => "market",
+        _ => "other",
+    }
+}
+
+///
+/// Documented requests per second of a rate limit group, see rate_limit_group.
+/// The market group is limited per IP (600 per 5 s) rather than per UID.
+///
+pub fn default_rate_limit(group: &str) -> u32 {
+    match group {
+        "order" | "order_batch" | "position" | "other" => 10,
+        "order_query" | "position_query" | "account" => 50,
+        "asset" => 5,
+        "market" => 120,
+        _ => 10,
+    }
+}
+
+///
+/// Longest startTime to endTime span, in milliseconds, an endpoint accepts.
+/// Returns None for endpoints without a documented cap.
+///
+pub fn max_time_window(path: &str) -> Option<u64> {
+    const DAY: u64 = 24 * 60 * 60 * 1000;
+    match path {
+        "/v5/execution/list"
+        | "/v5/position/closed-pnl"
+        | "/v5/order/history"
+        | "/v5/account/transaction-log" => Some(7 * DAY),
+        _ => None,
+    }
+}
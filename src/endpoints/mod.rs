@@ -1,3 +1,4 @@
+pub mod limits;
 pub mod v5account;
 pub mod v5asset;
 pub mod v5broker;
@@ -7,3 +8,63 @@ pub mod v5spot_leverage_token;
 pub mod v5spot_margin_trade;
 pub mod v5trade;
 pub mod v5user;
+
+use crate::errors::app_error::AppError;
+
+///
+/// Any REST endpoint of the crate. Every endpoint enum displays as its path and parses
+/// back from it, and so does this one across all of them:
+///
+/// ```text
+/// let endpoint: Endpoint = "/v5/market/time".parse()?;
+/// assert_eq!(endpoint, Endpoint::Market(v5market::MarketEnum::GetServerTime));
+/// ```
+///
+/// which lets a mock server or a log processor dispatch on the endpoint rather than
+/// compare strings. Unknown paths fail with `AppError::InvalidParameter`.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Endpoint {
+    Account(v5account::Account),
+    Asset(v5asset::Asset),
+    Broker(v5broker::Broker),
+    Market(v5market::MarketEnum),
+    Position(v5position::Position),
+    SpotLeverageToken(v5spot_leverage_token::SpotLeverageToken),
+    SpotMarginTrade(v5spot_margin_trade::SpotMarginTrade),
+    Trade(v5trade::Trade),
+    User(v5user::User),
+}
+
+impl std::fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Endpoint::Account(endpoint) => endpoint.fmt(f),
+            Endpoint::Asset(endpoint) => endpoint.fmt(f),
+            Endpoint::Broker(endpoint) => endpoint.fmt(f),
+            Endpoint::Market(endpoint) => endpoint.fmt(f),
+            Endpoint::Position(endpoint) => endpoint.fmt(f),
+            Endpoint::SpotLeverageToken(endpoint) => endpoint.fmt(f),
+            Endpoint::SpotMarginTrade(endpoint) => endpoint.fmt(f),
+            Endpoint::Trade(endpoint) => endpoint.fmt(f),
+            Endpoint::User(endpoint) => endpoint.fmt(f),
+        }
+    }
+}
+
+impl std::str::FromStr for Endpoint {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        s.parse()
+            .map(Endpoint::Account)
+            .or_else(|_| s.parse().map(Endpoint::Asset))
+            .or_else(|_| s.parse().map(Endpoint::Broker))
+            .or_else(|_| s.parse().map(Endpoint::Market))
+            .or_else(|_| s.parse().map(Endpoint::Position))
+            .or_else(|_| s.parse().map(Endpoint::SpotLeverageToken))
+            .or_else(|_| s.parse().map(Endpoint::SpotMarginTrade))
+            .or_else(|_| s.parse().map(Endpoint::Trade))
+            .or_else(|_| s.parse().map(Endpoint::User))
+    }
+}
@@ -1,3 +1,6 @@
+use crate::errors::app_error::AppError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Account {
     GetWalletBalance,
     UpgradeToUnifiedAccount,
@@ -8,11 +11,30 @@ pub enum Account {
     GetAccountInfo,
     GetTransactionLog,
     SetMarginMode,
+    SetHedgingMode,
     SetMMP,
     ResetMMP,
     GetMMPState,
 }
 
+impl Account {
+    pub const ALL: [Account; 13] = [
+        Account::GetWalletBalance,
+        Account::UpgradeToUnifiedAccount,
+        Account::GetBorrowHistory,
+        Account::GetCollateralInfo,
+        Account::GetCoinGreeks,
+        Account::GetFeeRate,
+        Account::GetAccountInfo,
+        Account::GetTransactionLog,
+        Account::SetMarginMode,
+        Account::SetHedgingMode,
+        Account::SetMMP,
+        Account::ResetMMP,
+        Account::GetMMPState,
+    ];
+}
+
 impl std::fmt::Display for Account {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
@@ -25,9 +47,22 @@ impl std::fmt::Display for Account {
             Account::GetAccountInfo => write!(f, "/v5/account/info"),
             Account::GetTransactionLog => write!(f, "/v5/account/transaction-log"),
             Account::SetMarginMode => write!(f, "/v5/account/set-margin-mode"),
+            Account::SetHedgingMode => write!(f, "/v5/account/set-hedging-mode"),
             Account::SetMMP => write!(f, "/v5/account/mmp-modify"),
             Account::ResetMMP => write!(f, "/v5/account/mmp-reset"),
             Account::GetMMPState => write!(f, "/v5/account/mmp-state"),
         }
     }
 }
+
+impl std::str::FromStr for Account {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Account::ALL
+            .iter()
+            .find(|endpoint| endpoint.to_string() == s)
+            .copied()
+            .ok_or_else(|| AppError::InvalidParameter(format!("unknown endpoint {}", s)))
+    }
+}
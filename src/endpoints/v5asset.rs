@@ -1,3 +1,6 @@
+use crate::errors::app_error::AppError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Asset {
     GetCoinExchangeRecords,
     GetOptionDeliveryRecord,
@@ -26,6 +29,36 @@ pub enum Asset {
     CancelWithdrawal,
 }
 
+impl Asset {
+    pub const ALL: [Asset; 25] = [
+        Asset::GetCoinExchangeRecords,
+        Asset::GetOptionDeliveryRecord,
+        Asset::GetUsdcContractSettlement,
+        Asset::GetSpotAssetInfo,
+        Asset::GetAllCoinsBalance,
+        Asset::GetSingleCoinBalance,
+        Asset::GetTransferableCoin,
+        Asset::CreateInternalTransfer,
+        Asset::GetInternalTransferRecords,
+        Asset::GetSubUid,
+        Asset::EnableUtForSubUid,
+        Asset::CreateUniversalTransfer,
+        Asset::GetUniversalTransferRecords,
+        Asset::GetAllowedDepositCoinInfo,
+        Asset::SetDepositAccount,
+        Asset::GetDepositRecords,
+        Asset::GetSubAccountDepositRecords,
+        Asset::GetInternalDepositRecords,
+        Asset::GetMasterDepositAddress,
+        Asset::GetSubDepositAddress,
+        Asset::GetCoinInfo,
+        Asset::GetWithdrawalRecords,
+        Asset::GetWithdrawableAmount,
+        Asset::Withdraw,
+        Asset::CancelWithdrawal,
+    ];
+}
+
 impl std::fmt::Display for Asset {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
@@ -69,3 +102,15 @@ impl std::fmt::Display for Asset {
         }
     }
 }
+
+impl std::str::FromStr for Asset {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Asset::ALL
+            .iter()
+            .find(|endpoint| endpoint.to_string() == s)
+            .copied()
+            .ok_or_else(|| AppError::InvalidParameter(format!("unknown endpoint {}", s)))
+    }
+}
@@ -1,7 +1,14 @@
+use crate::errors::app_error::AppError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Broker {
     GetBrokerEarnings,
 }
 
+impl Broker {
+    pub const ALL: [Broker; 1] = [Broker::GetBrokerEarnings];
+}
+
 impl std::fmt::Display for Broker {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
@@ -9,3 +16,15 @@ impl std::fmt::Display for Broker {
         }
     }
 }
+
+impl std::str::FromStr for Broker {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Broker::ALL
+            .iter()
+            .find(|endpoint| endpoint.to_string() == s)
+            .copied()
+            .ok_or_else(|| AppError::InvalidParameter(format!("unknown endpoint {}", s)))
+    }
+}
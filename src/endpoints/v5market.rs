@@ -1,3 +1,6 @@
+use crate::errors::app_error::AppError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum MarketEnum {
     GetKline,
     GetMarkPriceKline,
@@ -9,10 +12,33 @@ pub enum MarketEnum {
     GetFundingRateHistory,
     GetPublicTradingHistory,
     GetOpenInterest,
+    GetLongShortRatio,
     GetHistoricalVolatility,
     GetInsurance,
     GetRiskLimit,
     GetOptionDeliveryPrice,
+    GetServerTime,
+}
+
+impl MarketEnum {
+    pub const ALL: [MarketEnum; 16] = [
+        MarketEnum::GetKline,
+        MarketEnum::GetMarkPriceKline,
+        MarketEnum::GetIndexPriceKline,
+        MarketEnum::GetPremiumIndexPriceKline,
+        MarketEnum::GetInstrumentsInfo,
+        MarketEnum::GetOrderbook,
+        MarketEnum::GetTickers,
+        MarketEnum::GetFundingRateHistory,
+        MarketEnum::GetPublicTradingHistory,
+        MarketEnum::GetOpenInterest,
+        MarketEnum::GetLongShortRatio,
+        MarketEnum::GetHistoricalVolatility,
+        MarketEnum::GetInsurance,
+        MarketEnum::GetRiskLimit,
+        MarketEnum::GetOptionDeliveryPrice,
+        MarketEnum::GetServerTime,
+    ];
 }
 
 impl std::fmt::Display for MarketEnum {
@@ -30,11 +56,24 @@ impl std::fmt::Display for MarketEnum {
             MarketEnum::GetFundingRateHistory => write!(f, "/v5/market/funding/history"),
             MarketEnum::GetPublicTradingHistory => write!(f, "/v5/market/recent-trade"),
             MarketEnum::GetOpenInterest => write!(f, "/v5/market/open-interest"),
+            MarketEnum::GetLongShortRatio => write!(f, "/v5/market/account-ratio"),
             MarketEnum::GetHistoricalVolatility => write!(f, "/v5/market/historical-volatility"),
-            MarketEnum::GetInstrumentsInfo => write!(f, "/v5/market/insurance"),
+            MarketEnum::GetInsurance => write!(f, "/v5/market/insurance"),
             MarketEnum::GetRiskLimit => write!(f, "/v5/market/risk-limit"),
             MarketEnum::GetOptionDeliveryPrice => write!(f, "/v5/market/delivery-price"),
-            MarketEnum::GetInsurance => todo!(),
+            MarketEnum::GetServerTime => write!(f, "/v5/market/time"),
         }
     }
 }
+
+impl std::str::FromStr for MarketEnum {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        MarketEnum::ALL
+            .iter()
+            .find(|endpoint| endpoint.to_string() == s)
+            .copied()
+            .ok_or_else(|| AppError::InvalidParameter(format!("unknown endpoint {}", s)))
+    }
+}
@@ -1,3 +1,6 @@
+use crate::errors::app_error::AppError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Position {
     GetPositions,
     SetLeverage,
@@ -11,6 +14,21 @@ pub enum Position {
     GetClosedPnl,
 }
 
+impl Position {
+    pub const ALL: [Position; 10] = [
+        Position::GetPositions,
+        Position::SetLeverage,
+        Position::SwitchMarginMode,
+        Position::SetTpSlMode,
+        Position::SwitchPositionMode,
+        Position::SetRiskLimit,
+        Position::SetTradingStop,
+        Position::SetAutoAddMargin,
+        Position::GetExecutions,
+        Position::GetClosedPnl,
+    ];
+}
+
 impl std::fmt::Display for Position {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
@@ -27,3 +45,15 @@ impl std::fmt::Display for Position {
         }
     }
 }
+
+impl std::str::FromStr for Position {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Position::ALL
+            .iter()
+            .find(|endpoint| endpoint.to_string() == s)
+            .copied()
+            .ok_or_else(|| AppError::InvalidParameter(format!("unknown endpoint {}", s)))
+    }
+}
@@ -1,3 +1,6 @@
+use crate::errors::app_error::AppError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum SpotLeverageToken {
     GetLeveragedTokenInfo,
     GetLeveragedTokenMarket,
@@ -6,6 +9,16 @@ pub enum SpotLeverageToken {
     GetPurchaseRedemptionRecords,
 }
 
+impl SpotLeverageToken {
+    pub const ALL: [SpotLeverageToken; 5] = [
+        SpotLeverageToken::GetLeveragedTokenInfo,
+        SpotLeverageToken::GetLeveragedTokenMarket,
+        SpotLeverageToken::Purchase,
+        SpotLeverageToken::Redeem,
+        SpotLeverageToken::GetPurchaseRedemptionRecords,
+    ];
+}
+
 impl std::fmt::Display for SpotLeverageToken {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
@@ -21,3 +34,15 @@ impl std::fmt::Display for SpotLeverageToken {
         }
     }
 }
+
+impl std::str::FromStr for SpotLeverageToken {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        SpotLeverageToken::ALL
+            .iter()
+            .find(|endpoint| endpoint.to_string() == s)
+            .copied()
+            .ok_or_else(|| AppError::InvalidParameter(format!("unknown endpoint {}", s)))
+    }
+}
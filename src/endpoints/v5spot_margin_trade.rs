@@ -1,3 +1,6 @@
+use crate::errors::app_error::AppError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum SpotMarginTrade {
     // UTA endpoints
     ToggleMarginTrade,
@@ -14,6 +17,22 @@ pub enum SpotMarginTrade {
     NormalToggleMarginTrade,
 }
 
+impl SpotMarginTrade {
+    pub const ALL: [SpotMarginTrade; 11] = [
+        SpotMarginTrade::ToggleMarginTrade,
+        SpotMarginTrade::SetLeverage,
+        SpotMarginTrade::NormalGetMarginCoinInfo,
+        SpotMarginTrade::NormalGetBorrowableCoinInfo,
+        SpotMarginTrade::NormalGetInterestQuota,
+        SpotMarginTrade::NormalGetLoanAccountInfo,
+        SpotMarginTrade::NormalBorrow,
+        SpotMarginTrade::NormalRepay,
+        SpotMarginTrade::NormalGetBorrowOrderDetail,
+        SpotMarginTrade::NormalGetRepaymentOrderDetail,
+        SpotMarginTrade::NormalToggleMarginTrade,
+    ];
+}
+
 impl std::fmt::Display for SpotMarginTrade {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
@@ -45,3 +64,15 @@ impl std::fmt::Display for SpotMarginTrade {
         }
     }
 }
+
+impl std::str::FromStr for SpotMarginTrade {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        SpotMarginTrade::ALL
+            .iter()
+            .find(|endpoint| endpoint.to_string() == s)
+            .copied()
+            .ok_or_else(|| AppError::InvalidParameter(format!("unknown endpoint {}", s)))
+    }
+}
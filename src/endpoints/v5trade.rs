@@ -1,3 +1,6 @@
+use crate::errors::app_error::AppError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Trade {
     PlaceOrder,
     AmendOrder,
@@ -12,6 +15,22 @@ pub enum Trade {
     SetDcp,
 }
 
+impl Trade {
+    pub const ALL: [Trade; 11] = [
+        Trade::PlaceOrder,
+        Trade::AmendOrder,
+        Trade::CancelOrder,
+        Trade::GetOpenOrders,
+        Trade::CancelAllOrders,
+        Trade::GetOrderHistory,
+        Trade::BatchPlaceOrder,
+        Trade::BatchAmendOrder,
+        Trade::BatchCancelOrder,
+        Trade::GetBorrowQuota,
+        Trade::SetDcp,
+    ];
+}
+
 impl std::fmt::Display for Trade {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
@@ -23,11 +42,21 @@ impl std::fmt::Display for Trade {
             Trade::GetOpenOrders => write!(f, "/v5/order/realtime"),
             Trade::CancelAllOrders => write!(f, "/v5/order/cancel-all"),
             Trade::GetOrderHistory => write!(f, "/v5/order/history"),
-            Trade::BatchPlaceOrder => write!(f, "/v5/order/create-batch"),
-            Trade::BatchAmendOrder => write!(f, "/v5/order/amend-batch"),
             Trade::BatchCancelOrder => write!(f, "/v5/order/cancel-batch"),
             Trade::GetBorrowQuota => write!(f, "/v5/order/spot-borrow-check"),
             Trade::SetDcp => write!(f, "/v5/order/disconnected-cancel-all"),
         }
     }
 }
+
+impl std::str::FromStr for Trade {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Trade::ALL
+            .iter()
+            .find(|endpoint| endpoint.to_string() == s)
+            .copied()
+            .ok_or_else(|| AppError::InvalidParameter(format!("unknown endpoint {}", s)))
+    }
+}
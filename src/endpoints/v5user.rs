@@ -1,6 +1,10 @@
+use crate::errors::app_error::AppError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum User {
     CreateSubUid,
     CreateSubApiKey,
+    GetSubAccountAllApiKeys,
     GetSubUidList,
     FreezeSubUid,
     GetApiKeyInformation,
@@ -10,11 +14,27 @@ pub enum User {
     DeleteSubApiKey,
 }
 
+impl User {
+    pub const ALL: [User; 10] = [
+        User::CreateSubUid,
+        User::CreateSubApiKey,
+        User::GetSubAccountAllApiKeys,
+        User::GetSubUidList,
+        User::FreezeSubUid,
+        User::GetApiKeyInformation,
+        User::ModifyMasterApiKey,
+        User::ModifySubApiKey,
+        User::DeleteMasterApiKey,
+        User::DeleteSubApiKey,
+    ];
+}
+
 impl std::fmt::Display for User {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             User::CreateSubUid => write!(f, "/v5/user/create-sub-member"),
             User::CreateSubApiKey => write!(f, "/v5/user/create-sub-api"),
+            User::GetSubAccountAllApiKeys => write!(f, "/v5/user/sub-apikeys"),
             User::GetSubUidList => write!(f, "/v5/user/query-sub-members"),
             User::FreezeSubUid => write!(f, "/v5/user/frozen-sub-member"),
             User::GetApiKeyInformation => write!(f, "/v5/user/query-api"),
@@ -25,3 +45,15 @@ impl std::fmt::Display for User {
         }
     }
 }
+
+impl std::str::FromStr for User {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        User::ALL
+            .iter()
+            .find(|endpoint| endpoint.to_string() == s)
+            .copied()
+            .ok_or_else(|| AppError::InvalidParameter(format!("unknown endpoint {}", s)))
+    }
+}
@@ -1,11 +1,107 @@
 use std::fmt;
 
+use crate::bybit::websocket_stream::SubscribeFailure;
+
+/// Bybit's retCode for an account or endpoint over its request limit.
+pub const RATE_LIMITED: i32 = 10006;
+
+/// Bybit's retCode for an internal error, sent while services restart.
+pub const SYSTEM_ERROR: i32 = 10016;
+
+/// Bybit's retCode for a signed request whose timestamp is outside the recv window.
+pub const REQUEST_EXPIRED: i32 = 10002;
+
+///
+/// Every error the crate returns, `bybit::Error` is an alias of it. Failures reported
+/// by Bybit itself keep their retCode, so they can be matched on:
+/// `Err(AppError::Api { ret_code: 110007, .. })` is an insufficient balance.
+///
 #[derive(Debug)]
 pub enum AppError {
     EnvVarMissing(String),
-    RequestError(reqwest::Error),
-    JsonError(serde_json::Error),
-    HmacError,
+    /// Transport failure: connection, TLS, or reading the response.
+    Http(reqwest::Error),
+    /// The request took longer than the client's timeout.
+    Timeout,
+    /// A response or push that doesn't have the expected shape.
+    Deserialize(serde_json::Error),
+    /// The HMAC signature could not be computed from the api secret.
+    Signature,
+    Api {
+        ret_code: i32,
+        ret_msg: String,
+    },
+    /// retCode 10006 or HTTP 429, wait for the window to reset before retrying.
+    RateLimited(String),
+    MissingPermission(String),
+    InvalidParameter(String),
+    Blocked {
+        status: u16,
+        snippet: String,
+    },
+    /// Bybit is down for maintenance or restarting: an HTML 503 page or retCode 10016.
+    /// Back off and retry later.
+    SystemMaintenance(String),
+    /// A response that isn't JSON and isn't a known block or maintenance page.
+    UnexpectedResponse {
+        status: u16,
+        snippet: String,
+    },
+    SymbolNotAllowed(String),
+    /// The orderLinkId was already used, by this process or by an order Bybit still
+    /// knows about. Generate a new one and retry.
+    DuplicateOrderLinkId(String),
+    /// retCode 10002 again after HttpManager::with_expired_retry synced the clock and
+    /// widened the recv window. `clock_offset_ms` is the offset measured for the
+    /// retry, server minus local.
+    RequestExpired {
+        ret_msg: String,
+        clock_offset_ms: i64,
+        recv_window: u64,
+    },
+    RetriesExhausted {
+        retries: u32,
+        last_error: String,
+    },
+    MissingCredentials,
+    /// Query parameters that can't be form encoded.
+    Encoding(serde_urlencoded::ser::Error),
+    /// Reading or writing a local file, e.g. a kline export.
+    Io(std::io::Error),
+    /// The system clock is before the unix epoch, so no request timestamp can be made.
+    Clock(std::time::SystemTimeError),
+    WebSocket(tokio_tungstenite::tungstenite::Error),
+    /// The proxy refused or failed to open a tunnel for a WebSocket connection.
+    Proxy(String),
+    WebSocketClosed(String),
+    WebSocketAuth(String),
+    Subscribe(SubscribeFailure),
+}
+
+impl AppError {
+    /// The retCode of an error reported by Bybit.
+    pub fn ret_code(&self) -> Option<i32> {
+        match self {
+            AppError::Api { ret_code, .. } => Some(*ret_code),
+            AppError::RateLimited(_) => Some(RATE_LIMITED),
+            AppError::RequestExpired { .. } => Some(REQUEST_EXPIRED),
+            _ => None,
+        }
+    }
+
+    /// Whether sending the same request again later may succeed.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            AppError::Timeout
+            | AppError::RateLimited(_)
+            | AppError::SystemMaintenance(_)
+            | AppError::WebSocketClosed(_) => true,
+            AppError::Http(err) => err.is_connect() || err.is_request(),
+            AppError::WebSocket(_) => true,
+            AppError::Subscribe(SubscribeFailure::Transient(_)) => true,
+            _ => false,
+        }
+    }
 }
 
 impl fmt::Display for AppError {
@@ -14,21 +110,124 @@ impl fmt::Display for AppError {
             AppError::EnvVarMissing(var_name) => {
                 write!(f, "{} environment variable not set", var_name)
             }
-            AppError::RequestError(err) => write!(f, "Request error: {}", err),
-            AppError::JsonError(err) => write!(f, "JSON error: {}", err),
-            AppError::HmacError => write!(f, "HMAC creation error"),
+            AppError::Http(err) => write!(f, "Request error: {}", err),
+            AppError::Timeout => write!(f, "Request timed out"),
+            AppError::Deserialize(err) => write!(f, "JSON error: {}", err),
+            AppError::Signature => write!(f, "HMAC creation error"),
+            AppError::Api { ret_code, ret_msg } => {
+                write!(f, "API error {}: {}", ret_code, ret_msg)
+            }
+            AppError::RateLimited(msg) => write!(f, "Rate limited: {}", msg),
+            AppError::MissingPermission(permission) => {
+                write!(f, "API key is missing the {} permission", permission)
+            }
+            AppError::InvalidParameter(msg) => write!(f, "Invalid parameter: {}", msg),
+            AppError::RequestExpired {
+                ret_msg,
+                clock_offset_ms,
+                recv_window,
+            } => write!(
+                f,
+                "Request expired after a clock sync (offset {}ms, recv window {}ms): {}",
+                clock_offset_ms, recv_window, ret_msg
+            ),
+            AppError::RetriesExhausted {
+                retries,
+                last_error,
+            } => write!(
+                f,
+                "Request still failing after {} retries: {}",
+                retries, last_error
+            ),
+            AppError::SystemMaintenance(msg) => write!(f, "System maintenance: {}", msg),
+            AppError::UnexpectedResponse { status, snippet } => write!(
+                f,
+                "Unexpected non-JSON response (HTTP {}): {}",
+                status, snippet
+            ),
+            AppError::SymbolNotAllowed(symbol) => {
+                write!(f, "Symbol {} is not allowed by the symbol filter", symbol)
+            }
+            AppError::DuplicateOrderLinkId(id) => {
+                write!(f, "orderLinkId {} was already used", id)
+            }
+            AppError::MissingCredentials => {
+                write!(f, "Signed endpoints require an API key and secret")
+            }
+            AppError::Encoding(err) => write!(f, "Query encoding error: {}", err),
+            AppError::Io(err) => write!(f, "IO error: {}", err),
+            AppError::Clock(err) => write!(f, "System clock error: {}", err),
+            AppError::WebSocket(err) => write!(f, "WebSocket error: {}", err),
+            AppError::Proxy(msg) => write!(f, "Proxy error: {}", msg),
+            AppError::WebSocketClosed(msg) => write!(f, "WebSocket closed: {}", msg),
+            AppError::WebSocketAuth(msg) => write!(f, "WebSocket authentication failed: {}", msg),
+            AppError::Subscribe(failure) => write!(f, "{}", failure),
+            AppError::Blocked { status, snippet } => write!(
+                f,
+                "Request blocked before reaching the API (HTTP {}), likely a CDN rate or geo block: {}",
+                status, snippet
+            ),
+        }
+    }
+}
+
+impl std::error::Error for AppError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AppError::Http(err) => Some(err),
+            AppError::Deserialize(err) => Some(err),
+            AppError::Encoding(err) => Some(err),
+            AppError::Io(err) => Some(err),
+            AppError::Clock(err) => Some(err),
+            AppError::WebSocket(err) => Some(err),
+            AppError::Subscribe(failure) => Some(failure),
+            _ => None,
         }
     }
 }
 
 impl From<reqwest::Error> for AppError {
     fn from(err: reqwest::Error) -> Self {
-        AppError::RequestError(err)
+        if err.is_timeout() {
+            AppError::Timeout
+        } else {
+            AppError::Http(err)
+        }
     }
 }
 
 impl From<serde_json::Error> for AppError {
     fn from(err: serde_json::Error) -> Self {
-        AppError::JsonError(err)
+        AppError::Deserialize(err)
+    }
+}
+
+impl From<serde_urlencoded::ser::Error> for AppError {
+    fn from(err: serde_urlencoded::ser::Error) -> Self {
+        AppError::Encoding(err)
+    }
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(err: std::io::Error) -> Self {
+        AppError::Io(err)
+    }
+}
+
+impl From<std::time::SystemTimeError> for AppError {
+    fn from(err: std::time::SystemTimeError) -> Self {
+        AppError::Clock(err)
+    }
+}
+
+impl From<tokio_tungstenite::tungstenite::Error> for AppError {
+    fn from(err: tokio_tungstenite::tungstenite::Error) -> Self {
+        AppError::WebSocket(err)
+    }
+}
+
+impl From<SubscribeFailure> for AppError {
+    fn from(failure: SubscribeFailure) -> Self {
+        AppError::Subscribe(failure)
     }
 }
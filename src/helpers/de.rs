@@ -0,0 +1,291 @@
+use rust_decimal::Decimal;
+use serde::{Deserialize, Deserializer};
+use std::str::FromStr;
+
+use crate::bybit::types::Number;
+
+/// Bybit sends numbers as strings, but a few fields come through as plain JSON numbers.
+#[derive(serde_derive::Deserialize)]
+#[serde(untagged)]
+enum StringOrNumber {
+    String(String),
+    Number(serde_json::Number),
+}
+
+fn parse_f64<E: serde::de::Error>(value: StringOrNumber) -> Result<Option<f64>, E> {
+    match value {
+        StringOrNumber::String(s) if s.is_empty() => Ok(None),
+        StringOrNumber::String(s) => s.parse().map(Some).map_err(E::custom),
+        StringOrNumber::Number(n) => Ok(n.as_f64()),
+    }
+}
+
+///
+/// Deserializes a numeric string into f64, `""` becomes 0.
+///
+pub fn string_as_f64<'de, D>(deserializer: D) -> Result<f64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(parse_f64(StringOrNumber::deserialize(deserializer)?)?.unwrap_or_default())
+}
+
+///
+/// Deserializes a numeric string into `Option<f64>`, `""` and null become None.
+///
+pub fn string_as_option_f64<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<StringOrNumber>::deserialize(deserializer)? {
+        Some(value) => parse_f64(value),
+        None => Ok(None),
+    }
+}
+
+#[cfg(not(feature = "decimal"))]
+fn parse_number<E: serde::de::Error>(value: StringOrNumber) -> Result<Option<Number>, E> {
+    parse_f64(value)
+}
+
+#[cfg(feature = "decimal")]
+fn parse_number<E: serde::de::Error>(value: StringOrNumber) -> Result<Option<Number>, E> {
+    parse_decimal(value)
+}
+
+fn parse_decimal<E: serde::de::Error>(value: StringOrNumber) -> Result<Option<Decimal>, E> {
+    let text = match value {
+        StringOrNumber::String(s) if s.is_empty() => return Ok(None),
+        StringOrNumber::String(s) => s,
+        StringOrNumber::Number(n) => n.to_string(),
+    };
+    Decimal::from_str(&text)
+        .or_else(|_| Decimal::from_scientific(&text))
+        .map(Some)
+        .map_err(E::custom)
+}
+
+///
+/// Deserializes a numeric string into `Decimal` whatever the `decimal` feature,
+/// for values that must stay exact such as order book levels. `""` becomes 0.
+///
+pub fn string_as_decimal<'de, D>(deserializer: D) -> Result<Decimal, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(parse_decimal(StringOrNumber::deserialize(deserializer)?)?.unwrap_or_default())
+}
+
+///
+/// Deserializes a numeric string into `Option<Decimal>`, `""` and null become None.
+///
+pub fn string_as_option_decimal<'de, D>(deserializer: D) -> Result<Option<Decimal>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<StringOrNumber>::deserialize(deserializer)? {
+        Some(value) => parse_decimal(value),
+        None => Ok(None),
+    }
+}
+
+///
+/// Parses one numeric string the way string_as_decimal does, for values nested in
+/// arrays that `deserialize_with` can't reach.
+///
+pub fn parse_decimal_str<E: serde::de::Error>(value: &str) -> Result<Decimal, E> {
+    Ok(parse_decimal(StringOrNumber::String(value.to_string()))?.unwrap_or_default())
+}
+
+///
+/// Deserializes a numeric price, size or quantity into `Number`, `""` becomes 0.
+/// With the `decimal` feature the string is parsed exactly.
+///
+pub fn string_as_number<'de, D>(deserializer: D) -> Result<Number, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(parse_number(StringOrNumber::deserialize(deserializer)?)?.unwrap_or_default())
+}
+
+///
+/// Deserializes a numeric price, size or quantity into `Option<Number>`,
+/// `""` and null become None.
+///
+pub fn string_as_option_number<'de, D>(deserializer: D) -> Result<Option<Number>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<StringOrNumber>::deserialize(deserializer)? {
+        Some(value) => parse_number(value),
+        None => Ok(None),
+    }
+}
+
+///
+/// Deserializes a numeric string such as a ms timestamp into u64, `""` becomes 0.
+///
+pub fn string_as_u64<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match StringOrNumber::deserialize(deserializer)? {
+        StringOrNumber::String(s) if s.is_empty() => Ok(0),
+        StringOrNumber::String(s) => s.parse().map_err(serde::de::Error::custom),
+        StringOrNumber::Number(n) => n
+            .as_u64()
+            .ok_or_else(|| serde::de::Error::custom("expected an unsigned integer")),
+    }
+}
+
+///
+/// Deserializes a numeric string into `Option<u64>`, `""` and null become None.
+///
+pub fn string_as_option_u64<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<StringOrNumber>::deserialize(deserializer)? {
+        Some(StringOrNumber::String(s)) if s.is_empty() => Ok(None),
+        Some(StringOrNumber::String(s)) => s.parse().map(Some).map_err(serde::de::Error::custom),
+        Some(StringOrNumber::Number(n)) => Ok(n.as_u64()),
+        None => Ok(None),
+    }
+}
+
+///
+/// Deserializes a numeric string into u128, used for nanosecond timestamps.
+///
+pub fn string_as_u128<'de, D>(deserializer: D) -> Result<u128, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match StringOrNumber::deserialize(deserializer)? {
+        StringOrNumber::String(s) => s.parse().map_err(serde::de::Error::custom),
+        StringOrNumber::Number(n) => n
+            .as_u64()
+            .map(u128::from)
+            .ok_or_else(|| serde::de::Error::custom("expected an unsigned integer")),
+    }
+}
+
+///
+/// Deserializes an optional enum or string, `""` and null become None. Bybit sends
+/// an empty timeInForce or orderType where the field doesn't apply. Numbers have
+/// their own helpers, string_as_option_number and friends.
+///
+pub fn empty_string_as_none<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: serde::de::DeserializeOwned,
+{
+    use serde::de::IntoDeserializer;
+
+    match Option::<String>::deserialize(deserializer)? {
+        Some(s) if s.is_empty() => Ok(None),
+        Some(s) => T::deserialize(IntoDeserializer::<D::Error>::into_deserializer(s)).map(Some),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    fn number(value: &str) -> Number {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn floats() {
+        assert_eq!(string_as_f64(json!("")).unwrap(), 0.0);
+        assert_eq!(string_as_f64(json!("0")).unwrap(), 0.0);
+        assert_eq!(string_as_f64(json!("0.0001")).unwrap(), 0.0001);
+        assert_eq!(string_as_f64(json!(25)).unwrap(), 25.0);
+        assert!(string_as_f64(json!("1,5")).is_err());
+        assert_eq!(string_as_option_f64(json!("")).unwrap(), None);
+        assert_eq!(string_as_option_f64(Value::Null).unwrap(), None);
+        assert_eq!(string_as_option_f64(json!("0")).unwrap(), Some(0.0));
+        assert_eq!(string_as_option_f64(json!("-0.25")).unwrap(), Some(-0.25));
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(string_as_number(json!("")).unwrap(), number("0"));
+        assert_eq!(string_as_number(json!("0")).unwrap(), number("0"));
+        assert_eq!(
+            string_as_number(json!("16597.50")).unwrap(),
+            number("16597.5")
+        );
+        assert_eq!(string_as_option_number(json!("")).unwrap(), None);
+        assert_eq!(string_as_option_number(Value::Null).unwrap(), None);
+        assert_eq!(
+            string_as_option_number(json!("0")).unwrap(),
+            Some(number("0"))
+        );
+        assert_eq!(
+            string_as_option_number(json!("0.001")).unwrap(),
+            Some(number("0.001"))
+        );
+    }
+
+    #[test]
+    fn decimals() {
+        assert_eq!(string_as_decimal(json!("")).unwrap(), Decimal::ZERO);
+        assert_eq!(string_as_decimal(json!("0")).unwrap(), Decimal::ZERO);
+        assert_eq!(
+            string_as_decimal(json!("0.10")).unwrap(),
+            Decimal::new(1, 1)
+        );
+        assert_eq!(
+            string_as_decimal(json!("1.5731E7")).unwrap(),
+            Decimal::from(15_731_000)
+        );
+        assert_eq!(string_as_option_decimal(json!("")).unwrap(), None);
+        assert_eq!(
+            string_as_option_decimal(json!("0")).unwrap(),
+            Some(Decimal::ZERO)
+        );
+        assert_eq!(
+            parse_decimal_str::<serde_json::Error>("27000.5").unwrap(),
+            Decimal::new(270005, 1)
+        );
+    }
+
+    #[test]
+    fn integers() {
+        assert_eq!(string_as_u64(json!("")).unwrap(), 0);
+        assert_eq!(string_as_u64(json!("0")).unwrap(), 0);
+        assert_eq!(
+            string_as_u64(json!("1672364174443")).unwrap(),
+            1672364174443
+        );
+        assert!(string_as_u64(json!("-1")).is_err());
+        assert_eq!(string_as_option_u64(json!("")).unwrap(), None);
+        assert_eq!(string_as_option_u64(json!("0")).unwrap(), Some(0));
+        assert_eq!(string_as_option_u64(json!(480)).unwrap(), Some(480));
+        assert_eq!(string_as_u128(json!("0")).unwrap(), 0);
+        assert_eq!(
+            string_as_u128(json!("1688639403423213947")).unwrap(),
+            1688639403423213947
+        );
+        assert!(string_as_u128(json!("")).is_err());
+    }
+
+    #[test]
+    fn empty_strings_as_none() {
+        use crate::bybit::types::TimeInForce;
+
+        let none: Option<TimeInForce> = empty_string_as_none(json!("")).unwrap();
+        assert_eq!(none, None);
+        let none: Option<TimeInForce> = empty_string_as_none(Value::Null).unwrap();
+        assert_eq!(none, None);
+        let gtc: Option<TimeInForce> = empty_string_as_none(json!("GTC")).unwrap();
+        assert_eq!(gtc, Some(TimeInForce::Gtc));
+        let zero: Option<String> = empty_string_as_none(json!("0")).unwrap();
+        assert_eq!(zero.as_deref(), Some("0"));
+        assert!(empty_string_as_none::<_, TimeInForce>(json!("GTD")).is_err());
+    }
+}
@@ -1 +1,4 @@
+pub mod de;
+pub mod ser;
+pub mod symbols;
 pub mod utils;
@@ -0,0 +1,27 @@
+use serde::Serializer;
+
+use crate::bybit::types::{self, Number};
+
+///
+/// Serializes a Number as the decimal string Bybit expects, see types::format_number.
+///
+pub fn number_as_string<S>(number: &Number, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&types::format_number(*number))
+}
+
+///
+/// Serializes `Option<Number>` as a decimal string, pair with
+/// `skip_serializing_if = "Option::is_none"`.
+///
+pub fn option_number_as_string<S>(number: &Option<Number>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match number {
+        Some(number) => number_as_string(number, serializer),
+        None => serializer.serialize_none(),
+    }
+}
@@ -0,0 +1,147 @@
+use crate::{
+    bybit::{types::Category, Result},
+    errors::app_error::AppError,
+};
+
+const MONTHS: [&str; 12] = [
+    "JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC",
+];
+
+/// Quote coins tried, in order, when a symbol has to be split without instrument metadata.
+const QUOTE_COINS: [&str; 8] = ["USDT", "USDC", "USDE", "USD", "EUR", "BRL", "BTC", "ETH"];
+
+///
+/// Splits a concatenated symbol such as BTCUSDT into ("BTC", "USDT") by its quote coin suffix.
+/// Only a best effort, instrument metadata should be preferred when loaded.
+///
+pub fn split_symbol(symbol: &str) -> Option<(String, String)> {
+    QUOTE_COINS.iter().find_map(|quote| {
+        symbol
+            .strip_suffix(quote)
+            .filter(|base| !base.is_empty())
+            .map(|base| (base.to_string(), quote.to_string()))
+    })
+}
+
+///
+/// Bybit option symbols carry the expiry as DDMMMYY (BTC-29MAR24-70000-C),
+/// the unified form uses YYMMDD (BTC-240329-70000-C). An optional settle coin
+/// suffix (-USDT) is kept as is. Returns None for anything that isn't an option symbol.
+///
+pub fn option_to_unified(symbol: &str) -> Option<String> {
+    let mut parts: Vec<&str> = symbol.split('-').collect();
+    if parts.len() < 4 {
+        return None;
+    }
+    let expiry = parts[1];
+    let (day, rest) = expiry.split_at(expiry.len().checked_sub(5)?);
+    let (month, year) = rest.split_at(3);
+    let month = MONTHS.iter().position(|m| *m == month)? + 1;
+    let day: u32 = day.parse().ok()?;
+    let converted = format!("{}{:02}{:02}", year, month, day);
+    parts[1] = &converted;
+    Some(parts.join("-"))
+}
+
+///
+/// Reverse of option_to_unified: BTC-240329-70000-C -> BTC-29MAR24-70000-C.
+///
+pub fn option_from_unified(unified: &str) -> Option<String> {
+    let mut parts: Vec<&str> = unified.split('-').collect();
+    if parts.len() < 4 || parts[1].len() != 6 || !parts[1].chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let expiry = parts[1];
+    let month: usize = expiry[2..4].parse().ok()?;
+    let day: u32 = expiry[4..6].parse().ok()?;
+    let converted = format!(
+        "{}{}{}",
+        day,
+        MONTHS.get(month.checked_sub(1)?)?,
+        &expiry[0..2]
+    );
+    parts[1] = &converted;
+    Some(parts.join("-"))
+}
+
+///
+/// Settle coin of a derivatives symbol:
+/// linear BTCUSDT -> USDT, USDC contracts (BTCPERP, BTCUSDC, BTC-29MAR24) -> USDC,
+/// inverse BTCUSD / BTCUSDH24 -> BTC (coin margined). None for other categories.
+///
+pub fn infer_settle_coin(category: &str, symbol: &str) -> Option<String> {
+    match category {
+        "linear" if symbol.ends_with("USDT") => Some("USDT".to_string()),
+        "linear"
+            if symbol.ends_with("PERP") || symbol.ends_with("USDC") || symbol.contains('-') =>
+        {
+            Some("USDC".to_string())
+        }
+        "inverse" => symbol
+            .find("USD")
+            .filter(|i| *i > 0)
+            .map(|i| symbol[..i].to_string()),
+        _ => None,
+    }
+}
+
+///
+/// Checks the symbol / baseCoin / settleCoin filters of a list or cancel-all query
+/// before it's sent, failing with `AppError::InvalidParameter`:
+///
+/// - `symbol` and `settle_coin` together: Bybit either rejects the pair or silently
+///   drops the coin, depending on the endpoint.
+/// - `base_coin` outside `base_coin_categories`.
+/// - `settle_coin` on spot, whose instruments have no settle coin.
+///
+pub(crate) fn check_coin_filters(
+    category: Category,
+    symbol: Option<&str>,
+    base_coin: Option<&str>,
+    settle_coin: Option<&str>,
+    base_coin_categories: &[Category],
+) -> Result<()> {
+    if let (Some(symbol), Some(settle_coin)) = (symbol, settle_coin) {
+        return Err(AppError::InvalidParameter(format!(
+            "symbol {} and settleCoin {} can't be combined, pass one of them",
+            symbol, settle_coin
+        )));
+    }
+    if base_coin.is_some() && !base_coin_categories.contains(&category) {
+        return Err(AppError::InvalidParameter(format!(
+            "baseCoin is not supported for {}",
+            category
+        )));
+    }
+    if settle_coin.is_some() && category == Category::Spot {
+        return Err(AppError::InvalidParameter(
+            "settleCoin is not supported for spot".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+///
+/// Fills in `settleCoin` for linear filter queries (cancel all, open orders, position
+/// list) that pass none of symbol, baseCoin and settleCoin, which Bybit would reject.
+/// A settleCoin set by the caller always wins, and nothing is added next to a symbol
+/// since some endpoints refuse symbol and settleCoin together.
+///
+pub fn apply_default_settle_coin(
+    query: &mut std::collections::HashMap<String, String>,
+    default_settle_coin: Option<&str>,
+) {
+    let default_settle_coin = match default_settle_coin {
+        Some(coin) => coin,
+        None => return,
+    };
+    if query.get("category").map(String::as_str) != Some("linear") {
+        return;
+    }
+    let has_filter = ["symbol", "baseCoin", "settleCoin"]
+        .iter()
+        .any(|key| query.get(*key).map_or(false, |value| !value.is_empty()));
+    if !has_filter {
+        query.insert("settleCoin".to_string(), default_settle_coin.to_string());
+    }
+}
@@ -1,7 +1,8 @@
-use hmac::{Hmac, Mac};
+use hmac::{Hmac, KeyInit, Mac};
 use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+use serde_json::Value;
 use sha2::Sha256;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::time::{SystemTime, UNIX_EPOCH};
 use url::form_urlencoded::{self, Serializer};
 
@@ -15,13 +16,22 @@ pub fn generate_timestamp() -> Result<u128, std::time::SystemTimeError> {
     Ok(d.as_millis())
 }
 
+///
+/// generate_timestamp corrected by `offset_ms`, server minus local time as measured
+/// by HttpManager::sync_clock.
+///
+pub fn generate_timestamp_with_offset(offset_ms: i64) -> Result<u128, std::time::SystemTimeError> {
+    let local = generate_timestamp()? as i128;
+    Ok((local + offset_ms as i128).max(0) as u128)
+}
+
 ///
 ///
 ///
 ///
 pub fn sign_query_string(query_string: &str, secret: &str) -> Result<String, AppError> {
     let mut mac =
-        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).map_err(|_| AppError::HmacError)?;
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).map_err(|_| AppError::Signature)?;
     mac.update(query_string.as_bytes());
     Ok(bytes_to_hex(mac.finalize().into_bytes().to_vec()))
 }
@@ -69,3 +79,134 @@ pub fn build_private_headers(
 
     headers
 }
+
+///
+/// Random (version 4) UUID in its hyphenated form, as Bybit expects for transferId.
+///
+pub fn generate_uuid() -> String {
+    use ring::rand::SecureRandom;
+
+    let mut bytes = [0u8; 16];
+    ring::rand::SystemRandom::new()
+        .fill(&mut bytes)
+        .expect("system random number generator failed");
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = hex::encode(bytes);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+///
+/// Number of decimals of a tick size or qty step as Bybit sends it, e.g. "0.010" -> 2.
+///
+pub fn step_decimals(step: &str) -> u32 {
+    match step.split_once('.') {
+        Some((_, fraction)) => fraction.trim_end_matches('0').len() as u32,
+        None => 0,
+    }
+}
+
+///
+/// Formats a price or quantity for a query string with at most `decimals` decimals
+/// and no trailing zeros, so float artifacts like 0.30000000000000004 are sent as "0.3".
+///
+pub fn format_number(value: f64, decimals: u32) -> String {
+    let formatted = format!("{:.*}", decimals as usize, value);
+    let trimmed = if formatted.contains('.') {
+        formatted.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        formatted.as_str()
+    };
+    match trimmed {
+        "-0" => "0".to_string(),
+        _ => trimmed.to_string(),
+    }
+}
+
+///
+/// Formats a value with the precision of an instrument tick size or qty step,
+/// e.g. `format_to_step(25000.123456, "0.10")` -> "25000.1".
+///
+pub fn format_to_step(value: f64, step: &str) -> String {
+    format_number(value, step_decimals(step))
+}
+
+///
+/// Fields never converted by `convert_numeric_strings`: identifiers and free text that
+/// can look numeric (a numeric orderLinkId, a uid, an address tag) but must stay strings.
+/// Keys ending in `Id`, `ID` or `Cursor` are skipped as well.
+///
+pub const NUMERIC_STRING_EXCLUSIONS: [&str; 10] = [
+    "id", "uid", "uuid", "apiKey", "symbol", "coin", "note", "address", "tag", "chain",
+];
+
+fn is_numeric(s: &str) -> bool {
+    !s.is_empty()
+        && s.bytes().any(|b| b.is_ascii_digit())
+        && s.bytes()
+            .enumerate()
+            .all(|(i, b)| b.is_ascii_digit() || b == b'.' || (b == b'-' && i == 0))
+}
+
+///
+/// Turns numeric strings in object fields into JSON numbers, recursively, so that
+/// `value["result"]["list"][0]["lastPrice"].as_f64()` works on raw responses.
+///
+/// Additional information:
+///     Integers become u64 / i64, anything with a fraction an f64, so decimals beyond
+///     f64 precision are rounded.
+///     Keys in `exclude`, ending in `Id`, `ID` or `Cursor` keep their string values:
+///     ids like orderId or a numeric orderLinkId would otherwise lose leading zeros or
+///     precision. Strings directly inside arrays (kline rows, price levels) are not
+///     converted, only values of object fields.
+///
+pub fn convert_numeric_strings(value: &mut Value, exclude: &HashSet<String>) {
+    match value {
+        Value::Object(fields) => {
+            for (key, field) in fields.iter_mut() {
+                let skip = exclude.contains(key)
+                    || key.ends_with("Id")
+                    || key.ends_with("ID")
+                    || key.ends_with("Cursor");
+                match field {
+                    Value::String(s) if !skip && is_numeric(s) => {
+                        let number = s
+                            .parse::<u64>()
+                            .map(Value::from)
+                            .or_else(|_| s.parse::<i64>().map(Value::from))
+                            .ok()
+                            .or_else(|| {
+                                s.parse::<f64>()
+                                    .ok()
+                                    .and_then(serde_json::Number::from_f64)
+                                    .map(Value::Number)
+                            });
+                        if let Some(number) = number {
+                            *field = number;
+                        }
+                    }
+                    Value::Object(_) | Value::Array(_) if !skip => {
+                        convert_numeric_strings(field, exclude)
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Value::Array(items) => {
+            for item in items
+                .iter_mut()
+                .filter(|item| item.is_object() || item.is_array())
+            {
+                convert_numeric_strings(item, exclude);
+            }
+        }
+        _ => {}
+    }
+}
@@ -1,6 +1,12 @@
 #![allow(unused)]
+#![allow(
+    clippy::empty_docs,
+    clippy::empty_line_after_outer_attr,
+    clippy::empty_line_after_doc_comments
+)]
 
 pub mod bybit;
 pub mod endpoints;
 pub mod errors;
 pub mod helpers;
+pub mod test_util;
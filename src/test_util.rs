@@ -0,0 +1,168 @@
+use async_trait::async_trait;
+use reqwest::Method;
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Mutex,
+};
+
+use crate::{
+    bybit::http_manager::{HTTPManagerResult, Manager},
+    endpoints::Endpoint,
+    errors::app_error::AppError,
+};
+
+/// A request received by MockManager.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedRequest {
+    pub method: Method,
+    pub path: String,
+    pub query: HashMap<String, String>,
+    pub auth: bool,
+}
+
+impl RecordedRequest {
+    /// The endpoint requested, None for a path the crate doesn't know.
+    pub fn endpoint(&self) -> Option<Endpoint> {
+        self.path.parse().ok()
+    }
+}
+
+///
+/// Manager answering requests with preset bodies instead of sending them, for tests
+/// of code built on the endpoint clients:
+///
+/// ```text
+/// let manager = MockManager::new().with_response(
+///     MarketEnum::GetServerTime,
+///     &[],
+///     json!({ "retCode": 0, "retMsg": "OK", "result": { "timeSecond": "1700000000", "timeNano": "1700000000000000000" } }),
+/// );
+/// let market = MarketHTTP::with_manager(Arc::new(manager));
+/// ```
+///
+/// Paths are given as endpoint enums or as strings.
+/// Bodies are full responses, envelope included, and are returned as they are like
+/// HttpManager does: a non-zero retCode is turned into `AppError::Api` by the typed
+/// methods, not here. A request without a preset body fails with
+/// `AppError::InvalidParameter`.
+///
+#[derive(Default)]
+pub struct MockManager {
+    responses: HashMap<(String, BTreeMap<String, String>), Value>,
+    path_responses: HashMap<String, Value>,
+    requests: Mutex<Vec<RecordedRequest>>,
+}
+
+impl MockManager {
+    pub fn new() -> Self {
+        MockManager::default()
+    }
+
+    ///
+    /// Answers requests to `path` with exactly this query with `body`.
+    ///
+    pub fn with_response(
+        mut self,
+        path: impl std::fmt::Display,
+        query: &[(&str, &str)],
+        body: Value,
+    ) -> Self {
+        let query = query
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        self.responses.insert((path.to_string(), query), body);
+        self
+    }
+
+    ///
+    /// Answers requests to `path` whose query has no preset body with `body`.
+    ///
+    pub fn with_path_response(mut self, path: impl std::fmt::Display, body: Value) -> Self {
+        self.path_responses.insert(path.to_string(), body);
+        self
+    }
+
+    /// Every request received so far, oldest first.
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    fn respond(
+        &self,
+        method: Method,
+        path: &str,
+        query: HashMap<String, String>,
+        auth: bool,
+    ) -> HTTPManagerResult<Value> {
+        let key = (
+            path.to_string(),
+            query
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
+        );
+        let body = self
+            .responses
+            .get(&key)
+            .or_else(|| self.path_responses.get(path))
+            .cloned();
+        self.requests.lock().unwrap().push(RecordedRequest {
+            method,
+            path: path.to_string(),
+            query,
+            auth,
+        });
+        body.ok_or_else(|| {
+            AppError::InvalidParameter(format!("no mock response for {} {:?}", key.0, key.1))
+        })
+    }
+}
+
+#[async_trait]
+impl Manager for MockManager {
+    async fn auth(
+        &self,
+        req_params: &BTreeMap<String, String>,
+        _recv_window: u64,
+        _timestamp: u128,
+    ) -> Result<String, String> {
+        serde_urlencoded::to_string(req_params).map_err(|e| format!("Error: {:?}", e))
+    }
+
+    async fn submit_request(
+        &self,
+        method: Method,
+        path: &str,
+        query: HashMap<String, String>,
+        auth: bool,
+    ) -> HTTPManagerResult<Value> {
+        self.respond(method, path, query, auth)
+    }
+
+    ///
+    /// Records the fields of `json_input` as the query, which must be a JSON object
+    /// of strings to match a with_response entry.
+    ///
+    async fn submit_post_request<T: Serialize + Send>(
+        &self,
+        method: Method,
+        path: &str,
+        auth: bool,
+        json_input: T,
+    ) -> HTTPManagerResult<Value> {
+        let query = match serde_json::to_value(json_input)? {
+            Value::Object(fields) => fields
+                .into_iter()
+                .map(|(key, value)| match value {
+                    Value::String(value) => (key, value),
+                    value => (key, value.to_string()),
+                })
+                .collect(),
+            _ => HashMap::new(),
+        };
+        self.respond(method, path, query, auth)
+    }
+}